@@ -1,6 +1,11 @@
 use clap::Parser;
 use std::io::{self, Read};
-use anyhow::Result;
+use anyhow::{bail, Result};
+use emasm_common::{
+    disasm::disassemble,
+    eof::{EofContainer, MAGIC},
+    eof_verify::validate_eof,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "edisasm")]
@@ -13,21 +18,51 @@ struct Args {
     /// Input format: hex or bin
     #[arg(short, long, default_value = "hex")]
     format: String,
+
+    /// Validate an EOF container's code sections and fail on any issue
+    #[arg(long)]
+    validate: bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
     
-    let input = if args.input == "-" {
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer)?;
-        buffer
+    let mut input = Vec::new();
+    if args.input == "-" {
+        io::stdin().read_to_end(&mut input)?;
     } else {
-        std::fs::read_to_string(&args.input)?
+        input = std::fs::read(&args.input)?;
+    }
+    let bytecode = match args.format.as_str() {
+        "hex" => {
+            let text = String::from_utf8(input)?;
+            let digits = text.trim();
+            hex::decode(digits.strip_prefix("0x").unwrap_or(digits))?
+        }
+        "bin" => input,
+        other => bail!("unknown input format: {}", other),
     };
-    
-    println!("edisasm-cli: Disassembly functionality coming soon!");
-    println!("Input length: {} bytes", input.len());
+
+    // EOF containers print section by section, legacy code as one listing
+    if bytecode.starts_with(&MAGIC) {
+        let container = EofContainer::decode(&bytecode)?;
+        print!("{}", container);
+        if args.validate {
+            let issues = validate_eof(&container);
+            for issue in &issues {
+                eprintln!("{}", issue);
+            }
+            if !issues.is_empty() {
+                bail!("{} EOF validation errors", issues.len());
+            }
+        }
+    } else if args.validate {
+        bail!("--validate needs an EOF container");
+    } else {
+        for instruction in disassemble(&bytecode) {
+            println!("{}", instruction);
+        }
+    }
     
     Ok(())
 }
@@ -1,33 +1,378 @@
-use clap::Parser;
-use std::io::{self, Read};
-use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Result};
+use alloy_primitives::{Address, B256};
+use emasm::testing::{
+    revm::primitives,
+    snapshot::{Scenario, Snapshot},
+    Runner, Tracer, Tx, DEFAULT_CALLER,
+};
+use emasm_common::{
+    create2::{create2_address, initcode_hash, mine_salt, AddressPattern, SaltSearch},
+    source::{parse_abi, parse_eof_program_in, parse_program_in},
+    AsmElement,
+    Assembler,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "emasm")]
 #[command(about = "EVM Assembler CLI", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Input file (use - for stdin)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input file with a JSON program (use - for stdin)
     #[arg(default_value = "-")]
     input: String,
-    
-    /// Output format: hex, bin, or both
+
+    /// Output format: hex or bin
     #[arg(short, long, default_value = "hex")]
     format: String,
+
+    /// Remove unreachable code and unused segments before assembling
+    #[arg(long)]
+    eliminate_dead_code: bool,
+
+    /// Reorder segments to shorten label pushes and drop jumps to the next segment
+    #[arg(long)]
+    reorder_segments: bool,
+
+    /// Share identical segments and instruction tails through jumps
+    #[arg(long)]
+    merge_tails: bool,
+
+    /// Write the ABI JSON of the program's dispatcher to this file
+    #[arg(long)]
+    abi: Option<String>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    
-    let input = if args.input == "-" {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Estimate gas per segment and per acyclic path
+    Gas {
+        /// Input file with a JSON program (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+    },
+    /// Assemble an EOF v1 container from a JSON list of functions
+    Eof {
+        /// Input file with a JSON EOF program (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+        /// Output format: hex or bin
+        #[arg(short, long, default_value = "hex")]
+        format: String,
+    },
+    /// CREATE2 initcode hashes, addresses and salt mining
+    Create2 {
+        #[command(subcommand)]
+        command: Create2Command,
+    },
+    /// Run the program in a local EVM and print every executed instruction
+    Trace {
+        /// Input file with a JSON program (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+        /// Run the program as initcode in a contract creation instead of calling it
+        #[arg(long)]
+        deploy: bool,
+        /// Hex calldata, or constructor arguments with --deploy
+        #[arg(long, default_value = "")]
+        calldata: String,
+        /// Wei sent with the transaction
+        #[arg(long, default_value_t = 0)]
+        value: u128,
+        /// Print the trace as a JSON array of steps
+        #[arg(long)]
+        json: bool,
+    },
+    /// Measure gas of named scenarios and compare it with a snapshot file
+    Snapshot {
+        /// JSON list of scenarios: name, program file, and optional calldata, value and deploy
+        scenarios: String,
+        /// Snapshot file (defaults to .gas-snapshot next to the scenarios)
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+        /// Gas increase in percent a scenario may have before the run fails
+        #[arg(long, default_value_t = 0.0)]
+        tolerance: f64,
+        /// Compare without writing the snapshot file
+        #[arg(long)]
+        check: bool,
+        /// Write the snapshot file even if scenarios regressed
+        #[arg(long, conflicts_with = "check")]
+        update: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum Create2Command {
+    /// Print the keccak-256 hash of the initcode
+    Hash {
+        #[command(flatten)]
+        initcode: InitcodeArgs,
+    },
+    /// Print the address a deployer creates with a salt
+    Address {
+        /// Address of the contract that executes CREATE2
+        #[arg(long)]
+        deployer: Address,
+        #[arg(long)]
+        salt: B256,
+        #[command(flatten)]
+        initcode: InitcodeArgs,
+    },
+    /// Search for a salt whose address has a prefix or leading zero bytes
+    Mine {
+        /// Address of the contract that executes CREATE2
+        #[arg(long)]
+        deployer: Address,
+        /// Required hex prefix of the address, e.g. 0xdead
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Required number of leading zero bytes
+        #[arg(long, default_value_t = 0)]
+        leading_zero_bytes: usize,
+        /// Salt whose last 8 bytes are replaced by the attempt counter
+        #[arg(long, default_value_t = B256::ZERO)]
+        salt_base: B256,
+        /// Give up after this many attempts
+        #[arg(long)]
+        max_attempts: Option<u64>,
+        /// Worker threads (defaults to the number of CPUs)
+        #[arg(long)]
+        threads: Option<usize>,
+        #[command(flatten)]
+        initcode: InitcodeArgs,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct InitcodeArgs {
+    /// Input file with a JSON program, or hex initcode with --hex (use - for stdin)
+    #[arg(default_value = "-")]
+    input: String,
+
+    /// Read the input as hex initcode instead of a JSON program
+    #[arg(long)]
+    hex: bool,
+
+    /// Use this initcode hash instead of reading the input
+    #[arg(long, conflicts_with = "hex")]
+    init_code_hash: Option<B256>,
+}
+
+impl InitcodeArgs {
+    fn hash(&self, assembler: &Assembler) -> Result<B256> {
+        if let Some(hash) = self.init_code_hash {
+            return Ok(hash);
+        }
+        let source = read_source(&self.input)?;
+        let initcode = if self.hex {
+            let digits = source.trim();
+            hex::decode(digits.strip_prefix("0x").unwrap_or(digits))?
+        } else {
+            assembler.assemble(&parse_program_in(&source, &base_dir(&self.input))?)?
+        };
+        Ok(initcode_hash(&initcode))
+    }
+}
+
+fn read_source(path: &str) -> Result<String> {
+    if path == "-" {
         let mut buffer = String::new();
         io::stdin().read_to_string(&mut buffer)?;
-        buffer
+        Ok(buffer)
     } else {
-        std::fs::read_to_string(&args.input)?
+        Ok(std::fs::read_to_string(path)?)
+    }
+}
+
+/// Directory `file:` data is relative to: the input file's, or the current one for stdin
+fn base_dir(path: &str) -> PathBuf {
+    match Path::new(path).parent() {
+        Some(dir) if path != "-" => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn read_program(path: &str) -> Result<Vec<AsmElement>> {
+    Ok(parse_program_in(&read_source(path)?, &base_dir(path))?)
+}
+
+fn parse_hex(digits: &str) -> Result<Vec<u8>> {
+    let digits = digits.trim();
+    Ok(hex::decode(digits.strip_prefix("0x").unwrap_or(digits))?)
+}
+
+/// Scenarios of a snapshot run; program paths are relative to the scenarios file
+fn read_scenarios(assembler: &Assembler, path: &str) -> Result<Vec<Scenario>> {
+    let dir = base_dir(path);
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&read_source(path)?)?;
+    let mut scenarios = Vec::new();
+    for entry in entries {
+        let Some(name) = entry["name"].as_str() else { bail!("scenario without a name: {}", entry) };
+        let Some(program) = entry["program"].as_str() else { bail!("scenario {} has no program", name) };
+        let program = dir.join(program);
+        let code = assembler.assemble(&read_program(&program.to_string_lossy())?)?;
+        let tx = Tx::default()
+            .data(parse_hex(entry["calldata"].as_str().unwrap_or(""))?)
+            .value(primitives::U256::from(entry["value"].as_u64().unwrap_or(0)));
+        scenarios.push(match entry["deploy"].as_bool().unwrap_or(false) {
+            true => Scenario::deploy(name, code, tx),
+            false => Scenario::call(name, code, tx),
+        });
+    }
+    Ok(scenarios)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let assembler = Assembler::new();
+
+    match args.command {
+        Some(Command::Gas { input }) => {
+            let program = read_program(&input)?;
+            println!("{}", assembler.estimate_gas(&program)?);
+        }
+        Some(Command::Eof { input, format }) => {
+            let program = parse_eof_program_in(&read_source(&input)?, &base_dir(&input))?;
+            let issues = assembler.validate_eof(&program)?;
+            for issue in &issues {
+                eprintln!("{}", issue);
+            }
+            if !issues.is_empty() {
+                bail!("{} EOF validation errors", issues.len());
+            }
+            write_bytecode(&assembler.assemble_eof(&program)?, &format)?;
+        }
+        Some(Command::Create2 { command }) => create2(command, &assembler)?,
+        Some(Command::Trace { input, deploy, calldata, value, json }) => {
+            let program = read_program(&input)?;
+            trace(&assembler, &program, deploy, parse_hex(&calldata)?, value, json)?;
+        }
+        Some(Command::Snapshot { scenarios, snapshot, tolerance, check, update }) => {
+            let path = snapshot.unwrap_or_else(|| base_dir(&scenarios).join(".gas-snapshot"));
+            let current = Snapshot::run(&read_scenarios(&assembler, &scenarios)?)?;
+            let comparison = current.compare(&Snapshot::read(&path)?, tolerance);
+            println!("{}", comparison);
+            if !comparison.is_ok() && !update {
+                bail!("gas regressed; rerun with --update to accept");
+            }
+            if !check {
+                current.write(&path)?;
+            }
+        }
+        None => {
+            let source = read_source(&args.input)?;
+            let mut program = parse_program_in(&source, &base_dir(&args.input))?;
+            if let Some(path) = &args.abi {
+                std::fs::write(path, serde_json::to_string_pretty(&parse_abi(&source)?)?)?;
+            }
+            if args.eliminate_dead_code {
+                let (stripped, report) = assembler.eliminate_dead_code(&program)?;
+                eprintln!("{}", report);
+                program = stripped;
+            }
+            if args.merge_tails {
+                let (merged, report) = assembler.merge_tails(&program)?;
+                eprintln!("{}", report);
+                program = merged;
+            }
+            if args.reorder_segments {
+                program = assembler.reorder_segments(&program)?;
+            }
+            write_bytecode(&assembler.assemble(&program)?, &args.format)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_bytecode(bytecode: &[u8], format: &str) -> Result<()> {
+    match format {
+        "hex" => println!("0x{}", hex::encode(bytecode)),
+        "bin" => io::stdout().write_all(bytecode)?,
+        other => bail!("unknown output format: {}", other),
+    }
+    Ok(())
+}
+
+/// Account the traced program is installed at when it is called
+const TRACE_ADDRESS: primitives::Address = primitives::Address::repeat_byte(0x42);
+
+fn trace(
+    assembler: &Assembler,
+    program: &[AsmElement],
+    deploy: bool,
+    calldata: Vec<u8>,
+    value: u128,
+    json: bool,
+) -> Result<()> {
+    let (code, layout) = assembler.assemble_with_layout(program)?;
+    let mut tracer = Tracer::new().with_layout(&code, layout);
+    let mut runner = Runner::new();
+    let value = primitives::U256::from(value);
+    runner.set_balance(DEFAULT_CALLER, value);
+
+    let outcome = if deploy {
+        let mut initcode = code;
+        initcode.extend(calldata);
+        runner.trace_create(initcode, Tx::default().value(value), &mut tracer)
+    } else {
+        runner.install(TRACE_ADDRESS, code);
+        runner.trace_call(TRACE_ADDRESS, Tx::default().data(calldata).value(value), &mut tracer)
     };
-    
-    println!("emasm-cli: Assembly functionality coming soon!");
-    println!("Input length: {} bytes", input.len());
-    
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tracer.trace().to_json())?);
+    } else {
+        print!("{}", tracer.trace());
+    }
+    eprintln!("{}", outcome);
+    Ok(())
+}
+
+fn create2(command: Create2Command, assembler: &Assembler) -> Result<()> {
+    match command {
+        Create2Command::Hash { initcode } => println!("{}", initcode.hash(assembler)?),
+        Create2Command::Address { deployer, salt, initcode } => {
+            println!("{}", create2_address(deployer, salt, initcode.hash(assembler)?));
+        }
+        Create2Command::Mine {
+            deployer,
+            prefix,
+            leading_zero_bytes,
+            salt_base,
+            max_attempts,
+            threads,
+            initcode,
+        } => {
+            if prefix.is_none() && leading_zero_bytes == 0 {
+                bail!("give --prefix or --leading-zero-bytes to search for");
+            }
+            let pattern = match &prefix {
+                Some(prefix) => AddressPattern::prefix(prefix).map_err(anyhow::Error::msg)?,
+                None => AddressPattern::default(),
+            };
+            let mut search = SaltSearch {
+                pattern: pattern.with_leading_zero_bytes(leading_zero_bytes),
+                base: salt_base,
+                ..SaltSearch::default()
+            };
+            search.max_attempts = max_attempts.unwrap_or(search.max_attempts);
+            search.threads = threads.unwrap_or(search.threads);
+
+            match mine_salt(deployer, initcode.hash(assembler)?, &search) {
+                Some((salt, address)) => {
+                    println!("salt: {}", salt);
+                    println!("address: {}", address);
+                }
+                None => bail!("no matching salt in {} attempts", search.max_attempts),
+            }
+        }
+    }
     Ok(())
 }
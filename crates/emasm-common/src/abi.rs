@@ -0,0 +1,193 @@
+use crate::types::*;
+use alloy_primitives::keccak256;
+use serde_json::{json, Value};
+
+/// A function signature in canonical form, e.g. `transfer(address,uint256)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    /// Canonical parameter types.
+    pub inputs: Vec<String>,
+}
+
+impl Signature {
+    /// Parse and canonicalize a signature.
+    ///
+    /// Whitespace is ignored and `uint`/`int` become `uint256`/`int256`, so
+    /// `"transfer(address, uint)"` and `"transfer(address,uint256)"` are the
+    /// same signature. Parameter names are not allowed.
+    pub fn parse(signature: &str) -> Result<Self, AssemblerError> {
+        let invalid = || AssemblerError::InvalidSignature(signature.to_string());
+        let compact: String = signature.chars().filter(|c| !c.is_whitespace()).collect();
+        let (name, rest) = compact.split_once('(').ok_or_else(invalid)?;
+        let params = rest.strip_suffix(')').ok_or_else(invalid)?;
+        if !is_identifier(name) {
+            return Err(invalid());
+        }
+        let inputs = split_params(params)
+            .ok_or_else(invalid)?
+            .into_iter()
+            .map(|ty| canonical_type(ty).ok_or_else(invalid))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { name: name.to_string(), inputs })
+    }
+
+    pub fn canonical(&self) -> String {
+        format!("{}({})", self.name, self.inputs.join(","))
+    }
+
+    /// Keccak-256 of the canonical signature.
+    pub fn hash(&self) -> [u8; 32] {
+        keccak256(self.canonical().as_bytes()).0
+    }
+
+    /// First four bytes of the hash: a function or custom error selector.
+    pub fn selector(&self) -> [u8; 4] {
+        let hash = self.hash();
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// ABI JSON entry for a function with this signature.
+    ///
+    /// Outputs and mutability are not part of a signature, so the entry has no
+    /// outputs and is `nonpayable`.
+    pub fn function_abi(&self) -> Value {
+        json!({
+            "type": "function",
+            "name": self.name,
+            "inputs": self.inputs.iter().map(|ty| param_abi(ty)).collect::<Vec<_>>(),
+            "outputs": [],
+            "stateMutability": "nonpayable",
+        })
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.canonical())
+    }
+}
+
+/// Selector of a function signature such as `transfer(address,uint256)`.
+pub fn selector(signature: &str) -> Result<[u8; 4], AssemblerError> {
+    Ok(Signature::parse(signature)?.selector())
+}
+
+/// Topic 0 of an event such as `Transfer(address,address,uint256)`.
+pub fn event_topic(signature: &str) -> Result<[u8; 32], AssemblerError> {
+    Ok(Signature::parse(signature)?.hash())
+}
+
+/// Selector of a custom error such as `Unauthorized(address)`.
+pub fn error_selector(signature: &str) -> Result<[u8; 4], AssemblerError> {
+    selector(signature)
+}
+
+/// Value pushed by `"sig:..."`, `"event:..."` or `"error:..."`, or None for
+/// any other string.
+pub fn keccak_literal(s: &str) -> Option<Result<Vec<u8>, AssemblerError>> {
+    if let Some(signature) = s.strip_prefix("sig:") {
+        return Some(selector(signature).map(|s| s.to_vec()));
+    }
+    if let Some(signature) = s.strip_prefix("event:") {
+        return Some(event_topic(signature).map(|t| t.to_vec()));
+    }
+    if let Some(signature) = s.strip_prefix("error:") {
+        return Some(error_selector(signature).map(|s| s.to_vec()));
+    }
+    None
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Split a parameter list at top-level commas; None if parentheses do not balance.
+fn split_params(params: &str) -> Option<Vec<&str>> {
+    if params.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&params[start..]);
+    (depth == 0).then_some(parts)
+}
+
+/// Canonical spelling of an ABI type, or None if it is not one.
+fn canonical_type(ty: &str) -> Option<String> {
+    let (base, suffix) = split_array_suffix(ty)?;
+    let base = if let Some(inner) = base.strip_prefix('(') {
+        let members = split_params(inner.strip_suffix(')')?)?;
+        let members: Option<Vec<String>> = members.into_iter().map(canonical_type).collect();
+        format!("({})", members?.join(","))
+    } else {
+        canonical_elementary(base)?
+    };
+    Some(format!("{}{}", base, suffix))
+}
+
+fn canonical_elementary(ty: &str) -> Option<String> {
+    let sized = |digits: &str, valid: &dyn Fn(usize) -> bool| {
+        digits.parse::<usize>().ok().filter(|n| valid(*n) && !digits.starts_with('0'))
+    };
+    match ty {
+        "address" | "bool" | "string" | "bytes" | "function" => Some(ty.to_string()),
+        "uint" | "int" => Some(format!("{}256", ty)),
+        _ => {
+            if let Some(bits) = ty.strip_prefix("uint").or_else(|| ty.strip_prefix("int")) {
+                sized(bits, &|n| n % 8 == 0 && (8..=256).contains(&n))?;
+                Some(ty.to_string())
+            } else if let Some(len) = ty.strip_prefix("bytes") {
+                sized(len, &|n| (1..=32).contains(&n))?;
+                Some(ty.to_string())
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Split `T[2][]` into `T` and `[2][]`.
+fn split_array_suffix(ty: &str) -> Option<(&str, &str)> {
+    let mut end = ty.len();
+    while ty[..end].ends_with(']') {
+        let open = ty[..end].rfind('[')?;
+        let len = &ty[open + 1..end - 1];
+        if !len.is_empty() && (len.parse::<usize>().ok()? == 0 || len.starts_with('0')) {
+            return None;
+        }
+        end = open;
+    }
+    Some((&ty[..end], &ty[end..]))
+}
+
+/// ABI JSON for an unnamed parameter, expanding tuples into components.
+fn param_abi(ty: &str) -> Value {
+    let (base, suffix) = split_array_suffix(ty).expect("types are canonical");
+    match base.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+        Some(inner) => json!({
+            "name": "",
+            "type": format!("tuple{}", suffix),
+            "components": split_params(inner)
+                .expect("types are canonical")
+                .into_iter()
+                .map(param_abi)
+                .collect::<Vec<_>>(),
+        }),
+        None => json!({ "name": "", "type": ty }),
+    }
+}
@@ -1,10 +1,26 @@
 use crate::{
+    cfg::{align_name, Cfg, InstrKind, PushOperand},
+    layout::{Layout, LayoutEntry},
     opcodes::{opcode_map, Opcode},
     types::*,
+    verify::{verify, VerifyIssue},
     encodable::EVMEncodable,
+    stack::{check_stack, StackIssue},
+    slots::lower_stack_slots,
+    gas::{estimate_gas, GasReport},
+    dead_code::{eliminate_dead_code, DeadCodeReport},
+    peephole::{optimize, OptimizeOptions},
+    reorder::reorder_segments,
+    merge::{merge_tails, MergeReport},
+    jumptable::JUMP_TABLE_ENTRY_SIZE,
+    contract::{initcode, ContractOptions},
+    eof::{assemble_eof, EofProgram},
+    eof_verify::{validate_eof, EofIssue},
 };
 use std::collections::HashMap;
 
+type LayoutMaps = (HashMap<String, LabelInfo>, HashMap<String, BytesInfo>);
+
 pub struct Assembler {
     opcode_map: HashMap<&'static str, Opcode>,
 }
@@ -17,10 +33,139 @@ impl Assembler {
     }
 
     pub fn assemble(&self, elements: &[AsmElement]) -> Result<Vec<u8>, AssemblerError> {
-        let flattened = self.flatten(elements);
+        self.assemble_with_layout(elements).map(|(bytecode, _)| bytecode)
+    }
+
+    /// Assemble and also return where every element ended up in the bytecode.
+    pub fn assemble_with_layout(
+        &self,
+        elements: &[AsmElement],
+    ) -> Result<(Vec<u8>, Layout), AssemblerError> {
+        let lowered = self.lower_stack_slots(elements)?;
+        let flattened = self.flatten(&lowered);
         let (label_map, bytes_map) = self.first_pass(&flattened)?;
-        let optimized = self.optimize_labels(label_map, bytes_map, &flattened)?;
-        self.encode(&flattened, &optimized.0, &optimized.1)
+        let (labels, bytes) = self.optimize_labels(label_map, bytes_map, &flattened)?;
+
+        let mut bytecode = Vec::new();
+        let mut entries = Vec::new();
+        self.encode(&flattened, &labels, &bytes, &ElementPath::default(), &mut bytecode, &mut entries)?;
+
+        let layout = Layout {
+            labels: labels.into_iter().map(|(k, v)| (k, v.offset)).collect(),
+            bytes,
+            entries,
+        };
+        Ok((bytecode, layout))
+    }
+
+    /// Check the assembled program for unsafe jumps and executable data.
+    pub fn verify(&self, elements: &[AsmElement]) -> Result<Vec<VerifyIssue>, AssemblerError> {
+        let lowered = self.lower_stack_slots(elements)?;
+        let (bytecode, layout) = self.assemble_with_layout(&lowered)?;
+        let cfg = Cfg::build(&lowered, &self.opcode_map);
+        Ok(verify(&bytecode, &layout, &cfg))
+    }
+
+    /// Statically check stack heights along every reachable path of the program.
+    pub fn check_stack(&self, elements: &[AsmElement]) -> Vec<StackIssue> {
+        check_stack(elements, &self.opcode_map)
+    }
+
+    /// Resolve `let`/`dup:`/`swap:` named stack slots into DUPn/SWAPn opcodes.
+    pub fn lower_stack_slots(&self, elements: &[AsmElement]) -> Result<Vec<AsmElement>, AssemblerError> {
+        lower_stack_slots(elements, &self.opcode_map)
+    }
+
+    /// Estimate gas per segment and per acyclic path, see [`estimate_gas`].
+    pub fn estimate_gas(&self, elements: &[AsmElement]) -> Result<GasReport, AssemblerError> {
+        let lowered = self.lower_stack_slots(elements)?;
+        Ok(estimate_gas(&lowered, &self.opcode_map))
+    }
+
+    /// Remove unreachable code and unused segments, see [`eliminate_dead_code`].
+    pub fn eliminate_dead_code(
+        &self,
+        elements: &[AsmElement],
+    ) -> Result<(Vec<AsmElement>, DeadCodeReport), AssemblerError> {
+        let (result, mut report) = eliminate_dead_code(elements, &self.opcode_map);
+        let before = self.assemble(elements)?.len();
+        let after = self.assemble(&result)?.len();
+        report.bytes_saved = before.saturating_sub(after);
+        Ok((result, report))
+    }
+
+    /// Apply peephole rewrites, see [`optimize`]. Named stack slots are lowered first.
+    pub fn optimize(
+        &self,
+        elements: &[AsmElement],
+        options: &OptimizeOptions,
+    ) -> Result<Vec<AsmElement>, AssemblerError> {
+        let lowered = self.lower_stack_slots(elements)?;
+        Ok(optimize(&lowered, &self.opcode_map, options))
+    }
+
+    /// Reorder top-level segments to shrink the program, see [`reorder_segments`].
+    pub fn reorder_segments(&self, elements: &[AsmElement]) -> Result<Vec<AsmElement>, AssemblerError> {
+        // Report assembly errors instead of ranking layouts that cannot assemble
+        self.assemble(elements)?;
+        Ok(reorder_segments(elements, &self.opcode_map, |candidate| {
+            self.assemble(candidate).map_or(usize::MAX, |code| code.len())
+        }))
+    }
+
+    /// Share identical segments and instruction tails, see [`merge_tails`].
+    ///
+    /// Named stack slots are lowered first, and merges that would make the
+    /// stack checker report new errors are skipped.
+    pub fn merge_tails(
+        &self,
+        elements: &[AsmElement],
+    ) -> Result<(Vec<AsmElement>, MergeReport), AssemblerError> {
+        let lowered = self.lower_stack_slots(elements)?;
+        let before = self.assemble(&lowered)?.len();
+        let stack_errors = |program: &[AsmElement]| {
+            self.check_stack(program).iter().filter(|issue| issue.kind.is_error()).count()
+        };
+        let baseline = stack_errors(&lowered);
+
+        let (result, mut report) = merge_tails(&lowered, &self.opcode_map, |candidate| {
+            if stack_errors(candidate) > baseline {
+                return None;
+            }
+            self.assemble(candidate).ok().map(|code| code.len())
+        });
+        report.bytes_saved = before.saturating_sub(self.assemble(&result)?.len());
+        Ok((result, report))
+    }
+
+    /// Assemble `runtime`, then initcode that runs `constructor` and deploys it
+    /// with its immutables filled in, see [`initcode`].
+    pub fn assemble_contract(
+        &self,
+        constructor: &[AsmElement],
+        runtime: &[AsmElement],
+        options: &ContractOptions,
+    ) -> Result<Vec<u8>, AssemblerError> {
+        let (runtime, layout) = self.assemble_with_layout(runtime)?;
+        let slots = layout.immutable_slots();
+        self.assemble(&initcode(constructor, runtime, &slots, options)?)
+    }
+
+    /// Assemble an EOF v1 container with a code section per function, see
+    /// [`crate::eof`]. Containers that fail validation are an error.
+    pub fn assemble_eof(&self, program: &EofProgram) -> Result<Vec<u8>, AssemblerError> {
+        let (container, layouts) = assemble_eof(program, &self.opcode_map)?;
+        if let Some(issue) = validate_eof(&container).into_iter().next() {
+            return Err(AssemblerError::InvalidEof(issue.locate(&layouts).to_string()));
+        }
+        container.encode()
+    }
+
+    /// Validate the container of an EOF program, see [`validate_eof`], with
+    /// every issue located at the element that caused it.
+    pub fn validate_eof(&self, program: &EofProgram) -> Result<Vec<EofIssue>, AssemblerError> {
+        let (container, layouts) = assemble_eof(program, &self.opcode_map)?;
+        Ok(validate_eof(&container).into_iter().map(|issue| issue.locate(&layouts)).collect())
     }
 
     pub fn assemble_with_placeholders(
@@ -75,7 +220,7 @@ impl Assembler {
     fn first_pass(
         &self,
         elements: &[AsmElement],
-    ) -> Result<(HashMap<String, LabelInfo>, HashMap<String, BytesInfo>), AssemblerError> {
+    ) -> Result<LayoutMaps, AssemblerError> {
         let mut labels = HashMap::new();
         let mut bytes_segments = HashMap::new();
         let mut offset = 0;
@@ -102,11 +247,15 @@ impl Assembler {
                         LabelInfo {
                             offset: jumpdest_offset,
                             size_estimate: 2, // Initial estimate for PUSH address
+                            size: 0,
                         },
                     );
                     *offset += 1; // JUMPDEST
                     // Recursively process inner elements
                     self.first_pass_recursive(inner, labels, bytes_segments, offset);
+                    if let Some(info) = labels.get_mut(label) {
+                        info.size = *offset - jumpdest_offset;
+                    }
                 }
                 AsmElement::BytesSegment(label, data) => {
                     bytes_segments.insert(
@@ -118,6 +267,26 @@ impl Assembler {
                     );
                     *offset += data.len();
                 }
+                AsmElement::JumpTable(label, targets) => {
+                    bytes_segments.insert(
+                        label.clone(),
+                        BytesInfo {
+                            offset: *offset,
+                            size: JUMP_TABLE_ENTRY_SIZE * targets.len(),
+                        },
+                    );
+                    *offset += JUMP_TABLE_ENTRY_SIZE * targets.len();
+                }
+                AsmElement::Anchor(label) => {
+                    labels.insert(
+                        label.clone(),
+                        LabelInfo {
+                            offset: *offset,
+                            size_estimate: 2,
+                            size: 0,
+                        },
+                    );
+                }
                 AsmElement::Opcode(_) => *offset += 1,
                 AsmElement::Literal(data) => {
                     // Match encoding logic exactly
@@ -130,61 +299,36 @@ impl Assembler {
                     *offset += push_len;
                 }
                 AsmElement::Label(_) => *offset += 2, // Estimate PUSH1 (1) + 1-byte address (1)
-                AsmElement::BytesPtr(_) | AsmElement::BytesSize(_) => *offset += 2,
+                AsmElement::BytesPtr(_) | AsmElement::BytesSize(_) | AsmElement::Offset(_) => *offset += 2,
                 AsmElement::Placeholder(_) => *offset += 2, // Conservative estimate PUSH1 + data
+                AsmElement::Immutable(_, width) => *offset += 1 + width,
+                AsmElement::Align(alignment) => *offset += padding(*offset, *alignment),
+                AsmElement::Let(_) => {}
+                AsmElement::DupSlot(_) | AsmElement::SwapSlot(_) => *offset += 1,
             }
         }
     }
 
-    fn estimate_size(
-        &self,
-        elements: &[AsmElement],
-        labels: &HashMap<String, LabelInfo>,
-        bytes_map: &HashMap<String, BytesInfo>,
-    ) -> usize {
-        let mut size = 0;
-        for elem in elements {
-            match elem {
-                AsmElement::Segment(_, inner) => {
-                    size += 1; // JUMPDEST
-                    size += self.estimate_size(inner, labels, bytes_map);
-                }
-                AsmElement::Opcode(_) => size += 1,
-                AsmElement::Literal(data) => {
-                    // Match encoding logic: trim leading zeros, minimum PUSH1 0x00
-                    let trimmed_len = data.iter().skip_while(|&&b| b == 0).count();
-                    size += if trimmed_len == 0 { 2 } else { 1 + trimmed_len.max(1) };
-                }
-                AsmElement::Label(label) => {
-                    if let Some(info) = labels.get(label) {
-                        size += 1 + info.size_estimate;
-                    } else {
-                        size += 3; // PUSH2 conservative
-                    }
-                }
-                AsmElement::BytesPtr(_) | AsmElement::BytesSize(_) => size += 3,
-                AsmElement::BytesSegment(_, data) => size += data.len(),
-                AsmElement::Placeholder(_) => size += 3,
-            }
-        }
-        size
-    }
-
     fn optimize_labels(
         &self,
         mut labels: HashMap<String, LabelInfo>,
-        bytes_map: HashMap<String, BytesInfo>,
+        mut bytes_map: HashMap<String, BytesInfo>,
         elements: &[AsmElement],
-    ) -> Result<(HashMap<String, LabelInfo>, HashMap<String, BytesInfo>), AssemblerError> {
+    ) -> Result<LayoutMaps, AssemblerError> {
         const MAX_ITERATIONS: usize = 100;
         
         for _ in 0..MAX_ITERATIONS {
             let prev_labels = labels.clone();
-            labels = self.recalculate_offsets(elements, labels, &bytes_map)?;
+            let prev_bytes = bytes_map.clone();
+            self.recalculate_offsets(elements, &mut labels, &mut bytes_map);
             
-            if prev_labels.iter().all(|(k, v)| {
-                labels.get(k).map(|new_v| new_v.offset == v.offset).unwrap_or(false)
-            }) {
+            let labels_stable = prev_labels.iter().all(|(k, v)| {
+                labels.get(k).map(|new_v| new_v.offset == v.offset && new_v.size == v.size).unwrap_or(false)
+            });
+            let bytes_stable = prev_bytes.iter().all(|(k, v)| {
+                bytes_map.get(k).map(|new_v| new_v.offset == v.offset).unwrap_or(false)
+            });
+            if labels_stable && bytes_stable {
                 return Ok((labels, bytes_map));
             }
         }
@@ -195,12 +339,13 @@ impl Assembler {
     fn recalculate_offsets(
         &self,
         elements: &[AsmElement],
-        mut labels: HashMap<String, LabelInfo>,
-        bytes_map: &HashMap<String, BytesInfo>,
-    ) -> Result<HashMap<String, LabelInfo>, AssemblerError> {
+        labels: &mut HashMap<String, LabelInfo>,
+        bytes_map: &mut HashMap<String, BytesInfo>,
+    ) {
+        // Push widths are sized from the previous iteration's offsets
+        let prev_bytes = bytes_map.clone();
         let mut offset = 0;
-        self.recalculate_offsets_recursive(elements, &mut labels, bytes_map, &mut offset);
-        Ok(labels)
+        self.recalculate_offsets_recursive(elements, labels, &prev_bytes, bytes_map, &mut offset);
     }
 
     fn recalculate_offsets_recursive(
@@ -208,6 +353,7 @@ impl Assembler {
         elements: &[AsmElement],
         labels: &mut HashMap<String, LabelInfo>,
         bytes_map: &HashMap<String, BytesInfo>,
+        new_bytes_map: &mut HashMap<String, BytesInfo>,
         offset: &mut usize,
     ) {
         for elem in elements {
@@ -222,11 +368,30 @@ impl Assembler {
                     }
                     *offset += 1; // JUMPDEST
                     // Recursively process inner elements
-                    self.recalculate_offsets_recursive(inner, labels, bytes_map, offset);
+                    self.recalculate_offsets_recursive(inner, labels, bytes_map, new_bytes_map, offset);
+                    if let Some(info) = labels.get_mut(label) {
+                        info.size = *offset - jumpdest_offset;
+                    }
                 }
-                AsmElement::BytesSegment(_, data) => {
+                AsmElement::BytesSegment(label, data) => {
+                    if let Some(info) = new_bytes_map.get_mut(label) {
+                        info.offset = *offset;
+                    }
                     *offset += data.len();
                 }
+                AsmElement::JumpTable(label, targets) => {
+                    if let Some(info) = new_bytes_map.get_mut(label) {
+                        info.offset = *offset;
+                    }
+                    *offset += JUMP_TABLE_ENTRY_SIZE * targets.len();
+                }
+                AsmElement::Anchor(label) => {
+                    let push_size = self.calculate_push_size(*offset);
+                    if let Some(info) = labels.get_mut(label) {
+                        info.offset = *offset;
+                        info.size_estimate = push_size;
+                    }
+                }
                 AsmElement::Opcode(_) => *offset += 1,
                 AsmElement::Literal(data) => {
                     // Match the encoding logic: trim leading zeros, but minimum is PUSH1 0x00
@@ -245,59 +410,28 @@ impl Assembler {
                         *offset += 3;
                     }
                 }
-                AsmElement::BytesPtr(_) | AsmElement::BytesSize(_) => {
-                    *offset += 3;
-                }
-                AsmElement::Placeholder(_) => *offset += 3,
-            }
-        }
-    }
-
-    fn calculate_segment_size(
-        &self,
-        elements: &[AsmElement],
-        labels: &HashMap<String, LabelInfo>,
-        bytes_map: &HashMap<String, BytesInfo>,
-    ) -> usize {
-        let mut size = 0;
-        for elem in elements {
-            match elem {
-                AsmElement::Segment(_, inner) => {
-                    size += 1; // JUMPDEST
-                    size += self.calculate_segment_size(inner, labels, bytes_map);
-                }
-                AsmElement::Opcode(_) => size += 1,
-                AsmElement::Literal(data) => {
-                    // Match encoding logic: trim leading zeros, minimum PUSH1 0x00
-                    let trimmed_len = data.iter().skip_while(|&&b| b == 0).count();
-                    size += if trimmed_len == 0 { 2 } else { 1 + trimmed_len.max(1) };
-                }
-                AsmElement::Label(l) => {
-                    if let Some(info) = labels.get(l) {
-                        size += 1 + info.size_estimate;
-                    } else {
-                        size += 3;
-                    }
-                }
                 AsmElement::BytesPtr(l) => {
-                    if let Some(info) = bytes_map.get(l) {
-                        size += 1 + self.calculate_push_size(info.offset);
-                    } else {
-                        size += 3;
-                    }
+                    *offset += bytes_map.get(l)
+                        .map(|info| 1 + self.calculate_push_size(info.offset))
+                        .unwrap_or(3);
                 }
                 AsmElement::BytesSize(l) => {
-                    if let Some(info) = bytes_map.get(l) {
-                        size += 1 + self.calculate_push_size(info.size);
-                    } else {
-                        size += 3;
-                    }
+                    *offset += bytes_map.get(l)
+                        .map(|info| 1 + self.calculate_push_size(info.size))
+                        .unwrap_or(3);
+                }
+                AsmElement::Offset(expr) => {
+                    *offset += expr.eval(&segment_extent(labels, bytes_map))
+                        .map(|value| 1 + self.calculate_push_size(value))
+                        .unwrap_or(3);
                 }
-                AsmElement::BytesSegment(_, data) => size += data.len(),
-                AsmElement::Placeholder(_) => size += 3,
+                AsmElement::Placeholder(_) => *offset += 3,
+                AsmElement::Immutable(_, width) => *offset += 1 + width,
+                AsmElement::Align(alignment) => *offset += padding(*offset, *alignment),
+                AsmElement::Let(_) => {}
+                AsmElement::DupSlot(_) | AsmElement::SwapSlot(_) => *offset += 1,
             }
         }
-        size
     }
 
     fn calculate_push_size(&self, value: usize) -> usize {
@@ -314,66 +448,104 @@ impl Assembler {
         elements: &[AsmElement],
         labels: &HashMap<String, LabelInfo>,
         bytes_map: &HashMap<String, BytesInfo>,
-    ) -> Result<Vec<u8>, AssemblerError> {
-        let mut bytecode = Vec::new();
-
-        for elem in elements {
-            match elem {
+        parent: &ElementPath,
+        bytecode: &mut Vec<u8>,
+        entries: &mut Vec<LayoutEntry>,
+    ) -> Result<(), AssemblerError> {
+        for (i, elem) in elements.iter().enumerate() {
+            let offset = bytecode.len();
+            let kind = match elem {
                 AsmElement::Opcode(name) => {
                     let opcode = self.opcode_map.get(name.as_str())
                         .ok_or_else(|| AssemblerError::UnknownOpcode(name.clone()))?;
                     bytecode.push(opcode.0);
+                    if *opcode == Opcode::JUMPDEST {
+                        InstrKind::Jumpdest(None)
+                    } else {
+                        InstrKind::Op(*opcode)
+                    }
                 }
                 AsmElement::Literal(data) => {
-                    self.encode_push(&mut bytecode, data);
+                    encode_push(bytecode, data);
+                    InstrKind::Push(PushOperand::Literal(data.clone()))
                 }
-                AsmElement::Segment(_, inner) => {
+                AsmElement::Segment(label, inner) => {
+                    let head = parent.segment(label);
                     bytecode.push(Opcode::JUMPDEST.0);
-                    bytecode.extend(self.encode(inner, labels, bytes_map)?);
+                    entries.push(LayoutEntry {
+                        path: head.clone(),
+                        offset,
+                        size: 1,
+                        kind: InstrKind::Jumpdest(Some(label.clone())),
+                    });
+                    self.encode(inner, labels, bytes_map, &head, bytecode, entries)?;
+                    continue;
                 }
                 AsmElement::Label(label) => {
                     let info = labels.get(label)
                         .ok_or_else(|| AssemblerError::LabelNotFound(label.clone()))?;
-                    self.encode_push_value(&mut bytecode, info.offset);
+                    self.encode_push_value(bytecode, info.offset);
+                    InstrKind::Push(PushOperand::Label(label.clone()))
                 }
-                AsmElement::BytesSegment(_, data) => {
+                AsmElement::BytesSegment(label, data) => {
                     bytecode.extend(data);
+                    InstrKind::Data(label.clone())
+                }
+                AsmElement::JumpTable(label, targets) => {
+                    for target in targets {
+                        let info = labels.get(target)
+                            .ok_or_else(|| AssemblerError::LabelNotFound(target.clone()))?;
+                        let entry = u16::try_from(info.offset)
+                            .map_err(|_| AssemblerError::IntegerOverflow)?;
+                        bytecode.extend(entry.to_be_bytes());
+                    }
+                    InstrKind::Data(label.clone())
                 }
                 AsmElement::BytesPtr(label) => {
                     let info = bytes_map.get(label)
                         .ok_or_else(|| AssemblerError::LabelNotFound(label.clone()))?;
-                    self.encode_push_value(&mut bytecode, info.offset);
+                    self.encode_push_value(bytecode, info.offset);
+                    InstrKind::Push(PushOperand::BytesPtr(label.clone()))
                 }
                 AsmElement::BytesSize(label) => {
                     let info = bytes_map.get(label)
                         .ok_or_else(|| AssemblerError::LabelNotFound(label.clone()))?;
-                    self.encode_push_value(&mut bytecode, info.size);
+                    self.encode_push_value(bytecode, info.size);
+                    InstrKind::Push(PushOperand::BytesSize(label.clone()))
+                }
+                AsmElement::Offset(expr) => {
+                    let value = expr.eval(&segment_extent(labels, bytes_map))?;
+                    self.encode_push_value(bytecode, value);
+                    InstrKind::Push(PushOperand::Offset(expr.clone()))
                 }
                 AsmElement::Placeholder(_) => {
                     return Err(AssemblerError::InvalidPlaceholder(0));
                 }
-            }
+                AsmElement::Immutable(name, width) => {
+                    bytecode.push(Opcode::PUSH1.0 - 1 + *width as u8);
+                    bytecode.resize(bytecode.len() + width, 0);
+                    InstrKind::Push(PushOperand::Immutable(name.clone(), *width))
+                }
+                AsmElement::Align(alignment) => {
+                    bytecode.resize(offset + padding(offset, *alignment), 0);
+                    InstrKind::Data(align_name(*alignment))
+                }
+                AsmElement::Anchor(label) => InstrKind::Anchor(label.clone()),
+                AsmElement::Let(_) => continue,
+                AsmElement::DupSlot(name) | AsmElement::SwapSlot(name) => {
+                    // Slots are lowered to opcodes before encoding
+                    return Err(AssemblerError::UnknownStackSlot(name.clone()));
+                }
+            };
+            entries.push(LayoutEntry {
+                path: parent.child(i),
+                offset,
+                size: bytecode.len() - offset,
+                kind,
+            });
         }
 
-        Ok(bytecode)
-    }
-
-    fn encode_push(&self, bytecode: &mut Vec<u8>, data: &[u8]) {
-        let trimmed = data.iter()
-            .skip_while(|&&b| b == 0)
-            .copied()
-            .collect::<Vec<_>>();
-        
-        if trimmed.is_empty() {
-            // For zero, use PUSH1 0x00 for compatibility
-            bytecode.push(Opcode::PUSH1.0);
-            bytecode.push(0x00);
-            return;
-        }
-        
-        let len = trimmed.len().min(32);
-        bytecode.push(Opcode::PUSH1.0 - 1 + len as u8);
-        bytecode.extend(&trimmed[..len]);
+        Ok(())
     }
 
     fn encode_push_value(&self, bytecode: &mut Vec<u8>, value: usize) {
@@ -396,6 +568,41 @@ impl Assembler {
     }
 }
 
+/// PUSH of `data` without its leading zeros, PUSH1 0x00 for zero.
+pub(crate) fn encode_push(bytecode: &mut Vec<u8>, data: &[u8]) {
+    let trimmed = data.iter()
+        .skip_while(|&&b| b == 0)
+        .copied()
+        .collect::<Vec<_>>();
+
+    if trimmed.is_empty() {
+        // For zero, use PUSH1 0x00 for compatibility
+        bytecode.push(Opcode::PUSH1.0);
+        bytecode.push(0x00);
+        return;
+    }
+
+    let len = trimmed.len().min(32);
+    bytecode.push(Opcode::PUSH1.0 - 1 + len as u8);
+    bytecode.extend(&trimmed[..len]);
+}
+
+/// Offset and size of the code or bytes segment called `name`.
+fn segment_extent<'a>(
+    labels: &'a HashMap<String, LabelInfo>,
+    bytes_map: &'a HashMap<String, BytesInfo>,
+) -> impl Fn(&str) -> Option<(usize, usize)> + 'a {
+    move |name| {
+        labels.get(name).map(|info| (info.offset, info.size))
+            .or_else(|| bytes_map.get(name).map(|info| (info.offset, info.size)))
+    }
+}
+
+/// Zero bytes that bring `offset` to a multiple of `alignment`.
+fn padding(offset: usize, alignment: usize) -> usize {
+    (alignment - offset % alignment) % alignment
+}
+
 impl Default for Assembler {
     fn default() -> Self {
         Self::new()
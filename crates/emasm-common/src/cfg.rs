@@ -0,0 +1,305 @@
+use crate::{
+    jumptable::jump_tables,
+    offset::OffsetExpr,
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::HashMap;
+
+/// Value pushed by a PUSH instruction, before layout resolves it to bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushOperand {
+    Literal(Vec<u8>),
+    Label(String),
+    BytesPtr(String),
+    BytesSize(String),
+    Placeholder(usize),
+    /// Immutable name and width, filled in at deploy time.
+    Immutable(String, usize),
+    /// Arithmetic over segment offsets, known once the layout is.
+    Offset(OffsetExpr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstrKind {
+    /// JUMPDEST at the head of a segment (`Some(label)`) or written explicitly (`None`).
+    Jumpdest(Option<String>),
+    Op(Opcode),
+    Push(PushOperand),
+    /// Raw bytes of a bytes segment, jump table or alignment padding: never executable.
+    Data(String),
+    /// Opcode name that is not in the opcode table.
+    Unknown(String),
+    /// Named position that emits nothing and is not a jump target.
+    Anchor(String),
+    /// Named stack slot pseudo-instructions, before lowering.
+    Let(Vec<String>),
+    DupSlot(String),
+    SwapSlot(String),
+}
+
+/// Name of the data written by `"align:N"`, as it appears in diagnostics.
+pub(crate) fn align_name(alignment: usize) -> String {
+    format!("align:{}", alignment)
+}
+
+/// A single instruction of the linearized program, tagged with its source element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instr {
+    pub path: ElementPath,
+    pub kind: InstrKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpTarget {
+    /// Target pushed by the instruction right before the jump.
+    Label(String),
+    /// Target computed at runtime.
+    Dynamic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    /// Execution continues into the next block.
+    FallThrough,
+    Jump(JumpTarget),
+    JumpI(JumpTarget),
+    /// STOP, RETURN, REVERT, INVALID or SELFDESTRUCT.
+    Halt(Opcode),
+    /// Execution runs into a bytes segment.
+    Data,
+    /// Execution runs off the end of the code (an implicit STOP).
+    End,
+}
+
+/// Straight-line run of instructions with a single entry and a single exit.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    /// Segment label if the block starts at a segment's JUMPDEST.
+    pub label: Option<String>,
+    /// Range of instructions in `Cfg::instrs`.
+    pub start: usize,
+    pub end: usize,
+    pub terminator: Terminator,
+    pub successors: Vec<usize>,
+}
+
+/// Control-flow graph of a program in `AsmElement` form.
+///
+/// Jumps are resolved statically when the target label is pushed directly
+/// before the JUMP/JUMPI, which is how label references are written in
+/// practice; anything else is recorded as a dynamic jump. A dynamic JUMP in a
+/// block that pushes the address of a jump table gets every entry of that
+/// table as a successor.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub instrs: Vec<Instr>,
+    pub blocks: Vec<BasicBlock>,
+    pub label_blocks: HashMap<String, usize>,
+    /// Entries of each jump table, by table name.
+    pub jump_tables: HashMap<String, Vec<String>>,
+}
+
+impl Cfg {
+    pub fn build(elements: &[AsmElement], opcodes: &HashMap<&'static str, Opcode>) -> Self {
+        let mut instrs = Vec::new();
+        linearize(elements, opcodes, &ElementPath::default(), &mut instrs);
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut start = 0;
+        let mut label = None;
+        for (i, instr) in instrs.iter().enumerate() {
+            match &instr.kind {
+                InstrKind::Jumpdest(l) => {
+                    if i > start {
+                        blocks.push(open_block(label.take(), start, i));
+                    }
+                    start = i;
+                    label = l.clone();
+                }
+                InstrKind::Data(_) => {
+                    if i > start {
+                        blocks.push(open_block(label.take(), start, i));
+                    }
+                    let mut block = open_block(None, i, i + 1);
+                    block.terminator = Terminator::Data;
+                    blocks.push(block);
+                    start = i + 1;
+                    label = None;
+                }
+                InstrKind::Op(op) if *op == Opcode::JUMP || *op == Opcode::JUMPI || op.is_terminator() => {
+                    let target = match i.checked_sub(1).map(|p| &instrs[p].kind) {
+                        Some(InstrKind::Push(PushOperand::Label(l))) if i > start => {
+                            JumpTarget::Label(l.clone())
+                        }
+                        _ => JumpTarget::Dynamic,
+                    };
+                    let mut block = open_block(label.take(), start, i + 1);
+                    block.terminator = match *op {
+                        Opcode::JUMP => Terminator::Jump(target),
+                        Opcode::JUMPI => Terminator::JumpI(target),
+                        other => Terminator::Halt(other),
+                    };
+                    blocks.push(block);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if start < instrs.len() || label.is_some() {
+            blocks.push(open_block(label, start, instrs.len()));
+        }
+        if let Some(last) = blocks.last_mut() {
+            if last.terminator == Terminator::FallThrough {
+                last.terminator = Terminator::End;
+            }
+        }
+
+        let label_blocks: HashMap<String, usize> = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.label.clone().map(|l| (l, i)))
+            .collect();
+
+        let jump_tables = jump_tables(elements);
+        let count = blocks.len();
+        for (i, block) in blocks.iter_mut().enumerate() {
+            let next = (i + 1 < count).then_some(i + 1);
+            block.successors = match &block.terminator {
+                Terminator::FallThrough => next.into_iter().collect(),
+                Terminator::Jump(JumpTarget::Label(l)) => {
+                    label_blocks.get(l).copied().into_iter().collect()
+                }
+                Terminator::JumpI(JumpTarget::Label(l)) => {
+                    label_blocks.get(l).copied().into_iter().chain(next).collect()
+                }
+                Terminator::JumpI(JumpTarget::Dynamic) => next.into_iter().collect(),
+                Terminator::Jump(JumpTarget::Dynamic) => instrs[block.start..block.end]
+                    .iter()
+                    .filter_map(|instr| match &instr.kind {
+                        InstrKind::Push(PushOperand::BytesPtr(name)) => jump_tables.get(name),
+                        _ => None,
+                    })
+                    .flatten()
+                    .filter_map(|l| label_blocks.get(l).copied())
+                    .collect(),
+                _ => Vec::new(),
+            };
+        }
+
+        Self { instrs, blocks, label_blocks, jump_tables }
+    }
+
+    pub fn block_instrs(&self, block: usize) -> &[Instr] {
+        let b = &self.blocks[block];
+        &self.instrs[b.start..b.end]
+    }
+
+    /// Labels a block can hand to a jump: the labels it pushes, plus the
+    /// entries of every jump table whose address it pushes.
+    pub fn pushed_labels(&self, block: usize) -> Vec<&str> {
+        let mut labels = Vec::new();
+        for instr in self.block_instrs(block) {
+            match &instr.kind {
+                InstrKind::Push(PushOperand::Label(l)) => labels.push(l.as_str()),
+                InstrKind::Push(PushOperand::BytesPtr(name)) => {
+                    if let Some(entries) = self.jump_tables.get(name) {
+                        labels.extend(entries.iter().map(String::as_str));
+                    }
+                }
+                _ => {}
+            }
+        }
+        labels
+    }
+
+    /// Blocks reachable from the program entry through static edges.
+    ///
+    /// Targets of dynamic jumps cannot be followed, so once any dynamic jump
+    /// is reachable, every labelled block whose label is pushed by reachable
+    /// code, directly or through a jump table, counts as reachable too.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = Vec::new();
+        if !self.blocks.is_empty() {
+            stack.push(0);
+        }
+        let mut taken = Vec::new();
+        let mut dynamic = false;
+        while let Some(b) = stack.pop() {
+            if std::mem::replace(&mut seen[b], true) {
+                continue;
+            }
+            for l in self.pushed_labels(b) {
+                if let Some(&target) = self.label_blocks.get(l) {
+                    taken.push(target);
+                    if dynamic {
+                        stack.push(target);
+                    }
+                }
+            }
+            let block = &self.blocks[b];
+            if matches!(
+                block.terminator,
+                Terminator::Jump(JumpTarget::Dynamic) | Terminator::JumpI(JumpTarget::Dynamic)
+            ) && !dynamic
+            {
+                dynamic = true;
+                stack.extend(taken.iter().copied());
+            }
+            stack.extend(block.successors.iter().copied());
+        }
+        seen
+    }
+}
+
+fn open_block(label: Option<String>, start: usize, end: usize) -> BasicBlock {
+    BasicBlock {
+        label,
+        start,
+        end,
+        terminator: Terminator::FallThrough,
+        successors: Vec::new(),
+    }
+}
+
+fn linearize(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+    parent: &ElementPath,
+    out: &mut Vec<Instr>,
+) {
+    for (i, elem) in elements.iter().enumerate() {
+        let path = parent.child(i);
+        let kind = match elem {
+            AsmElement::Segment(label, inner) => {
+                let head = parent.segment(label);
+                out.push(Instr {
+                    path: head.clone(),
+                    kind: InstrKind::Jumpdest(Some(label.clone())),
+                });
+                linearize(inner, opcodes, &head, out);
+                continue;
+            }
+            AsmElement::Opcode(name) => match opcodes.get(name.as_str()) {
+                Some(&Opcode::JUMPDEST) => InstrKind::Jumpdest(None),
+                Some(op) => InstrKind::Op(*op),
+                None => InstrKind::Unknown(name.clone()),
+            },
+            AsmElement::Literal(data) => InstrKind::Push(PushOperand::Literal(data.clone())),
+            AsmElement::Label(l) => InstrKind::Push(PushOperand::Label(l.clone())),
+            AsmElement::BytesPtr(l) => InstrKind::Push(PushOperand::BytesPtr(l.clone())),
+            AsmElement::BytesSize(l) => InstrKind::Push(PushOperand::BytesSize(l.clone())),
+            AsmElement::Placeholder(idx) => InstrKind::Push(PushOperand::Placeholder(*idx)),
+            AsmElement::Immutable(name, width) => InstrKind::Push(PushOperand::Immutable(name.clone(), *width)),
+            AsmElement::Offset(expr) => InstrKind::Push(PushOperand::Offset(expr.clone())),
+            AsmElement::BytesSegment(l, _) | AsmElement::JumpTable(l, _) => InstrKind::Data(l.clone()),
+            AsmElement::Align(alignment) => InstrKind::Data(align_name(*alignment)),
+            AsmElement::Anchor(name) => InstrKind::Anchor(name.clone()),
+            AsmElement::Let(names) => InstrKind::Let(names.clone()),
+            AsmElement::DupSlot(name) => InstrKind::DupSlot(name.clone()),
+            AsmElement::SwapSlot(name) => InstrKind::SwapSlot(name.clone()),
+        };
+        out.push(Instr { path, kind });
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    opcodes::{Fork, Opcode},
+    peephole::Objective,
+    types::*,
+};
+use alloy_primitives::U256;
+
+/// How many NOT/shift steps a materialization may chain.
+const MAX_DEPTH: usize = 2;
+
+/// Instruction sequence that leaves a constant on the stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Materialization {
+    pub elements: Vec<AsmElement>,
+    /// Encoded size in bytes.
+    pub size: usize,
+    pub gas: u64,
+}
+
+impl Materialization {
+    fn push(value: U256) -> Self {
+        let bytes = value.to_be_bytes::<32>();
+        let trimmed: Vec<u8> = bytes.iter().skip_while(|&&b| b == 0).copied().collect();
+        Self {
+            size: 1 + trimmed.len().max(1),
+            gas: 3,
+            elements: vec![AsmElement::Literal(trimmed)],
+        }
+    }
+
+    fn then(mut self, other: Materialization) -> Self {
+        self.elements.extend(other.elements);
+        self.size += other.size;
+        self.gas += other.gas;
+        self
+    }
+
+    fn op(mut self, name: &str) -> Self {
+        self.elements.push(AsmElement::Opcode(name.to_string()));
+        self.size += 1;
+        self.gas += 3;
+        self
+    }
+
+    fn cost(&self, objective: Objective) -> (u64, u64) {
+        match objective {
+            Objective::Size => (self.size as u64, self.gas),
+            Objective::Gas => (self.gas, self.size as u64),
+        }
+    }
+}
+
+/// Cheapest way to put `value` on the stack for the objective.
+///
+/// Besides a plain PUSH this considers `PUSH0` for zero, `NOT` of the
+/// complement (so `0xff..ff` is `PUSH0 NOT`), and building the value from a
+/// shorter one with SHL or SHR (so `1 << 255` is `PUSH1 1 PUSH1 0xff SHL`).
+/// Opcodes missing from `fork` are never used.
+pub fn materialize(value: U256, fork: Fork, objective: Objective) -> Materialization {
+    best(value, fork, objective, MAX_DEPTH)
+}
+
+fn best(value: U256, fork: Fork, objective: Objective, depth: usize) -> Materialization {
+    let mut candidates = vec![Materialization::push(value)];
+
+    if value.is_zero() && Opcode::PUSH0.available_in(fork) {
+        candidates.push(Materialization {
+            elements: vec![AsmElement::Opcode("push0".to_string())],
+            size: 1,
+            gas: 2,
+        });
+    }
+
+    if depth > 0 {
+        candidates.push(best(!value, fork, objective, depth - 1).op("not"));
+
+        if Opcode::SHL.available_in(fork) && !value.is_zero() {
+            // Bits shifted out are free, so try filling them with zeros and with ones
+            let trailing = value.trailing_zeros();
+            if trailing > 0 {
+                let shifted = value >> trailing;
+                for operand in [shifted, shifted | !(U256::MAX >> trailing)] {
+                    let shift = Materialization::push(U256::from(trailing));
+                    candidates.push(best(operand, fork, objective, depth - 1).then(shift).op("shl"));
+                }
+            }
+            let leading = value.leading_zeros();
+            if leading > 0 {
+                let shifted = value << leading;
+                for operand in [shifted, shifted | !(U256::MAX << leading)] {
+                    let shift = Materialization::push(U256::from(leading));
+                    candidates.push(best(operand, fork, objective, depth - 1).then(shift).op("shr"));
+                }
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .min_by_key(|c| c.cost(objective))
+        .expect("a plain push is always a candidate")
+}
@@ -0,0 +1,173 @@
+use crate::{layout::ImmutableSlot, types::*};
+
+/// Name of the bytes segment that holds the runtime code in initcode.
+pub const RUNTIME_SEGMENT: &str = "runtime";
+
+/// Label of the code that copies the runtime to memory and returns it.
+pub const DEPLOY_LABEL: &str = "deploy";
+
+/// Memory offset the runtime is copied to when it has immutables, so that
+/// every slot can be patched by rewriting the 32-byte word that ends with it.
+const PATCH_BASE: usize = 0x20;
+
+/// How initcode is wrapped around the constructor.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ContractOptions {
+    /// Copy ABI-encoded constructor arguments appended to the initcode to
+    /// memory offset 0 before the constructor runs.
+    pub constructor_args: bool,
+    /// Immutables the constructor leaves values for, first name on top of the stack.
+    pub immutables: Vec<String>,
+}
+
+/// Initcode that runs `constructor` and then deploys `runtime`.
+///
+/// The layout is the optional argument copy, the constructor, a `deploy`
+/// segment that `CODECOPY`s the runtime to memory and `RETURN`s it, and the
+/// runtime bytes as the last bytes segment, named `runtime`. The constructor
+/// deploys by falling off its end or by jumping to `deploy`, so one that ends
+/// in segments of its own must jump there itself. It may use
+/// `bytes:runtime:ptr` and `bytes:runtime:size`, but must not define
+/// `runtime` or `deploy`.
+///
+/// Constructor arguments are everything after the runtime: their length is
+/// `CODESIZE` minus the initcode size, and they are copied to memory offset 0.
+///
+/// `slots` are the runtime's immutables, from [`Layout::immutable_slots`]. The
+/// constructor reaches `deploy` with one value per name in
+/// `options.immutables`, and `deploy` writes each value into every slot of
+/// that name. Values must fit the width of their slots.
+///
+/// [`Layout::immutable_slots`]: crate::layout::Layout::immutable_slots
+pub fn initcode(
+    constructor: &[AsmElement],
+    runtime: Vec<u8>,
+    slots: &[ImmutableSlot],
+    options: &ContractOptions,
+) -> Result<Vec<AsmElement>, AssemblerError> {
+    if let Some(name) = reserved_name(constructor) {
+        return Err(AssemblerError::InvalidContract(format!(
+            "the constructor defines {}, which the initcode wrapper generates",
+            name
+        )));
+    }
+    if let Some(slot) = slots.iter().find(|slot| !options.immutables.contains(&slot.name)) {
+        return Err(AssemblerError::InvalidContract(format!(
+            "the runtime uses immutable {}, which is not in the immutables list",
+            slot.name
+        )));
+    }
+
+    let mut out = Vec::new();
+    if options.constructor_args {
+        // The runtime is the last thing in the initcode, so it ends where the arguments start
+        out.extend([
+            size(),
+            ptr(),
+            op("add"),
+            op("dup1"),
+            op("codesize"),
+            op("sub"),
+            op("swap1"),
+            lit(0),
+            op("codecopy"),
+        ]);
+    }
+    out.extend_from_slice(constructor);
+
+    let deploy = if options.immutables.is_empty() {
+        vec![size(), op("dup1"), ptr(), lit(0), op("codecopy"), lit(0), op("return")]
+    } else {
+        let mut deploy = vec![size(), ptr(), lit(PATCH_BASE), op("codecopy")];
+        for name in &options.immutables {
+            // The value is on top; the last slot of the name consumes it
+            let named: Vec<_> = slots.iter().filter(|slot| &slot.name == name).collect();
+            if named.is_empty() {
+                deploy.push(op("pop"));
+            }
+            for (i, slot) in named.iter().enumerate() {
+                deploy.extend(patch(slot, i + 1 == named.len()));
+            }
+        }
+        deploy.extend([size(), lit(PATCH_BASE), op("return")]);
+        deploy
+    };
+
+    out.push(AsmElement::Segment(DEPLOY_LABEL.to_string(), deploy));
+    out.push(AsmElement::BytesSegment(RUNTIME_SEGMENT.to_string(), runtime));
+    Ok(out)
+}
+
+/// Write the value on top of the stack into `slot` of the runtime copied to memory.
+fn patch(slot: &ImmutableSlot, consume: bool) -> Vec<AsmElement> {
+    let mut out = Vec::new();
+    if slot.width == 32 {
+        if !consume {
+            out.push(op("dup1"));
+        }
+        out.extend([lit(PATCH_BASE + slot.offset), op("mstore")]);
+        return out;
+    }
+    if !consume {
+        out.push(op("dup1"));
+    }
+    // Cut the value to the slot, so that it cannot spill into the bytes before it
+    out.extend([AsmElement::Literal(vec![0xff; slot.width]), op("and")]);
+    // Keep the bytes before the slot: clear the low bytes of the word ending with it
+    let word = PATCH_BASE + slot.offset + slot.width - 32;
+    let bits = 8 * slot.width;
+    out.extend([lit(word), op("mload"), lit(bits), op("shr"), lit(bits), op("shl")]);
+    out.extend([op("or"), lit(word), op("mstore")]);
+    out
+}
+
+/// `"immutable:name"` or `"immutable:name:width"`, or None for any other string.
+pub fn parse_immutable(s: &str) -> Option<Result<AsmElement, AssemblerError>> {
+    let rest = s.strip_prefix("immutable:")?;
+    let (name, width) = match rest.split_once(':') {
+        Some((name, width)) => (name, width.parse().ok().filter(|w| (1..=32).contains(w))),
+        None => (rest, Some(32)),
+    };
+    Some(match width {
+        Some(width) if !name.is_empty() => Ok(AsmElement::Immutable(name.to_string(), width)),
+        _ => Err(AssemblerError::InvalidContract(format!(
+            "immutables are \"immutable:name\" or \"immutable:name:width\" with a width of 1 to 32 bytes: {}",
+            s
+        ))),
+    })
+}
+
+fn op(name: &str) -> AsmElement {
+    AsmElement::Opcode(name.to_string())
+}
+
+fn lit(value: usize) -> AsmElement {
+    AsmElement::Literal(value.to_be_bytes().iter().skip_while(|&&b| b == 0).copied().collect())
+}
+
+fn ptr() -> AsmElement {
+    AsmElement::BytesPtr(RUNTIME_SEGMENT.to_string())
+}
+
+fn size() -> AsmElement {
+    AsmElement::BytesSize(RUNTIME_SEGMENT.to_string())
+}
+
+/// The first definition of `runtime` or `deploy` in `constructor`.
+fn reserved_name(constructor: &[AsmElement]) -> Option<&str> {
+    constructor.iter().find_map(|elem| match elem {
+        AsmElement::Segment(name, inner) => {
+            if name == DEPLOY_LABEL || name == RUNTIME_SEGMENT {
+                Some(name.as_str())
+            } else {
+                reserved_name(inner)
+            }
+        }
+        AsmElement::BytesSegment(name, _) | AsmElement::JumpTable(name, _)
+            if name == DEPLOY_LABEL || name == RUNTIME_SEGMENT =>
+        {
+            Some(name.as_str())
+        }
+        _ => None,
+    })
+}
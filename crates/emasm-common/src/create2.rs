@@ -0,0 +1,116 @@
+use alloy_primitives::{keccak256, Address, B256};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Keccak-256 of the initcode, the hash CREATE2 addresses are derived from.
+pub fn initcode_hash(initcode: &[u8]) -> B256 {
+    keccak256(initcode)
+}
+
+/// Address of a contract deployed by `deployer` with CREATE2:
+/// `keccak256(0xff ++ deployer ++ salt ++ initcode_hash)[12..]`.
+pub fn create2_address(deployer: Address, salt: B256, initcode_hash: B256) -> Address {
+    deployer.create2(salt, initcode_hash)
+}
+
+/// Addresses a salt search accepts: a hex prefix and a number of leading zero bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressPattern {
+    /// Required leading nibbles.
+    pub prefix: Vec<u8>,
+    pub leading_zero_bytes: usize,
+}
+
+impl AddressPattern {
+    /// Pattern for a hex prefix such as `"0xdead"`; odd lengths match half a byte.
+    pub fn prefix(hex: &str) -> Result<Self, String> {
+        let digits = hex.strip_prefix("0x").unwrap_or(hex);
+        let prefix = digits
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()
+            .filter(|nibbles| nibbles.len() <= 40)
+            .ok_or_else(|| format!("invalid address prefix: {}", hex))?;
+        Ok(Self { prefix, ..Self::default() })
+    }
+
+    pub fn with_leading_zero_bytes(mut self, count: usize) -> Self {
+        self.leading_zero_bytes = count;
+        self
+    }
+
+    pub fn matches(&self, address: &Address) -> bool {
+        let bytes = address.as_slice();
+        let nibble = |i: usize| if i.is_multiple_of(2) { bytes[i / 2] >> 4 } else { bytes[i / 2] & 0x0f };
+        self.leading_zero_bytes <= bytes.len()
+            && bytes[..self.leading_zero_bytes].iter().all(|&b| b == 0)
+            && self.prefix.iter().enumerate().all(|(i, &n)| nibble(i) == n)
+    }
+}
+
+/// Parameters of [`mine_salt`].
+#[derive(Debug, Clone)]
+pub struct SaltSearch {
+    pub pattern: AddressPattern,
+    /// Salt whose last 8 bytes are replaced by the attempt counter, so the first
+    /// 24 bytes can hold e.g. the caller a factory requires.
+    pub base: B256,
+    /// Give up after this many attempts.
+    pub max_attempts: u64,
+    pub threads: usize,
+}
+
+impl Default for SaltSearch {
+    fn default() -> Self {
+        Self {
+            pattern: AddressPattern::default(),
+            base: B256::ZERO,
+            max_attempts: u64::MAX,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl SaltSearch {
+    /// Salt tried at attempt `counter`.
+    pub fn salt(&self, counter: u64) -> B256 {
+        let mut salt = self.base;
+        salt[24..].copy_from_slice(&counter.to_be_bytes());
+        salt
+    }
+}
+
+/// Search salts for a CREATE2 address that matches `search.pattern`.
+///
+/// Attempts are spread over `search.threads` threads, and the salt with the
+/// lowest counter wins, so the result does not depend on thread timing.
+pub fn mine_salt(deployer: Address, initcode_hash: B256, search: &SaltSearch) -> Option<(B256, Address)> {
+    let threads = search.threads.max(1) as u64;
+    let best = AtomicU64::new(u64::MAX);
+
+    std::thread::scope(|scope| {
+        for start in 0..threads {
+            let best = &best;
+            scope.spawn(move || {
+                let mut counter = start;
+                // Counters below the best match are all checked by some thread
+                while counter < search.max_attempts && counter < best.load(Ordering::Relaxed) {
+                    let address = create2_address(deployer, search.salt(counter), initcode_hash);
+                    if search.pattern.matches(&address) {
+                        best.fetch_min(counter, Ordering::Relaxed);
+                        return;
+                    }
+                    counter = match counter.checked_add(threads) {
+                        Some(next) => next,
+                        None => return,
+                    };
+                }
+            });
+        }
+    });
+
+    let counter = best.into_inner();
+    (counter != u64::MAX).then(|| {
+        let salt = search.salt(counter);
+        (salt, create2_address(deployer, salt, initcode_hash))
+    })
+}
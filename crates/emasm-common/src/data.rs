@@ -0,0 +1,56 @@
+use crate::types::*;
+use std::path::Path;
+
+/// Largest `"fill:"` count, the most bytes any code or data section can hold.
+const MAX_FILL: usize = 0xffff;
+
+/// Bytes of one data directive of a bytes segment:
+///
+/// - `"0xdeadbeef"` (the `0x` is optional): hex data;
+/// - `"utf8:Not authorized"`: the UTF-8 bytes of the text, e.g. a revert message;
+/// - `"fill:32"` or `"fill:32:0xff"`: that many copies of a byte, zero by default;
+/// - `"file:data/table.bin"`: the contents of a file, relative to `base_dir`.
+pub fn parse_data(directive: &str, base_dir: &Path) -> Result<Vec<u8>, AssemblerError> {
+    let invalid = |reason: String| AssemblerError::InvalidBytesSegment(format!("{}: {}", directive, reason));
+
+    if let Some(text) = directive.strip_prefix("utf8:") {
+        return Ok(text.as_bytes().to_vec());
+    }
+    if let Some(path) = directive.strip_prefix("file:") {
+        return std::fs::read(base_dir.join(path)).map_err(|e| invalid(e.to_string()));
+    }
+    if let Some(fill) = directive.strip_prefix("fill:") {
+        let (count, byte) = fill.split_once(':').unwrap_or((fill, "0x00"));
+        let count = parse_number(count).ok_or_else(|| invalid("invalid count".to_string()))?;
+        if count > MAX_FILL {
+            return Err(invalid(format!("the count must be at most {}", MAX_FILL)));
+        }
+        let byte = parse_number(byte)
+            .and_then(|b| u8::try_from(b).ok())
+            .ok_or_else(|| invalid("the fill value must be one byte".to_string()))?;
+        return Ok(vec![byte; count]);
+    }
+
+    let digits = directive.strip_prefix("0x").unwrap_or(directive);
+    let padded = if digits.len().is_multiple_of(2) { digits.to_string() } else { format!("0{}", digits) };
+    hex::decode(&padded).map_err(|_| AssemblerError::InvalidHexLiteral(directive.to_string()))
+}
+
+/// `"align:32"`: zero bytes up to the next multiple of 32, or None for any other string.
+pub fn parse_align(s: &str) -> Option<Result<AsmElement, AssemblerError>> {
+    let alignment = s.strip_prefix("align:")?;
+    Some(
+        parse_number(alignment)
+            .filter(|&n| n > 0)
+            .map(AsmElement::Align)
+            .ok_or_else(|| AssemblerError::ParseError(format!("invalid alignment: {}", s))),
+    )
+}
+
+/// Decimal or `0x` hex number.
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
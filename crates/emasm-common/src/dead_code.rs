@@ -0,0 +1,149 @@
+use crate::{
+    cfg::{Cfg, InstrKind, PushOperand},
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::{HashMap, HashSet};
+
+/// What dead-code elimination removed from a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeadCodeReport {
+    /// Segments that nothing jumps to or falls into.
+    pub removed_segments: Vec<String>,
+    /// Segments only entered by fall-through, now emitted without a JUMPDEST.
+    pub inlined_segments: Vec<String>,
+    /// Bytes segments and jump tables whose pointer and size are never pushed.
+    pub removed_bytes_segments: Vec<String>,
+    /// Instructions dropped after STOP, RETURN, REVERT, INVALID, SELFDESTRUCT or JUMP.
+    pub removed_instructions: usize,
+    /// Difference in assembled size.
+    pub bytes_saved: usize,
+}
+
+impl std::fmt::Display for DeadCodeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.removed_segments.is_empty() {
+            writeln!(f, "removed segments: {}", self.removed_segments.join(", "))?;
+        }
+        if !self.inlined_segments.is_empty() {
+            writeln!(f, "inlined segments: {}", self.inlined_segments.join(", "))?;
+        }
+        if !self.removed_bytes_segments.is_empty() {
+            writeln!(f, "removed bytes segments: {}", self.removed_bytes_segments.join(", "))?;
+        }
+        writeln!(f, "removed instructions: {}", self.removed_instructions)?;
+        write!(f, "bytes saved: {}", self.bytes_saved)
+    }
+}
+
+/// Remove code that can never execute and JUMPDESTs that are never jumped to.
+///
+/// A segment is kept as a jump target only if its label is pushed by
+/// reachable code; other segments are dropped when execution cannot fall
+/// into them, and spliced into their parent without a JUMPDEST when it can.
+/// Instructions after an unconditional terminator are dropped up to the next
+/// jump target. An explicit `"jumpdest"` opcode is kept, together with the
+/// code after it, since it may be the target of a computed jump.
+///
+/// `bytes_saved` is left at zero; the assembler fills it in.
+pub fn eliminate_dead_code(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+) -> (Vec<AsmElement>, DeadCodeReport) {
+    let cfg = Cfg::build(elements, opcodes);
+    let mut targets = HashSet::new();
+    let mut data_refs = HashSet::new();
+    for (b, reachable) in cfg.reachable().into_iter().enumerate() {
+        if !reachable {
+            continue;
+        }
+        targets.extend(cfg.pushed_labels(b).into_iter().map(str::to_string));
+        for instr in cfg.block_instrs(b) {
+            match &instr.kind {
+                InstrKind::Push(PushOperand::BytesPtr(l) | PushOperand::BytesSize(l)) => {
+                    data_refs.insert(l.clone());
+                }
+                // Offsets of a segment only exist if it keeps its place and JUMPDEST
+                InstrKind::Push(PushOperand::Offset(expr)) => {
+                    for name in expr.names() {
+                        targets.insert(name.to_string());
+                        data_refs.insert(name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut pass = Pass {
+        opcodes,
+        targets: &targets,
+        data_refs: &data_refs,
+        report: DeadCodeReport::default(),
+    };
+    let mut live = true;
+    let result = pass.run(elements, &mut live);
+    (result, pass.report)
+}
+
+struct Pass<'a> {
+    opcodes: &'a HashMap<&'static str, Opcode>,
+    targets: &'a HashSet<String>,
+    data_refs: &'a HashSet<String>,
+    report: DeadCodeReport,
+}
+
+impl Pass<'_> {
+    /// `live` tracks whether execution can fall through to the current element.
+    fn run(&mut self, elements: &[AsmElement], live: &mut bool) -> Vec<AsmElement> {
+        let mut result = Vec::new();
+        for elem in elements {
+            match elem {
+                AsmElement::Segment(label, inner) if self.targets.contains(label) => {
+                    *live = true;
+                    let inner = self.run(inner, live);
+                    result.push(AsmElement::Segment(label.clone(), inner));
+                }
+                AsmElement::Segment(label, inner) => {
+                    if *live {
+                        self.report.inlined_segments.push(label.clone());
+                    } else {
+                        self.report.removed_segments.push(label.clone());
+                    }
+                    // Nested jump targets survive even when their parent does not
+                    result.extend(self.run(inner, live));
+                }
+                AsmElement::BytesSegment(label, _) | AsmElement::JumpTable(label, _) => {
+                    if self.data_refs.contains(label) {
+                        result.push(elem.clone());
+                    } else {
+                        self.report.removed_bytes_segments.push(label.clone());
+                    }
+                }
+                // Padding and positions are layout, not code
+                AsmElement::Align(_) | AsmElement::Anchor(_) => result.push(elem.clone()),
+                AsmElement::Opcode(name) => match self.opcodes.get(name.as_str()) {
+                    Some(&Opcode::JUMPDEST) => {
+                        *live = true;
+                        result.push(elem.clone());
+                    }
+                    Some(op) if *live && op.is_terminator() => {
+                        *live = false;
+                        result.push(elem.clone());
+                    }
+                    _ => self.keep_if_live(elem, *live, &mut result),
+                },
+                _ => self.keep_if_live(elem, *live, &mut result),
+            }
+        }
+        result
+    }
+
+    fn keep_if_live(&mut self, elem: &AsmElement, live: bool, result: &mut Vec<AsmElement>) {
+        if live {
+            result.push(elem.clone());
+        } else {
+            self.report.removed_instructions += 1;
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{eof, opcodes::Opcode};
+use std::fmt;
+
+/// One instruction decoded from bytecode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub pc: usize,
+    pub opcode: Opcode,
+    /// PUSH data; shorter than the opcode's width if the code ends inside it.
+    pub immediate: Vec<u8>,
+}
+
+impl Instruction {
+    /// Bytes the instruction occupies in the code.
+    pub fn size(&self) -> usize {
+        1 + self.immediate.len()
+    }
+
+    /// Whether the code ends before the PUSH data does.
+    pub fn is_truncated(&self) -> bool {
+        self.immediate.len() < self.opcode.immediate_size()
+    }
+}
+
+/// Decode `bytecode` front to back, the way the EVM sees it: PUSH data is
+/// skipped, and data segments decode as whatever opcodes their bytes are.
+pub fn disassemble(bytecode: &[u8]) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while pc < bytecode.len() {
+        let opcode = Opcode(bytecode[pc]);
+        let end = (pc + 1 + opcode.immediate_size()).min(bytecode.len());
+        instructions.push(Instruction { pc, opcode, immediate: bytecode[pc + 1..end].to_vec() });
+        pc = end;
+    }
+    instructions
+}
+
+/// Decode an EOF code section, whose RJUMP, CALLF and data instructions
+/// have immediates too; see [`EofContainer`](crate::eof::EofContainer) for
+/// whole containers.
+pub fn disassemble_eof(code: &[u8]) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let end = (pc + 1 + eof::immediate_size(code, pc)).min(code.len());
+        instructions.push(Instruction { pc, opcode: Opcode(code[pc]), immediate: code[pc + 1..end].to_vec() });
+        pc = end;
+    }
+    instructions
+}
+
+/// Encode instructions back into bytecode; the inverse of [`disassemble`].
+pub fn reassemble(instructions: &[Instruction]) -> Vec<u8> {
+    let mut bytecode = Vec::new();
+    for instruction in instructions {
+        bytecode.push(instruction.opcode.0);
+        bytecode.extend(&instruction.immediate);
+    }
+    bytecode
+}
+
+/// `0x0003: PUSH1 0x06`, with bytes that are not opcodes as `UNKNOWN(0x0c)`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06x}: ", self.pc)?;
+        match self.opcode.info() {
+            Some(info) => write!(f, "{}", info.name)?,
+            None => write!(f, "UNKNOWN({:#04x})", self.opcode.0)?,
+        }
+        if self.opcode.immediate_size() > 0 {
+            write!(f, " 0x{}", hex::encode(&self.immediate))?;
+        }
+        Ok(())
+    }
+}
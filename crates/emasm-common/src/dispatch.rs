@@ -0,0 +1,240 @@
+use crate::{
+    abi::Signature,
+    jumptable::table_jump,
+    types::*,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Binary search switches to linear comparisons below this many functions.
+const BINARY_LEAF_SIZE: usize = 4;
+
+/// Largest jump table tried, in index bits.
+const MAX_TABLE_BITS: u32 = 8;
+
+/// How the selector is matched against the known functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DispatchMode {
+    /// Compare against each selector in turn.
+    #[default]
+    Linear,
+    /// Binary search over the sorted selectors.
+    Binary,
+    /// Index a jump table with a few selector bits, then check the one candidate.
+    JumpTable,
+}
+
+impl std::str::FromStr for DispatchMode {
+    type Err = AssemblerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "binary" => Ok(Self::Binary),
+            "jumptable" => Ok(Self::JumpTable),
+            other => Err(AssemblerError::InvalidDispatch(format!("unknown mode: {}", other))),
+        }
+    }
+}
+
+/// Function selector dispatcher: `["dispatch", {"sig": "label", ...}]`.
+///
+/// The generated code reads the selector from calldata and jumps to the label
+/// of the matching function, or to the fallback. Handlers and the fallback are
+/// entered with the selector still on the stack, as Solidity does. Without a
+/// fallback label, unknown selectors and calldata shorter than four bytes
+/// revert with empty data.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Dispatcher {
+    /// Function signatures and the labels that handle them, in source order.
+    pub functions: Vec<(String, String)>,
+    pub fallback: Option<String>,
+    pub mode: DispatchMode,
+}
+
+impl Dispatcher {
+    pub fn new(functions: Vec<(String, String)>) -> Self {
+        Self { functions, ..Default::default() }
+    }
+
+    /// Build a dispatcher from `(signature, label)` pairs and `(key, value)`
+    /// options, where the keys are `fallback` and `mode`.
+    pub fn from_parts(
+        functions: Vec<(String, String)>,
+        options: Vec<(String, String)>,
+    ) -> Result<Self, AssemblerError> {
+        let mut dispatcher = Self::new(functions);
+        for (key, value) in options {
+            match key.as_str() {
+                "fallback" => dispatcher.fallback = Some(value),
+                "mode" => dispatcher.mode = value.parse()?,
+                other => {
+                    return Err(AssemblerError::InvalidDispatch(format!("unknown option: {}", other)))
+                }
+            }
+        }
+        Ok(dispatcher)
+    }
+
+    /// Selectors with their handler labels, sorted by selector.
+    pub fn selectors(&self) -> Result<Vec<(u32, String)>, AssemblerError> {
+        let mut cases = Vec::new();
+        let mut seen = HashSet::new();
+        for (signature, label) in &self.functions {
+            let signature = Signature::parse(signature)?;
+            let selector = u32::from_be_bytes(signature.selector());
+            if !seen.insert(selector) {
+                return Err(AssemblerError::InvalidDispatch(format!(
+                    "selector 0x{:08x} of {} is used twice",
+                    selector, signature
+                )));
+            }
+            cases.push((selector, label.clone()));
+        }
+        cases.sort();
+        Ok(cases)
+    }
+
+    /// ABI JSON entries for the dispatched functions, in source order.
+    pub fn abi(&self) -> Result<Vec<Value>, AssemblerError> {
+        self.functions
+            .iter()
+            .map(|(signature, _)| Ok(Signature::parse(signature)?.function_abi()))
+            .collect()
+    }
+
+    /// Expand into plain elements.
+    ///
+    /// Generated segments are named after the selectors they handle
+    /// (`dispatch_<selector>`, `dispatch_lt_<selector>`), plus
+    /// `dispatch_table` and `dispatch_fallback`, so a program can hold one
+    /// dispatcher.
+    pub fn lower(&self) -> Result<Vec<AsmElement>, AssemblerError> {
+        let cases = self.selectors()?;
+        let fallback = self.fallback.clone().unwrap_or_else(|| "dispatch_fallback".to_string());
+
+        let mut out = vec![
+            lit(&[]),
+            op("calldataload"),
+            lit(&[0xe0]),
+            op("shr"),
+            // Shorter calldata cannot hold a selector
+            lit(&[0x04]),
+            op("calldatasize"),
+            op("lt"),
+            AsmElement::Label(fallback.clone()),
+            op("jumpi"),
+        ];
+        match self.mode {
+            DispatchMode::Linear => linear(&cases, &fallback, &mut out),
+            DispatchMode::Binary => binary(&cases, &fallback, &mut out),
+            DispatchMode::JumpTable => jump_table(&cases, &fallback, &mut out)?,
+        }
+        if self.fallback.is_none() {
+            out.push(AsmElement::Segment(fallback, vec![lit(&[]), lit(&[]), op("revert")]));
+        }
+        Ok(out)
+    }
+}
+
+/// ABI JSON for every function dispatched in a program.
+pub fn abi_json(dispatchers: &[Dispatcher]) -> Result<Value, AssemblerError> {
+    let mut entries = Vec::new();
+    for dispatcher in dispatchers {
+        entries.extend(dispatcher.abi()?);
+    }
+    Ok(Value::Array(entries))
+}
+
+fn op(name: &str) -> AsmElement {
+    AsmElement::Opcode(name.to_string())
+}
+
+fn lit(data: &[u8]) -> AsmElement {
+    AsmElement::Literal(data.to_vec())
+}
+
+fn compare(selector: u32, label: &str, out: &mut Vec<AsmElement>) {
+    out.extend([
+        op("dup1"),
+        lit(&selector.to_be_bytes()),
+        op("eq"),
+        AsmElement::Label(label.to_string()),
+        op("jumpi"),
+    ]);
+}
+
+fn linear(cases: &[(u32, String)], fallback: &str, out: &mut Vec<AsmElement>) {
+    for (selector, label) in cases {
+        compare(*selector, label, out);
+    }
+    out.extend([AsmElement::Label(fallback.to_string()), op("jump")]);
+}
+
+fn binary(cases: &[(u32, String)], fallback: &str, out: &mut Vec<AsmElement>) {
+    if cases.len() <= BINARY_LEAF_SIZE {
+        return linear(cases, fallback, out);
+    }
+    let (lower, upper) = cases.split_at(cases.len() / 2);
+    let pivot = upper[0].0;
+    let left = format!("dispatch_lt_{:08x}", pivot);
+    // pivot > selector: search the lower half
+    out.extend([
+        op("dup1"),
+        lit(&pivot.to_be_bytes()),
+        op("gt"),
+        AsmElement::Label(left.clone()),
+        op("jumpi"),
+    ]);
+    binary(upper, fallback, out);
+    let mut inner = Vec::new();
+    binary(lower, fallback, &mut inner);
+    out.push(AsmElement::Segment(left, inner));
+}
+
+fn jump_table(cases: &[(u32, String)], fallback: &str, out: &mut Vec<AsmElement>) -> Result<(), AssemblerError> {
+    let (shift, bits) = find_index_bits(cases).ok_or_else(|| {
+        AssemblerError::InvalidDispatch(format!(
+            "no {}-bit window of the selectors tells them apart",
+            MAX_TABLE_BITS
+        ))
+    })?;
+    let mask = (1u32 << bits) - 1;
+    let index = |selector: u32| ((selector >> shift) & mask) as usize;
+
+    out.push(op("dup1"));
+    if shift > 0 {
+        out.extend([lit(&[shift as u8]), op("shr")]);
+    }
+    out.extend([lit(&[mask as u8]), op("and")]);
+    out.extend(table_jump("dispatch_table"));
+
+    let mut entries = vec![fallback.to_string(); 1 << bits];
+    let mut stubs = Vec::new();
+    for (selector, label) in cases {
+        let stub = format!("dispatch_{:08x}", selector);
+        entries[index(*selector)] = stub.clone();
+        // Other selectors share the slot, so check the whole selector
+        let mut body = Vec::new();
+        compare(*selector, label, &mut body);
+        body.extend([AsmElement::Label(fallback.to_string()), op("jump")]);
+        stubs.push(AsmElement::Segment(stub, body));
+    }
+    out.push(AsmElement::JumpTable("dispatch_table".to_string(), entries));
+    out.extend(stubs);
+    Ok(())
+}
+
+/// Smallest window of selector bits, as (shift, width), that gives every
+/// selector its own slot.
+fn find_index_bits(cases: &[(u32, String)]) -> Option<(u32, u32)> {
+    let min_bits = cases.len().max(1).next_power_of_two().trailing_zeros();
+    (min_bits..=MAX_TABLE_BITS).find_map(|bits| {
+        let mask = (1u32 << bits) - 1;
+        (0..=32 - bits).find(|&shift| {
+            let mut seen = HashSet::new();
+            cases.iter().all(|(selector, _)| seen.insert((selector >> shift) & mask))
+        })
+        .map(|shift| (shift, bits))
+    })
+}
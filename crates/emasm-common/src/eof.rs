@@ -0,0 +1,651 @@
+//! EOF v1 containers (EIP-3540, EIP-3670, EIP-4750, EIP-5450).
+//!
+//! An [`EofProgram`] is a list of functions, each assembled into a code
+//! section of its own; the first one is the entry point. EOF code has no
+//! JUMPDESTs or absolute jumps, so segments and anchors only name positions
+//! for the relative jumps of the same function: `"rjump:label"`,
+//! `"rjumpi:label"` and the jump table `"rjumpv:l0,l1,..."`. `"callf:name"`
+//! and `"jumpf:name"` call and tail-call other functions, and `"retf"`
+//! returns from one.
+//!
+//! Bytes segments of every function are collected, in order, into the data
+//! section: their name or `"bytes:name:ptr"` pushes the segment's offset in
+//! it for `dataload` and `datacopy`, `"bytes:name:size"` its size, and
+//! `"dataloadn:name"` loads its first word.
+//!
+//! Assembled containers are checked by [`validate_eof`](crate::eof_verify::validate_eof).
+
+use crate::{
+    assembler::encode_push,
+    cfg::{InstrKind, PushOperand},
+    disasm::{disassemble_eof, Instruction},
+    layout::{Layout, LayoutEntry},
+    opcodes::{Opcode, OpcodeInfo},
+    slots::lower_stack_slots,
+    types::*,
+};
+use std::{collections::HashMap, fmt};
+
+pub const MAGIC: [u8; 2] = [0xef, 0x00];
+pub const VERSION: u8 = 1;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_CONTAINER: u8 = 0x03;
+const KIND_DATA: u8 = 0x04;
+const TERMINATOR: u8 = 0x00;
+
+/// `outputs` of a function that never returns to its caller.
+pub const NON_RETURNING: u8 = 0x80;
+/// Largest `max_stack_height` a types entry may declare.
+pub const MAX_STACK_HEIGHT: u16 = 0x3ff;
+const MAX_CODE_SECTIONS: usize = 1024;
+const MAX_CONTAINERS: usize = 256;
+
+/// A function of an EOF program, assembled into its own code section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EofFunction {
+    pub name: String,
+    pub inputs: u8,
+    /// None for a function that never returns to its caller.
+    pub outputs: Option<u8>,
+    /// Largest stack height the code reaches, inputs included.
+    pub max_stack_height: u16,
+    pub code: Vec<AsmElement>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EofProgram {
+    /// Code sections in order; the first is the entry point and must take no
+    /// inputs and not return.
+    pub functions: Vec<EofFunction>,
+}
+
+/// Types section entry of a code section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EofType {
+    pub inputs: u8,
+    /// Number of outputs, or [`NON_RETURNING`].
+    pub outputs: u8,
+    pub max_stack_height: u16,
+}
+
+/// A decoded EOF v1 container.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EofContainer {
+    pub types: Vec<EofType>,
+    pub code: Vec<Vec<u8>>,
+    pub containers: Vec<EofContainer>,
+    pub data: Vec<u8>,
+}
+
+impl EofFunction {
+    pub fn new(
+        name: impl Into<String>,
+        inputs: u8,
+        outputs: Option<u8>,
+        max_stack_height: u16,
+        code: Vec<AsmElement>,
+    ) -> Self {
+        Self { name: name.into(), inputs, outputs, max_stack_height, code }
+    }
+}
+
+impl EofProgram {
+    pub fn new(functions: Vec<EofFunction>) -> Self {
+        Self { functions }
+    }
+}
+
+impl EofType {
+    pub fn is_returning(&self) -> bool {
+        self.outputs != NON_RETURNING
+    }
+}
+
+impl EofContainer {
+    /// Decode a container, checking the header and that section sizes add up
+    /// to the length of `bytes`. Code is not validated.
+    pub fn decode(bytes: &[u8]) -> Result<Self, AssemblerError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(2)? != MAGIC {
+            return Err(invalid("missing EOF magic 0xef00"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported EOF version {}", version)));
+        }
+
+        reader.expect_kind(KIND_TYPES)?;
+        let types_size = reader.u16()? as usize;
+        reader.expect_kind(KIND_CODE)?;
+        let code_sizes = reader.sizes(MAX_CODE_SECTIONS, "code sections")?;
+        if types_size != code_sizes.len() * 4 {
+            return Err(invalid(format!(
+                "types section of {} bytes for {} code sections",
+                types_size,
+                code_sizes.len()
+            )));
+        }
+        let container_sizes = match reader.peek()? {
+            KIND_CONTAINER => {
+                reader.u8()?;
+                reader.sizes(MAX_CONTAINERS, "containers")?
+            }
+            _ => Vec::new(),
+        };
+        reader.expect_kind(KIND_DATA)?;
+        let data_size = reader.u16()? as usize;
+        reader.expect_kind(TERMINATOR)?;
+
+        let mut types = Vec::new();
+        for _ in 0..code_sizes.len() {
+            let (inputs, outputs, max_stack_height) = (reader.u8()?, reader.u8()?, reader.u16()?);
+            types.push(EofType { inputs, outputs, max_stack_height });
+        }
+        let mut code = Vec::new();
+        for size in code_sizes {
+            code.push(reader.take(size)?.to_vec());
+        }
+        let mut containers = Vec::new();
+        for size in container_sizes {
+            containers.push(Self::decode(reader.take(size)?)?);
+        }
+        let data = reader.take(data_size)?.to_vec();
+        if reader.pos != bytes.len() {
+            return Err(invalid(format!("{} bytes after the data section", bytes.len() - reader.pos)));
+        }
+
+        Ok(Self { types, code, containers, data })
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, AssemblerError> {
+        if self.types.len() != self.code.len() {
+            return Err(invalid(format!("{} types entries for {} code sections", self.types.len(), self.code.len())));
+        }
+        if self.code.is_empty() || self.code.len() > MAX_CODE_SECTIONS {
+            return Err(invalid(format!("{} code sections, expected 1 to {}", self.code.len(), MAX_CODE_SECTIONS)));
+        }
+        if self.containers.len() > MAX_CONTAINERS {
+            return Err(invalid(format!("{} containers, at most {} allowed", self.containers.len(), MAX_CONTAINERS)));
+        }
+        let containers = self.containers.iter().map(Self::encode).collect::<Result<Vec<_>, _>>()?;
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(KIND_TYPES);
+        bytes.extend(section_size(self.types.len() * 4, "types section")?);
+        bytes.push(KIND_CODE);
+        bytes.extend((self.code.len() as u16).to_be_bytes());
+        for code in &self.code {
+            bytes.extend(section_size(code.len(), "code section")?);
+        }
+        if !containers.is_empty() {
+            bytes.push(KIND_CONTAINER);
+            bytes.extend((containers.len() as u16).to_be_bytes());
+            for container in &containers {
+                bytes.extend(section_size(container.len(), "container")?);
+            }
+        }
+        bytes.push(KIND_DATA);
+        bytes.extend(section_size(self.data.len(), "data section")?);
+        bytes.push(TERMINATOR);
+
+        for ty in &self.types {
+            bytes.extend([ty.inputs, ty.outputs]);
+            bytes.extend(ty.max_stack_height.to_be_bytes());
+        }
+        for code in &self.code {
+            bytes.extend(code);
+        }
+        for container in containers {
+            bytes.extend(container);
+        }
+        bytes.extend(&self.data);
+        Ok(bytes)
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: &str) -> fmt::Result {
+        writeln!(
+            f,
+            "{}EOF v{}: {} code sections, {} containers, {} data bytes",
+            indent,
+            VERSION,
+            self.code.len(),
+            self.containers.len(),
+            self.data.len()
+        )?;
+        for (i, (ty, code)) in self.types.iter().zip(&self.code).enumerate() {
+            let outputs = match ty.is_returning() {
+                true => format!("{} outputs", ty.outputs),
+                false => "non-returning".to_string(),
+            };
+            writeln!(
+                f,
+                "{}code section {}: {} inputs, {}, max stack {}",
+                indent, i, ty.inputs, outputs, ty.max_stack_height
+            )?;
+            for instruction in disassemble_eof(code) {
+                writeln!(f, "{}  {}", indent, EofInstruction(&instruction))?;
+            }
+        }
+        if !self.data.is_empty() {
+            writeln!(f, "{}data: 0x{}", indent, hex::encode(&self.data))?;
+        }
+        for (i, container) in self.containers.iter().enumerate() {
+            writeln!(f, "{}container {}:", indent, i)?;
+            container.fmt_indented(f, &format!("{}  ", indent))?;
+        }
+        Ok(())
+    }
+}
+
+/// Header line, then every code section's instructions, the data and the
+/// nested containers, indented.
+impl fmt::Display for EofContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, "")
+    }
+}
+
+/// An instruction of an EOF code section, with relative jumps shown as
+/// `RJUMPI +3 (-> 0x000b)` and section indices as `CALLF 1`.
+struct EofInstruction<'a>(&'a Instruction);
+
+impl fmt::Display for EofInstruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Instruction { pc, opcode, immediate } = self.0;
+        write!(f, "{:#06x}: ", pc)?;
+        match eof_info(*opcode) {
+            Some(info) => write!(f, "{}", info.name)?,
+            None => return write!(f, "UNKNOWN({:#04x})", opcode.0),
+        }
+        let end = pc + 1 + immediate.len();
+        match *opcode {
+            Opcode::RJUMP | Opcode::RJUMPI if immediate.len() == 2 => {
+                let offset = read_i16(immediate, 0);
+                write!(f, " {:+} (-> {:#06x})", offset, end as isize + offset as isize)
+            }
+            Opcode::RJUMPV if immediate.len() > 1 => {
+                let offsets: Vec<i16> = (0..immediate.len() / 2).map(|k| read_i16(immediate, 1 + 2 * k)).collect();
+                let targets: Vec<String> = offsets.iter().map(|&o| format!("{:#06x}", end as isize + o as isize)).collect();
+                let offsets: Vec<String> = offsets.iter().map(|o| format!("{:+}", o)).collect();
+                write!(f, " {} (-> {})", offsets.join(", "), targets.join(", "))
+            }
+            Opcode::CALLF | Opcode::JUMPF if immediate.len() == 2 => write!(f, " {}", read_u16(immediate, 0)),
+            Opcode::DATALOADN if immediate.len() == 2 => write!(f, " {:#06x}", read_u16(immediate, 0)),
+            _ if !immediate.is_empty() => write!(f, " 0x{}", hex::encode(immediate)),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], AssemblerError> {
+        let bytes = self.bytes.get(self.pos..self.pos + n).ok_or_else(|| {
+            invalid(format!("container ends at {:#x}, {} more bytes expected", self.bytes.len(), self.pos + n - self.bytes.len()))
+        })?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, AssemblerError> {
+        self.bytes.get(self.pos).copied().ok_or_else(|| invalid("truncated header"))
+    }
+
+    fn u8(&mut self) -> Result<u8, AssemblerError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, AssemblerError> {
+        Ok(read_u16(self.take(2)?, 0))
+    }
+
+    fn expect_kind(&mut self, kind: u8) -> Result<(), AssemblerError> {
+        let found = self.u8()?;
+        if found != kind {
+            return Err(invalid(format!("expected section kind {:#04x} at {:#x}, found {:#04x}", kind, self.pos - 1, found)));
+        }
+        Ok(())
+    }
+
+    /// Section count followed by that many non-zero sizes.
+    fn sizes(&mut self, max: usize, what: &str) -> Result<Vec<usize>, AssemblerError> {
+        let count = self.u16()? as usize;
+        if count == 0 || count > max {
+            return Err(invalid(format!("{} {}, expected 1 to {}", count, what, max)));
+        }
+        let sizes = (0..count).map(|_| self.u16().map(usize::from)).collect::<Result<Vec<_>, _>>()?;
+        if sizes.contains(&0) {
+            return Err(invalid(format!("empty entry among {}", what)));
+        }
+        Ok(sizes)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> AssemblerError {
+    AssemblerError::InvalidEof(message.into())
+}
+
+fn section_size(size: usize, what: &str) -> Result<[u8; 2], AssemblerError> {
+    u16::try_from(size)
+        .map(u16::to_be_bytes)
+        .map_err(|_| invalid(format!("{} of {} bytes is over the 65535 byte limit", what, size)))
+}
+
+pub(crate) fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]])
+}
+
+pub(crate) fn read_i16(bytes: &[u8], at: usize) -> i16 {
+    read_u16(bytes, at) as i16
+}
+
+/// Mnemonic and stack effect of an opcode in EOF code. CALLF, RETF and JUMPF
+/// take their stack effect from the types section instead.
+pub fn eof_info(opcode: Opcode) -> Option<OpcodeInfo> {
+    let (name, inputs, outputs) = match opcode {
+        Opcode::DATALOAD => ("DATALOAD", 1, 1),
+        Opcode::DATALOADN => ("DATALOADN", 0, 1),
+        Opcode::DATASIZE => ("DATASIZE", 0, 1),
+        Opcode::DATACOPY => ("DATACOPY", 3, 0),
+        Opcode::RJUMP => ("RJUMP", 0, 0),
+        Opcode::RJUMPI => ("RJUMPI", 1, 0),
+        Opcode::RJUMPV => ("RJUMPV", 1, 0),
+        Opcode::CALLF => ("CALLF", 0, 0),
+        Opcode::RETF => ("RETF", 0, 0),
+        Opcode::JUMPF => ("JUMPF", 0, 0),
+        _ => return opcode.info(),
+    };
+    Some(OpcodeInfo { name, inputs, outputs })
+}
+
+/// Number of immediate bytes of the instruction at `pc`, including the jump
+/// table of RJUMPV.
+pub fn immediate_size(code: &[u8], pc: usize) -> usize {
+    match Opcode(code[pc]) {
+        Opcode::RJUMP | Opcode::RJUMPI | Opcode::CALLF | Opcode::JUMPF | Opcode::DATALOADN => 2,
+        Opcode::RJUMPV => 1 + code.get(pc + 1).map_or(0, |&max_index| 2 * (max_index as usize + 1)),
+        opcode => opcode.immediate_size(),
+    }
+}
+
+/// EOF instruction written as a string element, see the module docs.
+enum EofOp<'a> {
+    Plain(Opcode),
+    /// RJUMP or RJUMPI to a label.
+    Jump(Opcode, &'a str),
+    /// RJUMPV over labels.
+    Switch(Vec<&'a str>),
+    /// CALLF or JUMPF to a function.
+    Call(Opcode, &'a str),
+    DataLoadN(&'a str),
+}
+
+fn parse_instruction(s: &str) -> Option<EofOp<'_>> {
+    if let Some((name, operand)) = s.split_once(':') {
+        return match name {
+            "rjump" => Some(EofOp::Jump(Opcode::RJUMP, operand)),
+            "rjumpi" => Some(EofOp::Jump(Opcode::RJUMPI, operand)),
+            "rjumpv" => Some(EofOp::Switch(operand.split(',').map(str::trim).collect())),
+            "callf" => Some(EofOp::Call(Opcode::CALLF, operand)),
+            "jumpf" => Some(EofOp::Call(Opcode::JUMPF, operand)),
+            "dataloadn" => Some(EofOp::DataLoadN(operand)),
+            _ => None,
+        };
+    }
+    match s {
+        "retf" => Some(EofOp::Plain(Opcode::RETF)),
+        "dataload" => Some(EofOp::Plain(Opcode::DATALOAD)),
+        "datasize" => Some(EofOp::Plain(Opcode::DATASIZE)),
+        "datacopy" => Some(EofOp::Plain(Opcode::DATACOPY)),
+        _ => None,
+    }
+}
+
+/// Whether `s` is one of the EOF-only instructions, such as `"rjump:loop"`.
+pub fn is_eof_instruction(s: &str) -> bool {
+    parse_instruction(s).is_some()
+}
+
+/// A function's code before relative jumps are resolved; every item has a
+/// fixed size.
+enum Item {
+    Bytes(Vec<u8>, InstrKind),
+    Label(String),
+    Jump(Opcode, String),
+    Switch(Vec<String>),
+}
+
+impl Item {
+    fn size(&self) -> usize {
+        match self {
+            Item::Bytes(bytes, _) => bytes.len(),
+            Item::Label(_) => 0,
+            Item::Jump(..) => 3,
+            Item::Switch(labels) => 2 + 2 * labels.len(),
+        }
+    }
+}
+
+struct Context<'a> {
+    opcodes: &'a HashMap<&'static str, Opcode>,
+    functions: HashMap<&'a str, u16>,
+    data: HashMap<String, BytesInfo>,
+}
+
+/// Assemble `program` into a container, with the layout of each code section.
+/// Element paths start with the function's name.
+pub(crate) fn assemble_eof(
+    program: &EofProgram,
+    opcodes: &HashMap<&'static str, Opcode>,
+) -> Result<(EofContainer, Vec<Layout>), AssemblerError> {
+    let mut functions = HashMap::new();
+    for (i, function) in program.functions.iter().enumerate() {
+        if functions.insert(function.name.as_str(), i as u16).is_some() {
+            return Err(invalid(format!("duplicate function {}", function.name)));
+        }
+    }
+
+    let lowered = program.functions.iter()
+        .map(|function| lower_stack_slots(&function.code, opcodes))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut data = Vec::new();
+    let mut data_map = HashMap::new();
+    for code in &lowered {
+        collect_data(code, &mut data, &mut data_map)?;
+    }
+
+    let context = Context { opcodes, functions, data: data_map };
+    let mut container = EofContainer { data, ..EofContainer::default() };
+    let mut layouts = Vec::new();
+    for (function, code) in program.functions.iter().zip(&lowered) {
+        let mut items = Vec::new();
+        lower_function(code, &ElementPath::default().segment(&function.name), &context, &mut items)?;
+        let (bytecode, layout) = encode_function(&function.name, &items, &context)?;
+        container.types.push(EofType {
+            inputs: function.inputs,
+            outputs: function.outputs.unwrap_or(NON_RETURNING),
+            max_stack_height: function.max_stack_height,
+        });
+        container.code.push(bytecode);
+        layouts.push(layout);
+    }
+
+    Ok((container, layouts))
+}
+
+/// Move every bytes segment into the data section, in program order.
+fn collect_data(
+    elements: &[AsmElement],
+    data: &mut Vec<u8>,
+    map: &mut HashMap<String, BytesInfo>,
+) -> Result<(), AssemblerError> {
+    for elem in elements {
+        match elem {
+            AsmElement::Segment(_, inner) => collect_data(inner, data, map)?,
+            AsmElement::BytesSegment(name, bytes) => {
+                let info = BytesInfo { offset: data.len(), size: bytes.len() };
+                if map.insert(name.clone(), info).is_some() {
+                    return Err(AssemblerError::InvalidBytesSegment(format!("duplicate bytes segment {}", name)));
+                }
+                data.extend(bytes);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn lower_function(
+    elements: &[AsmElement],
+    parent: &ElementPath,
+    context: &Context,
+    items: &mut Vec<(ElementPath, Item)>,
+) -> Result<(), AssemblerError> {
+    for (i, elem) in elements.iter().enumerate() {
+        let path = parent.child(i);
+        let item = match elem {
+            AsmElement::Opcode(name) => match parse_instruction(name) {
+                Some(op) => lower_instruction(op, context)?,
+                None => {
+                    // Opcodes EOF removed are left for validation to report
+                    let opcode = *context.opcodes.get(name.as_str())
+                        .ok_or_else(|| AssemblerError::UnknownOpcode(name.clone()))?;
+                    Item::Bytes(vec![opcode.0], InstrKind::Op(opcode))
+                }
+            },
+            AsmElement::Literal(data) => {
+                let mut bytes = Vec::new();
+                encode_push(&mut bytes, data);
+                Item::Bytes(bytes, InstrKind::Push(PushOperand::Literal(data.clone())))
+            }
+            AsmElement::BytesPtr(name) | AsmElement::BytesSize(name) => {
+                let info = context.data.get(name)
+                    .ok_or_else(|| AssemblerError::LabelNotFound(name.clone()))?;
+                let mut bytes = Vec::new();
+                let kind = match elem {
+                    AsmElement::BytesPtr(_) => {
+                        encode_push(&mut bytes, &info.offset.to_be_bytes());
+                        PushOperand::BytesPtr(name.clone())
+                    }
+                    _ => {
+                        encode_push(&mut bytes, &info.size.to_be_bytes());
+                        PushOperand::BytesSize(name.clone())
+                    }
+                };
+                Item::Bytes(bytes, InstrKind::Push(kind))
+            }
+            AsmElement::Segment(label, inner) => {
+                let head = parent.segment(label);
+                items.push((head.clone(), Item::Label(label.clone())));
+                lower_function(inner, &head, context, items)?;
+                continue;
+            }
+            AsmElement::Anchor(label) => Item::Label(label.clone()),
+            AsmElement::BytesSegment(..) | AsmElement::Let(_) => continue,
+            AsmElement::Label(label) => {
+                return Err(invalid(format!(
+                    "label push {} at {} has no JUMP to use it in EOF code, jump with rjump:{} instead",
+                    label, path, label
+                )));
+            }
+            other => {
+                return Err(invalid(format!("{:?} at {} is not supported in EOF code", other, path)));
+            }
+        };
+        items.push((path, item));
+    }
+    Ok(())
+}
+
+fn lower_instruction(op: EofOp, context: &Context) -> Result<Item, AssemblerError> {
+    Ok(match op {
+        EofOp::Plain(opcode) => Item::Bytes(vec![opcode.0], InstrKind::Op(opcode)),
+        EofOp::Jump(opcode, label) => Item::Jump(opcode, label.to_string()),
+        EofOp::Switch(labels) => {
+            if labels.len() > 256 {
+                return Err(invalid(format!("RJUMPV with {} targets, at most 256 allowed", labels.len())));
+            }
+            Item::Switch(labels.into_iter().map(str::to_string).collect())
+        }
+        EofOp::Call(opcode, name) => {
+            let index = context.functions.get(name)
+                .ok_or_else(|| invalid(format!("unknown function {}", name)))?;
+            let mut bytes = vec![opcode.0];
+            bytes.extend(index.to_be_bytes());
+            Item::Bytes(bytes, InstrKind::Op(opcode))
+        }
+        EofOp::DataLoadN(name) => {
+            let info = context.data.get(name)
+                .ok_or_else(|| AssemblerError::LabelNotFound(name.to_string()))?;
+            let offset = u16::try_from(info.offset).map_err(|_| AssemblerError::IntegerOverflow)?;
+            let mut bytes = vec![Opcode::DATALOADN.0];
+            bytes.extend(offset.to_be_bytes());
+            Item::Bytes(bytes, InstrKind::Op(Opcode::DATALOADN))
+        }
+    })
+}
+
+fn encode_function(
+    name: &str,
+    items: &[(ElementPath, Item)],
+    context: &Context,
+) -> Result<(Vec<u8>, Layout), AssemblerError> {
+    let mut labels = HashMap::new();
+    let mut offset = 0;
+    for (_, item) in items {
+        if let Item::Label(label) = item {
+            if labels.insert(label.clone(), offset).is_some() {
+                return Err(invalid(format!("duplicate label {} in function {}", label, name)));
+            }
+        }
+        offset += item.size();
+    }
+
+    // Offsets are relative to the end of the jump instruction
+    let relative = |label: &str, end: usize| -> Result<[u8; 2], AssemblerError> {
+        let target = *labels.get(label).ok_or_else(|| AssemblerError::LabelNotFound(label.to_string()))?;
+        i16::try_from(target as isize - end as isize)
+            .map(i16::to_be_bytes)
+            .map_err(|_| invalid(format!("{} is out of reach of a relative jump", label)))
+    };
+
+    let mut bytecode = Vec::new();
+    let mut entries = Vec::new();
+    for (path, item) in items {
+        let offset = bytecode.len();
+        let end = offset + item.size();
+        let kind = match item {
+            Item::Bytes(bytes, kind) => {
+                bytecode.extend(bytes);
+                kind.clone()
+            }
+            Item::Label(label) => InstrKind::Anchor(label.clone()),
+            Item::Jump(opcode, label) => {
+                bytecode.push(opcode.0);
+                bytecode.extend(relative(label, end)?);
+                InstrKind::Op(*opcode)
+            }
+            Item::Switch(targets) => {
+                if targets.is_empty() {
+                    return Err(invalid(format!("RJUMPV at {} has no targets", path)));
+                }
+                bytecode.extend([Opcode::RJUMPV.0, (targets.len() - 1) as u8]);
+                for label in targets {
+                    bytecode.extend(relative(label, end)?);
+                }
+                InstrKind::Op(Opcode::RJUMPV)
+            }
+        };
+        entries.push(LayoutEntry { path: path.clone(), offset, size: end - offset, kind });
+    }
+
+    let layout = Layout { labels, bytes: context.data.clone(), entries };
+    Ok((bytecode, layout))
+}
@@ -0,0 +1,421 @@
+//! Validation of EOF containers (EIP-3670, EIP-4750, EIP-5450), without a
+//! client: opcodes, relative jump targets and the stack heights of every code
+//! section against its types entry.
+
+use crate::{
+    eof::{
+        eof_info, immediate_size, read_i16, read_u16, EofContainer, EofType, MAX_STACK_HEIGHT, NON_RETURNING,
+    },
+    layout::Layout,
+    opcodes::Opcode,
+    types::*,
+};
+use std::fmt;
+
+/// Items the EVM stack holds at most.
+const STACK_LIMIT: i32 = 1024;
+
+/// Legacy opcodes that EIP-3670 rejects in EOF code: code introspection,
+/// absolute jumps, gas observation and the legacy call and create family.
+pub fn is_disabled_in_eof(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::CODESIZE | Opcode::CODECOPY | Opcode::EXTCODESIZE | Opcode::EXTCODECOPY | Opcode::EXTCODEHASH
+            | Opcode::JUMP | Opcode::JUMPI | Opcode::PC | Opcode::GAS
+            | Opcode::CREATE | Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::CREATE2
+            | Opcode::STATICCALL | Opcode::SELFDESTRUCT
+    )
+}
+
+/// Whether execution never continues to the next instruction in EOF code.
+pub fn is_terminating_in_eof(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::STOP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID
+            | Opcode::RJUMP | Opcode::RETF | Opcode::JUMPF
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EofIssueKind {
+    /// A byte that is not an opcode.
+    UnknownOpcode(u8),
+    /// A legacy opcode EOF removed, such as JUMP or CODECOPY.
+    DisabledOpcode(&'static str),
+    /// The section ends inside the instruction's immediate bytes.
+    MissingImmediate(&'static str),
+    /// Neither reached from the previous instruction nor by a forward jump.
+    Unreachable(&'static str),
+    /// A relative jump whose target is outside the section.
+    JumpOutOfBounds { target: isize },
+    /// A relative jump into the immediate bytes of another instruction.
+    JumpIntoImmediate { target: usize },
+    /// A backward jump reaches its target with other stack heights than the
+    /// target already has.
+    BackwardJumpHeightMismatch { target: usize },
+    StackUnderflow { opcode: &'static str, required: i32, available: i32 },
+    /// Items on the stack, including the callee's, could exceed the stack limit.
+    StackOverflow { height: i32 },
+    /// CALLF or JUMPF to a code section that does not exist.
+    UnknownSection(usize),
+    /// CALLF to a section that never returns.
+    CallToNonReturning(usize),
+    /// JUMPF to a returning section with more outputs than this one has.
+    JumpfOutputs { target: usize },
+    /// RETF, or JUMPF to a returning section, with other than the expected
+    /// number of items on the stack.
+    ReturnHeight { opcode: &'static str, height: i32, expected: i32 },
+    /// DATALOADN reads past the end of the data section.
+    DataLoadOutOfBounds { offset: usize, data_size: usize },
+    /// The last instruction of the section falls through.
+    MissingTerminator,
+    /// The section returns although it is declared non-returning, or never
+    /// returns although it declares outputs.
+    ReturningMismatch { returns: bool },
+    /// A container without code sections.
+    NoCodeSections,
+    /// The types section has an entry count other than the number of code
+    /// sections.
+    SectionCountMismatch { types: usize, code: usize },
+    /// The first section takes inputs or returns.
+    InvalidEntryPoint,
+    /// Inputs, outputs or max stack height out of range.
+    InvalidType(EofType),
+    MaxStackHeightMismatch { declared: u16, computed: u16 },
+    /// A code section that no CALLF or JUMPF reaches from the first one.
+    UnreachableSection,
+    /// A nested container that no instruction refers to.
+    UnusedContainer(usize),
+}
+
+impl fmt::Display for EofIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EofIssueKind::UnknownOpcode(byte) => write!(f, "unknown opcode {:#04x}", byte),
+            EofIssueKind::DisabledOpcode(name) => write!(f, "{} is not valid in EOF code", name),
+            EofIssueKind::MissingImmediate(name) => write!(f, "{} is missing immediate bytes", name),
+            EofIssueKind::Unreachable(name) => write!(f, "{} is unreachable", name),
+            EofIssueKind::JumpOutOfBounds { target } => {
+                write!(f, "jump target {} is outside the code section", target)
+            }
+            EofIssueKind::JumpIntoImmediate { target } => {
+                write!(f, "jump to {:#x}, inside immediate bytes", target)
+            }
+            EofIssueKind::BackwardJumpHeightMismatch { target } => {
+                write!(f, "stack height differs at {:#x}, the target of a backward jump", target)
+            }
+            EofIssueKind::StackUnderflow { opcode, required, available } => {
+                write!(f, "stack underflow: {} needs {} items, {} available", opcode, required, available)
+            }
+            EofIssueKind::StackOverflow { height } => {
+                write!(f, "stack overflow: up to {} items, over the {} limit", height, STACK_LIMIT)
+            }
+            EofIssueKind::UnknownSection(index) => write!(f, "code section {} does not exist", index),
+            EofIssueKind::CallToNonReturning(index) => {
+                write!(f, "CALLF to non-returning code section {}", index)
+            }
+            EofIssueKind::JumpfOutputs { target } => {
+                write!(f, "JUMPF to code section {}, which returns more outputs", target)
+            }
+            EofIssueKind::ReturnHeight { opcode, height, expected } => {
+                write!(f, "{} items on the stack at {}, expected {}", height, opcode, expected)
+            }
+            EofIssueKind::DataLoadOutOfBounds { offset, data_size } => {
+                write!(f, "DATALOADN {:#x} reads past the {} byte data section", offset, data_size)
+            }
+            EofIssueKind::MissingTerminator => {
+                write!(f, "code section does not end in a terminating instruction")
+            }
+            EofIssueKind::ReturningMismatch { returns: true } => {
+                write!(f, "code section returns but is declared non-returning")
+            }
+            EofIssueKind::ReturningMismatch { returns: false } => {
+                write!(f, "code section declares outputs but never returns")
+            }
+            EofIssueKind::NoCodeSections => write!(f, "container has no code sections"),
+            EofIssueKind::SectionCountMismatch { types, code } => {
+                write!(f, "{} types entries for {} code sections", types, code)
+            }
+            EofIssueKind::InvalidEntryPoint => {
+                write!(f, "first code section must take no inputs and not return")
+            }
+            EofIssueKind::InvalidType(ty) => write!(
+                f,
+                "invalid types entry: {} inputs, {:#04x} outputs, max stack {}",
+                ty.inputs, ty.outputs, ty.max_stack_height
+            ),
+            EofIssueKind::MaxStackHeightMismatch { declared, computed } => {
+                write!(f, "declared max stack height {}, but the code reaches {}", declared, computed)
+            }
+            EofIssueKind::UnreachableSection => {
+                write!(f, "code section is never called from the first one")
+            }
+            EofIssueKind::UnusedContainer(index) => write!(f, "container {} is never used", index),
+        }
+    }
+}
+
+/// A validation failure in code section `section`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EofIssue {
+    pub section: usize,
+    /// Offset in the section of the offending instruction, None for issues
+    /// with the section as a whole.
+    pub pc: Option<usize>,
+    /// The element that emitted the instruction, or the function itself,
+    /// once [`locate`](EofIssue::locate)d.
+    pub path: Option<ElementPath>,
+    pub kind: EofIssueKind,
+}
+
+impl EofIssue {
+    /// Fill in the element path from the layouts of the code sections.
+    ///
+    /// Issues at the end of a section point at its last instruction.
+    pub fn locate(mut self, layouts: &[Layout]) -> Self {
+        let Some(layout) = layouts.get(self.section) else { return self };
+        let entry = match self.pc {
+            Some(pc) => layout.entry_at(pc).or_else(|| layout.entries.iter().rev().find(|e| e.size > 0)),
+            None => None,
+        };
+        // Paths start with the function name, see `Assembler::assemble_eof`
+        let function = layout.entries.first().and_then(|e| e.path.segments.first());
+        self.path = match (entry, function) {
+            (Some(entry), _) => Some(entry.path.clone()),
+            (None, Some(name)) => Some(ElementPath::default().segment(name)),
+            (None, None) => None,
+        };
+        self
+    }
+}
+
+/// `code section 1 at pc 0x4 (double+2): ...`
+impl fmt::Display for EofIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "code section {}", self.section)?;
+        if let Some(pc) = self.pc {
+            write!(f, " at pc {:#x}", pc)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+/// What validating a code section found out about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SectionInfo {
+    max_stack_height: u16,
+    /// Sections it calls or jumps to.
+    targets: Vec<usize>,
+}
+
+/// Validate every code section of `container` against the EOF rules.
+///
+/// Each section is checked in a single forward pass, as EIP-5450 specifies,
+/// so at most one issue is reported per section.
+pub fn validate_eof(container: &EofContainer) -> Vec<EofIssue> {
+    let mut issues = Vec::new();
+    let section_issue = |section: usize, kind| EofIssue { section, pc: None, path: None, kind };
+
+    // Sections are analyzed against their types entry, so both must be there
+    if container.code.is_empty() {
+        return vec![section_issue(0, EofIssueKind::NoCodeSections)];
+    }
+    if container.types.len() != container.code.len() {
+        let kind = EofIssueKind::SectionCountMismatch { types: container.types.len(), code: container.code.len() };
+        return vec![section_issue(0, kind)];
+    }
+    let entry = container.types[0];
+    if entry.inputs != 0 || entry.is_returning() {
+        issues.push(section_issue(0, EofIssueKind::InvalidEntryPoint));
+    }
+    let mut targets = vec![Vec::new(); container.code.len()];
+    for (i, ty) in container.types.iter().enumerate() {
+        if ty.inputs > 0x7f || (ty.outputs > 0x7f && ty.outputs != NON_RETURNING) || ty.max_stack_height > MAX_STACK_HEIGHT {
+            issues.push(section_issue(i, EofIssueKind::InvalidType(*ty)));
+            continue;
+        }
+        match analyze_section(container, i) {
+            Ok(section) => {
+                if section.max_stack_height != ty.max_stack_height {
+                    issues.push(section_issue(i, EofIssueKind::MaxStackHeightMismatch {
+                        declared: ty.max_stack_height,
+                        computed: section.max_stack_height,
+                    }));
+                }
+                targets[i] = section.targets;
+            }
+            Err((pc, kind)) => issues.push(EofIssue { section: i, pc: Some(pc), path: None, kind }),
+        }
+    }
+
+    let mut reached = vec![false; container.code.len()];
+    let mut worklist = vec![0];
+    while let Some(i) = worklist.pop() {
+        if i < reached.len() && !reached[i] {
+            reached[i] = true;
+            worklist.extend(&targets[i]);
+        }
+    }
+    // Sections that failed validation have no known targets, so only report
+    // unreachable sections when every section was analyzed
+    if issues.iter().all(|issue| issue.pc.is_none()) {
+        for (i, reached) in reached.iter().enumerate() {
+            if !reached {
+                issues.push(section_issue(i, EofIssueKind::UnreachableSection));
+            }
+        }
+    }
+    // EOFCREATE and RETURNCONTRACT, which refer to containers, are not supported
+    for i in 0..container.containers.len() {
+        issues.push(section_issue(0, EofIssueKind::UnusedContainer(i)));
+    }
+    issues
+}
+
+/// Stack heights an instruction is reached with, over every path to it.
+#[derive(Debug, Clone, Copy)]
+struct Heights {
+    min: i32,
+    max: i32,
+    is_immediate: bool,
+    /// Target of a forward jump, so reachable after a terminating instruction.
+    is_jump_target: bool,
+}
+
+/// Validate code section `index` and compute its maximum stack height. The
+/// error carries the pc of the offending instruction.
+fn analyze_section(container: &EofContainer, index: usize) -> Result<SectionInfo, (usize, EofIssueKind)> {
+    let code = &container.code[index];
+    let types = &container.types;
+    let this = types[index];
+    let unset = Heights { min: i32::MAX, max: i32::MIN, is_immediate: false, is_jump_target: false };
+    let mut heights = vec![unset; code.len()];
+    let (mut next_min, mut next_max) = (this.inputs as i32, this.inputs as i32);
+    let mut after_terminator = false;
+    let mut returns = false;
+    let mut sections = Vec::new();
+
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = Opcode(code[pc]);
+        let info = eof_info(opcode).ok_or((pc, EofIssueKind::UnknownOpcode(opcode.0)))?;
+        if is_disabled_in_eof(opcode) {
+            return Err((pc, EofIssueKind::DisabledOpcode(info.name)));
+        }
+        if !after_terminator {
+            heights[pc].min = heights[pc].min.min(next_min);
+            heights[pc].max = heights[pc].max.max(next_max);
+        } else if !heights[pc].is_jump_target {
+            return Err((pc, EofIssueKind::Unreachable(info.name)));
+        }
+        let here = heights[pc];
+
+        let size = immediate_size(code, pc);
+        if size > 0 && pc + size >= code.len() {
+            return Err((pc, EofIssueKind::MissingImmediate(info.name)));
+        }
+        for (offset, immediate) in heights[pc + 1..pc + 1 + size].iter_mut().enumerate() {
+            if immediate.is_jump_target {
+                return Err((pc, EofIssueKind::JumpIntoImmediate { target: pc + 1 + offset }));
+            }
+            immediate.is_immediate = true;
+        }
+        let end = pc + 1 + size;
+
+        let (mut inputs, mut outputs) = (info.inputs as i32, info.outputs as i32);
+        let mut targets = Vec::new();
+        match opcode {
+            Opcode::RJUMP | Opcode::RJUMPI => targets.push(end as isize + read_i16(code, pc + 1) as isize),
+            Opcode::RJUMPV => {
+                for k in 0..(size - 1) / 2 {
+                    targets.push(end as isize + read_i16(code, pc + 2 + 2 * k) as isize);
+                }
+            }
+            Opcode::CALLF | Opcode::JUMPF => {
+                let target = read_u16(code, pc + 1) as usize;
+                let ty = *types.get(target).ok_or((pc, EofIssueKind::UnknownSection(target)))?;
+                sections.push(target);
+                inputs = ty.inputs as i32;
+                // The callee's inputs are counted in its own max stack height
+                let height = here.max - inputs + ty.max_stack_height as i32;
+                if height > STACK_LIMIT {
+                    return Err((pc, EofIssueKind::StackOverflow { height }));
+                }
+                if opcode == Opcode::CALLF {
+                    if !ty.is_returning() {
+                        return Err((pc, EofIssueKind::CallToNonReturning(target)));
+                    }
+                    outputs = ty.outputs as i32;
+                } else if ty.is_returning() {
+                    returns = true;
+                    if !this.is_returning() || this.outputs < ty.outputs {
+                        return Err((pc, EofIssueKind::JumpfOutputs { target }));
+                    }
+                    inputs = this.outputs as i32 + ty.inputs as i32 - ty.outputs as i32;
+                    if here.max > inputs {
+                        return Err((pc, EofIssueKind::ReturnHeight { opcode: info.name, height: here.max, expected: inputs }));
+                    }
+                }
+            }
+            Opcode::RETF => {
+                returns = true;
+                inputs = this.outputs as i32;
+                if here.max > inputs {
+                    return Err((pc, EofIssueKind::ReturnHeight { opcode: info.name, height: here.max, expected: inputs }));
+                }
+            }
+            Opcode::DATALOADN => {
+                let offset = read_u16(code, pc + 1) as usize;
+                let data_size = container.data.len();
+                if offset + 32 > data_size {
+                    return Err((pc, EofIssueKind::DataLoadOutOfBounds { offset, data_size }));
+                }
+            }
+            _ => {}
+        }
+        if inputs > here.min {
+            return Err((pc, EofIssueKind::StackUnderflow { opcode: info.name, required: inputs, available: here.min }));
+        }
+        next_min = here.min - inputs + outputs;
+        next_max = here.max - inputs + outputs;
+
+        for target in targets {
+            if target < 0 || target >= code.len() as isize {
+                return Err((pc, EofIssueKind::JumpOutOfBounds { target }));
+            }
+            let (target, backward) = (target as usize, target as usize <= pc);
+            let heights = &mut heights[target];
+            if heights.is_immediate {
+                return Err((pc, EofIssueKind::JumpIntoImmediate { target }));
+            }
+            heights.is_jump_target = true;
+            if backward {
+                // Backward targets were reached already and must agree exactly
+                if (heights.min, heights.max) != (next_min, next_max) {
+                    return Err((pc, EofIssueKind::BackwardJumpHeightMismatch { target }));
+                }
+            } else {
+                heights.min = heights.min.min(next_min);
+                heights.max = heights.max.max(next_max);
+            }
+        }
+
+        after_terminator = is_terminating_in_eof(opcode);
+        pc = end;
+    }
+
+    if !after_terminator {
+        return Err((code.len(), EofIssueKind::MissingTerminator));
+    }
+    if returns != this.is_returning() {
+        return Err((code.len(), EofIssueKind::ReturningMismatch { returns }));
+    }
+    let max = heights.iter().map(|h| h.max).max().unwrap_or(0).max(0);
+    if max > MAX_STACK_HEIGHT as i32 {
+        return Err((code.len(), EofIssueKind::StackOverflow { height: max }));
+    }
+    Ok(SectionInfo { max_stack_height: max as u16, targets: sections })
+}
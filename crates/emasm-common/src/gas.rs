@@ -0,0 +1,503 @@
+use crate::{
+    cfg::{Cfg, InstrKind, PushOperand, Terminator},
+    jumptable::JUMP_TABLE_ENTRY_SIZE,
+    opcodes::Opcode,
+    types::*,
+};
+use alloy_primitives::U256;
+use std::collections::{HashMap, HashSet};
+
+/// Upper limit on enumerated paths, to keep branchy programs tractable.
+pub const MAX_PATHS: usize = 1024;
+
+const COLD_SLOAD: u64 = 2100;
+const COLD_ACCOUNT_ACCESS: u64 = 2600;
+const WARM_ACCESS: u64 = 100;
+
+/// Range of gas a piece of code may use; `max: None` means unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasRange {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl GasRange {
+    pub const ZERO: GasRange = GasRange { min: 0, max: Some(0) };
+
+    pub fn exact(gas: u64) -> Self {
+        Self { min: gas, max: Some(gas) }
+    }
+
+    pub fn between(min: u64, max: u64) -> Self {
+        Self { min, max: Some(max) }
+    }
+
+    pub fn at_least(min: u64) -> Self {
+        Self { min, max: None }
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.max.is_some()
+    }
+}
+
+impl std::ops::Add for GasRange {
+    type Output = GasRange;
+
+    fn add(self, other: GasRange) -> GasRange {
+        GasRange {
+            min: self.min + other.min,
+            max: self.max.zip(other.max).map(|(a, b)| a + b),
+        }
+    }
+}
+
+impl std::ops::AddAssign for GasRange {
+    fn add_assign(&mut self, other: GasRange) {
+        *self = *self + other;
+    }
+}
+
+impl std::fmt::Display for GasRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{}..{}", self.min, max),
+            None => write!(f, "{}..unbounded", self.min),
+        }
+    }
+}
+
+/// Gas for one pass through a segment's own elements (nested segments excluded).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentGas {
+    pub label: String,
+    pub gas: GasRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathEnd {
+    /// A halting opcode such as RETURN or REVERT.
+    Halt(&'static str),
+    /// Execution ran off the end of the code.
+    End,
+    /// Execution ran into a bytes segment.
+    Data,
+    /// The path continues through a jump whose target is not known statically.
+    DynamicJump,
+    /// The path jumps back to a block it already went through.
+    Loop(String),
+}
+
+/// Gas along one acyclic path through the control-flow graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathGas {
+    /// Blocks on the path, named by segment label (or element path if unlabelled).
+    pub blocks: Vec<String>,
+    pub gas: GasRange,
+    pub end: PathEnd,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasReport {
+    pub segments: Vec<SegmentGas>,
+    pub paths: Vec<PathGas>,
+    /// Labels that start a loop; paths through them have no upper bound.
+    pub loops: Vec<String>,
+    /// Set when path enumeration stopped at `MAX_PATHS`.
+    pub truncated: bool,
+}
+
+impl GasReport {
+    /// Cheapest and most expensive of all enumerated paths.
+    pub fn total(&self) -> GasRange {
+        let min = self.paths.iter().map(|p| p.gas.min).min().unwrap_or(0);
+        let max = self.paths.iter().try_fold(0, |acc: u64, p| p.gas.max.map(|m| acc.max(m)));
+        GasRange { min, max: if self.truncated { None } else { max } }
+    }
+}
+
+impl std::fmt::Display for GasReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "segments:")?;
+        for seg in &self.segments {
+            writeln!(f, "  {:<24} {}", seg.label, seg.gas)?;
+        }
+        writeln!(f, "paths:")?;
+        for path in &self.paths {
+            let end = match &path.end {
+                PathEnd::Halt(op) => op.to_string(),
+                PathEnd::End => "end of code".to_string(),
+                PathEnd::Data => "data".to_string(),
+                PathEnd::DynamicJump => "dynamic jump".to_string(),
+                PathEnd::Loop(label) => format!("loop at {}", label),
+            };
+            writeln!(f, "  {} -> {}: {}", path.blocks.join(" -> "), end, path.gas)?;
+        }
+        if self.truncated {
+            writeln!(f, "  (stopped after {} paths)", MAX_PATHS)?;
+        }
+        if !self.loops.is_empty() {
+            writeln!(f, "unbounded loops: {}", self.loops.join(", "))?;
+        }
+        write!(f, "total: {}", self.total())
+    }
+}
+
+/// Static gas of an opcode, not counting memory, access or copy costs.
+pub fn base_cost(op: Opcode) -> u64 {
+    match op.0 {
+        0x00 | 0xf3 | 0xfd | 0xfe => 0,
+        0x5b => 1,
+        0x30 | 0x32..=0x34 | 0x36 | 0x38 | 0x3a | 0x3d | 0x41..=0x46 | 0x48 | 0x50 | 0x58..=0x5a
+        | 0x5f => 2,
+        0x01 | 0x03 | 0x10..=0x1d | 0x35 | 0x37 | 0x39 | 0x3e | 0x51..=0x53 | 0x60..=0x9f => 3,
+        0x02 | 0x04..=0x07 | 0x0b | 0x47 => 5,
+        0x08 | 0x09 | 0x56 => 8,
+        0x0a | 0x57 => 10,
+        0x40 => 20,
+        0x20 => 30,
+        0xa0..=0xa4 => 375 * (1 + (op.0 - 0xa0) as u64),
+        0xf0 | 0xf5 => 32000,
+        0xff => 5000,
+        _ => 0,
+    }
+}
+
+/// Estimate gas per segment and per acyclic path through the program.
+///
+/// Besides static opcode costs, the model covers memory expansion when
+/// offsets and sizes are pushed as constants, EIP-2929 warm/cold pricing for
+/// storage and account access (a constant key is cold on first use and warm
+/// afterwards; an unknown key may be either), per-word copy and hashing
+/// costs, and the value-transfer surcharge of CALL. Gas forwarded to callees
+/// and burned by INVALID is not included.
+pub fn estimate_gas(elements: &[AsmElement], opcodes: &HashMap<&'static str, Opcode>) -> GasReport {
+    let cfg = Cfg::build(elements, opcodes);
+    let bytes_sizes = collect_bytes_sizes(elements);
+
+    let mut segments = vec![SegmentGas {
+        label: "<root>".to_string(),
+        gas: GasRange::ZERO,
+    }];
+    let mut segment_index: HashMap<Vec<String>, usize> = HashMap::new();
+    segment_index.insert(Vec::new(), 0);
+    let mut segment_state: Vec<GasState> = vec![GasState::default()];
+    for instr in &cfg.instrs {
+        let key = &instr.path.segments;
+        let idx = *segment_index.entry(key.clone()).or_insert_with(|| {
+            segments.push(SegmentGas {
+                label: key.join("/"),
+                gas: GasRange::ZERO,
+            });
+            segment_state.push(GasState::default());
+            segments.len() - 1
+        });
+        segments[idx].gas += segment_state[idx].step(&instr.kind, &bytes_sizes);
+    }
+
+    let mut walker = PathWalker {
+        cfg: &cfg,
+        bytes_sizes: &bytes_sizes,
+        paths: Vec::new(),
+        loops: Vec::new(),
+        truncated: false,
+    };
+    if !cfg.blocks.is_empty() {
+        walker.walk(0, GasState::at_entry(), GasRange::ZERO, &mut Vec::new());
+    }
+
+    GasReport {
+        segments,
+        paths: walker.paths,
+        loops: walker.loops,
+        truncated: walker.truncated,
+    }
+}
+
+fn collect_bytes_sizes(elements: &[AsmElement]) -> HashMap<String, usize> {
+    let mut sizes = HashMap::new();
+    for elem in elements {
+        match elem {
+            AsmElement::BytesSegment(label, data) => {
+                sizes.insert(label.clone(), data.len());
+            }
+            AsmElement::JumpTable(label, targets) => {
+                sizes.insert(label.clone(), JUMP_TABLE_ENTRY_SIZE * targets.len());
+            }
+            AsmElement::Segment(_, inner) => sizes.extend(collect_bytes_sizes(inner)),
+            _ => {}
+        }
+    }
+    sizes
+}
+
+struct PathWalker<'a> {
+    cfg: &'a Cfg,
+    bytes_sizes: &'a HashMap<String, usize>,
+    paths: Vec<PathGas>,
+    loops: Vec<String>,
+    truncated: bool,
+}
+
+impl PathWalker<'_> {
+    fn walk(&mut self, block: usize, mut state: GasState, mut gas: GasRange, path: &mut Vec<usize>) {
+        if self.paths.len() >= MAX_PATHS {
+            self.truncated = true;
+            return;
+        }
+        path.push(block);
+        for instr in self.cfg.block_instrs(block) {
+            gas += state.step(&instr.kind, self.bytes_sizes);
+        }
+
+        let b = &self.cfg.blocks[block];
+        let end = match &b.terminator {
+            Terminator::Halt(op) => Some(PathEnd::Halt(op.info().map(|i| i.name).unwrap_or("halt"))),
+            Terminator::End => Some(PathEnd::End),
+            Terminator::Data => Some(PathEnd::Data),
+            // Jump table dispatches have successors; other dynamic jumps do not
+            _ if b.successors.is_empty() => Some(PathEnd::DynamicJump),
+            _ => None,
+        };
+
+        match end {
+            Some(end) => self.finish(path, gas, end),
+            None => {
+                for &succ in &b.successors {
+                    if path.contains(&succ) {
+                        let label = self.block_name(succ);
+                        if !self.loops.contains(&label) {
+                            self.loops.push(label.clone());
+                        }
+                        let unbounded = GasRange::at_least(gas.min);
+                        self.finish(path, unbounded, PathEnd::Loop(label));
+                    } else {
+                        self.walk(succ, state.clone(), gas, path);
+                    }
+                }
+            }
+        }
+        path.pop();
+    }
+
+    fn finish(&mut self, path: &[usize], gas: GasRange, end: PathEnd) {
+        if self.paths.len() >= MAX_PATHS {
+            self.truncated = true;
+            return;
+        }
+        let blocks = path.iter().map(|&b| self.block_name(b)).collect();
+        self.paths.push(PathGas { blocks, gas, end });
+    }
+
+    fn block_name(&self, block: usize) -> String {
+        let b = &self.cfg.blocks[block];
+        b.label.clone().unwrap_or_else(|| self.cfg.instrs[b.start].path.to_string())
+    }
+}
+
+/// What is known about the machine at a point on a path.
+#[derive(Debug, Clone, Default)]
+struct GasState {
+    /// Abstract stack, bottom first; `Some` for values known to be constant.
+    stack: Vec<Option<U256>>,
+    /// Current memory size in words, if known.
+    mem_words: Option<u64>,
+    warm_slots: HashSet<U256>,
+    warm_accounts: HashSet<U256>,
+}
+
+impl GasState {
+    /// State at the start of execution: empty stack and memory.
+    fn at_entry() -> Self {
+        Self {
+            mem_words: Some(0),
+            ..Self::default()
+        }
+    }
+
+    fn arg(&self, i: usize) -> Option<U256> {
+        self.stack.iter().rev().nth(i).copied().flatten()
+    }
+
+    fn step(&mut self, kind: &InstrKind, bytes_sizes: &HashMap<String, usize>) -> GasRange {
+        match kind {
+            InstrKind::Push(operand) => {
+                let value = match operand {
+                    PushOperand::Literal(data) => U256::try_from_be_slice(data),
+                    PushOperand::BytesSize(label) => bytes_sizes.get(label).map(|&s| U256::from(s)),
+                    _ => None,
+                };
+                self.stack.push(value);
+                GasRange::exact(3)
+            }
+            InstrKind::DupSlot(_) => {
+                self.stack.push(None);
+                GasRange::exact(3)
+            }
+            InstrKind::SwapSlot(_) => GasRange::exact(3),
+            InstrKind::Jumpdest(_) => GasRange::exact(1),
+            InstrKind::Op(op) => {
+                let cost = self.op_cost(*op);
+                self.apply(*op);
+                cost
+            }
+            InstrKind::Data(_) | InstrKind::Unknown(_) | InstrKind::Anchor(_) | InstrKind::Let(_) => {
+                GasRange::ZERO
+            }
+        }
+    }
+
+    fn op_cost(&mut self, op: Opcode) -> GasRange {
+        let base = GasRange::exact(base_cost(op));
+        let args: Vec<Option<U256>> = (0..7).map(|i| self.arg(i)).collect();
+        let a = |i: usize| args[i];
+        match op {
+            Opcode::SLOAD => self.access_slot(a(0), WARM_ACCESS, COLD_SLOAD),
+            Opcode::SSTORE => {
+                // Cold surcharge on top of the EIP-2200 cost of the write
+                self.access_slot(a(0), 0, COLD_SLOAD) + GasRange::between(WARM_ACCESS, 20000)
+            }
+            Opcode::BALANCE | Opcode::EXTCODESIZE | Opcode::EXTCODEHASH => {
+                self.access_account(a(0), WARM_ACCESS, COLD_ACCOUNT_ACCESS)
+            }
+            Opcode::EXTCODECOPY => {
+                let (mem, size) = (a(1), a(3));
+                self.access_account(a(0), WARM_ACCESS, COLD_ACCOUNT_ACCESS)
+                    + per_word(3, size)
+                    + self.expand(mem, size)
+            }
+            Opcode::CALLDATACOPY | Opcode::CODECOPY | Opcode::RETURNDATACOPY => {
+                let (mem, size) = (a(0), a(2));
+                base + per_word(3, size) + self.expand(mem, size)
+            }
+            Opcode::MLOAD | Opcode::MSTORE => base + self.expand(a(0), Some(U256::from(32))),
+            Opcode::MSTORE8 => base + self.expand(a(0), Some(U256::from(1))),
+            Opcode::SHA3 => {
+                let (mem, size) = (a(0), a(1));
+                base + per_word(6, size) + self.expand(mem, size)
+            }
+            Opcode::LOG0 | Opcode::LOG1 | Opcode::LOG2 | Opcode::LOG3 | Opcode::LOG4 => {
+                let (mem, size) = (a(0), a(1));
+                let data = match size.and_then(to_u64) {
+                    Some(size) => GasRange::exact(8 * size),
+                    None => GasRange::at_least(0),
+                };
+                base + data + self.expand(mem, size)
+            }
+            Opcode::RETURN | Opcode::REVERT => self.expand(a(0), a(1)),
+            Opcode::EXP => match a(1) {
+                Some(exp) => GasRange::exact(10 + 50 * exp.byte_len() as u64),
+                None => GasRange::between(10, 10 + 50 * 32),
+            },
+            Opcode::CALL | Opcode::CALLCODE => {
+                let (addr, value) = (a(1), a(2));
+                let (args, args_size, ret, ret_size) = (a(3), a(4), a(5), a(6));
+                let transfer = match value {
+                    Some(v) if v.is_zero() => GasRange::ZERO,
+                    Some(_) if op == Opcode::CALLCODE => GasRange::exact(9000),
+                    Some(_) => GasRange::between(9000, 9000 + 25000),
+                    None => GasRange::between(0, 9000 + 25000),
+                };
+                self.access_account(addr, WARM_ACCESS, COLD_ACCOUNT_ACCESS)
+                    + transfer
+                    + self.expand(args, args_size)
+                    + self.expand(ret, ret_size)
+            }
+            Opcode::DELEGATECALL | Opcode::STATICCALL => {
+                let (addr, args, args_size, ret, ret_size) = (a(1), a(2), a(3), a(4), a(5));
+                self.access_account(addr, WARM_ACCESS, COLD_ACCOUNT_ACCESS)
+                    + self.expand(args, args_size)
+                    + self.expand(ret, ret_size)
+            }
+            Opcode::CREATE | Opcode::CREATE2 => {
+                let (mem, size) = (a(1), a(2));
+                // EIP-3860 initcode cost, plus hashing for CREATE2
+                let per_init_word = if op == Opcode::CREATE2 { 2 + 6 } else { 2 };
+                base + per_word(per_init_word, size) + self.expand(mem, size)
+            }
+            Opcode::SELFDESTRUCT => {
+                base + self.access_account(a(0), 0, COLD_ACCOUNT_ACCESS) + GasRange::between(0, 25000)
+            }
+            _ => base,
+        }
+    }
+
+    fn apply(&mut self, op: Opcode) {
+        let Some(info) = op.info() else { return };
+        let len = self.stack.len();
+        match op.0 {
+            0x80..=0x8f => {
+                let value = self.arg(info.inputs - 1);
+                self.stack.push(value);
+            }
+            0x90..=0x9f => {
+                let n = info.inputs - 1;
+                if n < len {
+                    self.stack.swap(len - 1, len - 1 - n);
+                }
+            }
+            _ => {
+                self.stack.truncate(len.saturating_sub(info.inputs));
+                self.stack.extend(std::iter::repeat_n(None, info.outputs));
+            }
+        }
+    }
+
+    fn access_slot(&mut self, key: Option<U256>, warm: u64, cold: u64) -> GasRange {
+        access(&mut self.warm_slots, key, warm, cold)
+    }
+
+    fn access_account(&mut self, address: Option<U256>, warm: u64, cold: u64) -> GasRange {
+        access(&mut self.warm_accounts, address, warm, cold)
+    }
+
+    /// Memory expansion cost of touching `size` bytes at `offset`.
+    fn expand(&mut self, offset: Option<U256>, size: Option<U256>) -> GasRange {
+        if size.is_some_and(|s| s.is_zero()) {
+            return GasRange::ZERO;
+        }
+        let end = offset.and_then(to_u64).zip(size.and_then(to_u64))
+            .and_then(|(o, s)| o.checked_add(s));
+        let Some(end) = end else {
+            self.mem_words = None;
+            return GasRange::at_least(0);
+        };
+        let words = end.div_ceil(32);
+        match self.mem_words {
+            Some(current) if words <= current => GasRange::ZERO,
+            Some(current) => {
+                self.mem_words = Some(words);
+                GasRange::exact(memory_cost(words) - memory_cost(current))
+            }
+            None => GasRange::between(0, memory_cost(words)),
+        }
+    }
+}
+
+fn access(warm_set: &mut HashSet<U256>, key: Option<U256>, warm: u64, cold: u64) -> GasRange {
+    match key {
+        Some(key) if warm_set.contains(&key) => GasRange::exact(warm),
+        Some(key) => {
+            warm_set.insert(key);
+            GasRange::exact(cold)
+        }
+        None => GasRange::between(warm, cold),
+    }
+}
+
+fn per_word(cost: u64, size: Option<U256>) -> GasRange {
+    match size.and_then(to_u64) {
+        Some(size) => GasRange::exact(cost * size.div_ceil(32)),
+        None => GasRange::at_least(0),
+    }
+}
+
+fn memory_cost(words: u64) -> u64 {
+    3 * words + words * words / 512
+}
+
+fn to_u64(value: U256) -> Option<u64> {
+    // Anything near this large would run out of gas on memory alone
+    u64::try_from(value).ok().filter(|v| *v <= u32::MAX as u64)
+}
@@ -0,0 +1,58 @@
+use crate::types::*;
+use std::collections::HashMap;
+
+/// Width of a jump table entry: enough for any offset below the 24KiB code size limit.
+pub const JUMP_TABLE_ENTRY_SIZE: usize = 2;
+
+/// Jump to entry `i` of the jump table `name`, with `i` on top of the stack.
+///
+/// This is what `"jumptable:name"` expands to:
+///
+/// ```text
+/// dup1 add            ; 2i
+/// <name> add          ; address of entry i
+/// 0x02 swap1 0x00 codecopy
+/// 0x00 mload 0xf0 shr ; entry i
+/// jump
+/// ```
+///
+/// The entry is copied into memory at 0x00..0x02, so the scratch word at
+/// 0x00 is overwritten. The index is not bounds-checked: an index past the
+/// end of the table reads whatever code follows it.
+pub fn table_jump(name: &str) -> Vec<AsmElement> {
+    let op = |name: &str| AsmElement::Opcode(name.to_string());
+    vec![
+        op("dup1"),
+        op("add"),
+        AsmElement::BytesPtr(name.to_string()),
+        op("add"),
+        AsmElement::Literal(vec![JUMP_TABLE_ENTRY_SIZE as u8]),
+        op("swap1"),
+        AsmElement::Literal(Vec::new()),
+        op("codecopy"),
+        AsmElement::Literal(Vec::new()),
+        op("mload"),
+        AsmElement::Literal(vec![(256 - 8 * JUMP_TABLE_ENTRY_SIZE) as u8]),
+        op("shr"),
+        op("jump"),
+    ]
+}
+
+/// Labels listed in every jump table of the program, by table name.
+pub fn jump_tables(elements: &[AsmElement]) -> HashMap<String, Vec<String>> {
+    let mut tables = HashMap::new();
+    collect_tables(elements, &mut tables);
+    tables
+}
+
+fn collect_tables(elements: &[AsmElement], tables: &mut HashMap<String, Vec<String>>) {
+    for elem in elements {
+        match elem {
+            AsmElement::JumpTable(name, targets) => {
+                tables.insert(name.clone(), targets.clone());
+            }
+            AsmElement::Segment(_, inner) => collect_tables(inner, tables),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    cfg::{InstrKind, PushOperand},
+    types::*,
+};
+use std::collections::HashMap;
+
+/// Where an element was placed in the assembled bytecode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutEntry {
+    pub path: ElementPath,
+    pub offset: usize,
+    /// Number of bytes emitted, including PUSH immediates.
+    pub size: usize,
+    pub kind: InstrKind,
+}
+
+/// Final placement of labels, bytes segments and elements after assembly.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub labels: HashMap<String, usize>,
+    pub bytes: HashMap<String, BytesInfo>,
+    /// Emitted elements in bytecode order.
+    pub entries: Vec<LayoutEntry>,
+}
+
+impl Layout {
+    /// Entry whose bytes cover the program counter `pc`.
+    pub fn entry_at(&self, pc: usize) -> Option<&LayoutEntry> {
+        let i = self.entries.partition_point(|e| e.offset + e.size <= pc);
+        self.entries.get(i).filter(|e| e.offset <= pc)
+    }
+
+    /// Every immutable PUSH in bytecode order, with the offset of its value bytes.
+    pub fn immutable_slots(&self) -> Vec<ImmutableSlot> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match &entry.kind {
+                InstrKind::Push(PushOperand::Immutable(name, width)) => Some(ImmutableSlot {
+                    name: name.clone(),
+                    offset: entry.offset + 1,
+                    width: *width,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Zero-filled bytes of an immutable PUSH that deployment overwrites.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImmutableSlot {
+    pub name: String,
+    /// Offset of the first value byte, just after the PUSH opcode.
+    pub offset: usize,
+    pub width: usize,
+}
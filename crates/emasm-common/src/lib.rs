@@ -2,6 +2,28 @@ pub mod opcodes;
 pub mod types;
 pub mod assembler;
 pub mod encodable;
+pub mod cfg;
+pub mod stack;
+pub mod slots;
+pub mod gas;
+pub mod source;
+pub mod layout;
+pub mod verify;
+pub mod dead_code;
+pub mod peephole;
+pub mod constants;
+pub mod reorder;
+pub mod merge;
+pub mod jumptable;
+pub mod abi;
+pub mod dispatch;
+pub mod contract;
+pub mod create2;
+pub mod data;
+pub mod offset;
+pub mod disasm;
+pub mod eof;
+pub mod eof_verify;
 
 pub use types::*;
 pub use encodable::EVMEncodable;
@@ -0,0 +1,392 @@
+use crate::{
+    jumptable::JUMP_TABLE_ENTRY_SIZE,
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Most candidate rewrites evaluated per round.
+const MAX_CANDIDATES: usize = 32;
+
+/// Shortest tail, in estimated bytes, worth replacing with a jump.
+const MIN_TAIL_SIZE: usize = 5;
+
+/// What tail merging changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Duplicate segments that were removed, with the segment now used instead.
+    pub merged_segments: Vec<(String, String)>,
+    /// Identical tails replaced by a jump to a single copy.
+    pub merged_tails: usize,
+    /// Difference in assembled size.
+    pub bytes_saved: usize,
+}
+
+impl std::fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (removed, kept) in &self.merged_segments {
+            writeln!(f, "merged segment {} into {}", removed, kept)?;
+        }
+        writeln!(f, "merged tails: {}", self.merged_tails)?;
+        write!(f, "bytes saved: {}", self.bytes_saved)
+    }
+}
+
+/// Position of a straight-line run: the nested segment indices leading to
+/// its element list, and where the run starts and ends in that list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Occurrence {
+    list: Vec<usize>,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Candidate {
+    /// Segments with identical bodies; the first one is kept.
+    Segments(Vec<Vec<usize>>),
+    /// Identical runs ending in a terminator; one is kept and labelled.
+    Tail(Vec<Occurrence>),
+}
+
+/// Keep one copy of identical segments and of identical instruction tails,
+/// redirecting the others with jumps.
+///
+/// Only code that cannot depend on where it sits is merged: segment bodies
+/// and tails must end with STOP, RETURN, REVERT, INVALID, SELFDESTRUCT or
+/// JUMP and must not use PC. References to a removed segment are redirected
+/// to its twin. A kept tail that is not already a whole segment is wrapped
+/// in a new segment named `tail_N`.
+///
+/// Each rewrite is applied only if `size_of` reports the result as strictly
+/// smaller; it returns `None` for programs that must be rejected, e.g. because
+/// they no longer pass the stack checker. `bytes_saved` is left at zero; the
+/// assembler fills it in.
+pub fn merge_tails(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+    size_of: impl Fn(&[AsmElement]) -> Option<usize>,
+) -> (Vec<AsmElement>, MergeReport) {
+    let mut current = elements.to_vec();
+    let mut report = MergeReport::default();
+    let Some(mut current_size) = size_of(&current) else {
+        return (current, report);
+    };
+
+    loop {
+        let mut improved = false;
+        for candidate in candidates(&current, opcodes).into_iter().take(MAX_CANDIDATES) {
+            let mut names = HashSet::new();
+            collect_names(&current, &mut names);
+            let (rewritten, merged) = apply(&current, &candidate, opcodes, &names);
+            match size_of(&rewritten) {
+                Some(size) if size < current_size => {
+                    current = rewritten;
+                    current_size = size;
+                    match merged {
+                        Some(pairs) => report.merged_segments.extend(pairs),
+                        None => report.merged_tails += 1,
+                    }
+                    improved = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if !improved {
+            return (current, report);
+        }
+    }
+}
+
+/// Rewrites worth trying, most promising first.
+fn candidates(elements: &[AsmElement], opcodes: &HashMap<&'static str, Opcode>) -> Vec<Candidate> {
+    let mut scored: Vec<(usize, Candidate)> = Vec::new();
+
+    // Groups keep first-seen order so that ties are broken deterministically
+    let mut bodies: Vec<(Vec<AsmElement>, Vec<Vec<usize>>)> = Vec::new();
+    let mut body_index: HashMap<Vec<AsmElement>, usize> = HashMap::new();
+    collect_segments(elements, &mut Vec::new(), opcodes, &mut |path, inner| {
+        match body_index.get(inner) {
+            Some(&i) => bodies[i].1.push(path),
+            None => {
+                body_index.insert(inner.to_vec(), bodies.len());
+                bodies.push((inner.to_vec(), vec![path]));
+            }
+        }
+    });
+    for (body, paths) in bodies {
+        if paths.len() > 1 {
+            let saved = (paths.len() - 1) * (1 + estimate_size(&body));
+            scored.push((saved, Candidate::Segments(paths)));
+        }
+    }
+
+    let mut runs = Vec::new();
+    collect_runs(elements, &mut Vec::new(), opcodes, &mut runs);
+    let mut tails: Vec<(&[AsmElement], Vec<Occurrence>)> = Vec::new();
+    let mut tail_index: HashMap<&[AsmElement], usize> = HashMap::new();
+    for (run, elements) in &runs {
+        for start in run.start..=run.end {
+            let tail = &elements[start - run.start..];
+            let occurrence = Occurrence { list: run.list.clone(), start, end: run.end };
+            match tail_index.get(tail) {
+                Some(&i) => tails[i].1.push(occurrence),
+                None => {
+                    tail_index.insert(tail, tails.len());
+                    tails.push((tail, vec![occurrence]));
+                }
+            }
+        }
+    }
+    for (tail, occurrences) in tails {
+        let size = estimate_size(tail);
+        if occurrences.len() > 1 && size >= MIN_TAIL_SIZE {
+            let saved = (occurrences.len() - 1) * (size - 4);
+            scored.push((saved, Candidate::Tail(occurrences)));
+        }
+    }
+
+    // Stable, so equal savings keep discovery order
+    scored.sort_by_key(|(saved, _)| std::cmp::Reverse(*saved));
+    scored.into_iter().map(|(_, c)| c).collect()
+}
+
+/// Apply a candidate; for segment merges also return the (removed, kept) pairs.
+fn apply(
+    elements: &[AsmElement],
+    candidate: &Candidate,
+    opcodes: &HashMap<&'static str, Opcode>,
+    names: &HashSet<String>,
+) -> (Vec<AsmElement>, Option<Vec<(String, String)>>) {
+    let mut result = elements.to_vec();
+    match candidate {
+        Candidate::Segments(paths) => {
+            let kept = segment_label(&result, &paths[0]);
+            let mut renames = HashMap::new();
+            // Later positions first, so earlier paths stay valid
+            let mut dups = paths[1..].to_vec();
+            dups.sort();
+            for path in dups.iter().rev() {
+                let (index, parent) = path.split_last().expect("segment paths are never empty");
+                let list = list_mut(&mut result, parent);
+                // The first element of a list is entered from the program start or its parent's JUMPDEST
+                let falls_into = *index == 0 || !ends_with_terminator(&list[..*index], opcodes);
+                let AsmElement::Segment(label, _) = &list[*index] else {
+                    unreachable!("paths point at segments")
+                };
+                renames.insert(label.clone(), kept.clone());
+                if falls_into {
+                    list.splice(*index..=*index, [
+                        AsmElement::Label(kept.clone()),
+                        AsmElement::Opcode("jump".to_string()),
+                    ]);
+                } else {
+                    list.remove(*index);
+                }
+            }
+            rename_labels(&mut result, &renames);
+            let mut pairs: Vec<_> = renames.into_iter().collect();
+            pairs.sort();
+            (result, Some(pairs))
+        }
+        Candidate::Tail(occurrences) => {
+            // A tail that is a whole segment body can be jumped to as it is
+            let whole = occurrences.iter().position(|o| o.start == 0 && !o.list.is_empty() && {
+                let (index, parent) = o.list.split_last().unwrap();
+                matches!(&list_ref(elements, parent)[*index], AsmElement::Segment(_, inner) if inner.len() == o.end + 1)
+            });
+            let kept_index = whole.unwrap_or(0);
+            let kept = &occurrences[kept_index];
+            let label = match whole {
+                Some(_) => {
+                    let (index, parent) = kept.list.split_last().unwrap();
+                    segment_label(elements, &[parent, &[*index]].concat())
+                }
+                None => fresh_label(names),
+            };
+
+            let mut edits: Vec<&Occurrence> = occurrences.iter().collect();
+            edits.sort_by(|a, b| (&b.list, b.start).cmp(&(&a.list, a.start)));
+            for occurrence in edits {
+                let list = list_mut(&mut result, &occurrence.list);
+                let range = occurrence.start..=occurrence.end;
+                if occurrence == kept {
+                    if whole.is_none() {
+                        let tail: Vec<_> = list[range.clone()].to_vec();
+                        list.splice(range, [AsmElement::Segment(label.clone(), tail)]);
+                    }
+                } else {
+                    list.splice(range, [
+                        AsmElement::Label(label.clone()),
+                        AsmElement::Opcode("jump".to_string()),
+                    ]);
+                }
+            }
+            (result, None)
+        }
+    }
+}
+
+/// Visit segments whose bodies can be shared: no nested segments, no PC, ending in a terminator.
+fn collect_segments(
+    elements: &[AsmElement],
+    path: &mut Vec<usize>,
+    opcodes: &HashMap<&'static str, Opcode>,
+    visit: &mut impl FnMut(Vec<usize>, &[AsmElement]),
+) {
+    for (i, elem) in elements.iter().enumerate() {
+        if let AsmElement::Segment(_, inner) = elem {
+            path.push(i);
+            let flat = !inner.iter().any(|e| matches!(e, AsmElement::Segment(..)));
+            if flat && ends_with_terminator(inner, opcodes) && !uses_pc(inner, opcodes) {
+                visit(path.clone(), inner);
+            }
+            collect_segments(inner, path, opcodes, visit);
+            path.pop();
+        }
+    }
+}
+
+/// Straight-line runs ending in a terminator, with their elements.
+fn collect_runs<'a>(
+    elements: &'a [AsmElement],
+    path: &mut Vec<usize>,
+    opcodes: &HashMap<&'static str, Opcode>,
+    runs: &mut Vec<(Occurrence, &'a [AsmElement])>,
+) {
+    let mut start = 0;
+    for (i, elem) in elements.iter().enumerate() {
+        let op = match elem {
+            AsmElement::Opcode(name) => opcodes.get(name.as_str()).copied(),
+            _ => None,
+        };
+        match elem {
+            AsmElement::Segment(_, inner) => {
+                path.push(i);
+                collect_runs(inner, path, opcodes, runs);
+                path.pop();
+                start = i + 1;
+            }
+            AsmElement::BytesSegment(..)
+            | AsmElement::JumpTable(..)
+            | AsmElement::Align(_)
+            | AsmElement::Anchor(_)
+            | AsmElement::Let(_)
+            | AsmElement::DupSlot(_)
+            | AsmElement::SwapSlot(_) => start = i + 1,
+            _ if op == Some(Opcode::JUMPDEST) || op == Some(Opcode::PC) => start = i + 1,
+            _ if op.is_some_and(|op| op.is_terminator()) => {
+                runs.push((
+                    Occurrence { list: path.clone(), start, end: i },
+                    &elements[start..=i],
+                ));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn ends_with_terminator(elements: &[AsmElement], opcodes: &HashMap<&'static str, Opcode>) -> bool {
+    match elements.last() {
+        Some(AsmElement::Opcode(name)) => opcodes.get(name.as_str()).is_some_and(|op| op.is_terminator()),
+        Some(AsmElement::Segment(_, inner)) => ends_with_terminator(inner, opcodes),
+        _ => false,
+    }
+}
+
+fn uses_pc(elements: &[AsmElement], opcodes: &HashMap<&'static str, Opcode>) -> bool {
+    elements.iter().any(|e| {
+        matches!(e, AsmElement::Opcode(name) if opcodes.get(name.as_str()) == Some(&Opcode::PC))
+    })
+}
+
+fn list_ref<'a>(elements: &'a [AsmElement], path: &[usize]) -> &'a [AsmElement] {
+    match path.split_first() {
+        None => elements,
+        Some((i, rest)) => match &elements[*i] {
+            AsmElement::Segment(_, inner) => list_ref(inner, rest),
+            _ => unreachable!("paths point at segments"),
+        },
+    }
+}
+
+fn list_mut<'a>(elements: &'a mut Vec<AsmElement>, path: &[usize]) -> &'a mut Vec<AsmElement> {
+    match path.split_first() {
+        None => elements,
+        Some((i, rest)) => match &mut elements[*i] {
+            AsmElement::Segment(_, inner) => list_mut(inner, rest),
+            _ => unreachable!("paths point at segments"),
+        },
+    }
+}
+
+fn segment_label(elements: &[AsmElement], path: &[usize]) -> String {
+    let (index, parent) = path.split_last().expect("segment paths are never empty");
+    match &list_ref(elements, parent)[*index] {
+        AsmElement::Segment(label, _) => label.clone(),
+        _ => unreachable!("paths point at segments"),
+    }
+}
+
+fn rename_labels(elements: &mut [AsmElement], renames: &HashMap<String, String>) {
+    for elem in elements {
+        match elem {
+            AsmElement::Label(name) => {
+                if let Some(new) = renames.get(name) {
+                    *name = new.clone();
+                }
+            }
+            AsmElement::Segment(_, inner) => rename_labels(inner, renames),
+            AsmElement::JumpTable(_, targets) => {
+                for target in targets {
+                    if let Some(new) = renames.get(target) {
+                        *target = new.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_names(elements: &[AsmElement], names: &mut HashSet<String>) {
+    for elem in elements {
+        match elem {
+            AsmElement::Segment(label, inner) => {
+                names.insert(label.clone());
+                collect_names(inner, names);
+            }
+            AsmElement::BytesSegment(label, _) | AsmElement::JumpTable(label, _) => {
+                names.insert(label.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn fresh_label(names: &HashSet<String>) -> String {
+    (0..)
+        .map(|n| format!("tail_{}", n))
+        .find(|name| !names.contains(name))
+        .expect("there is always an unused name")
+}
+
+fn estimate_size(elements: &[AsmElement]) -> usize {
+    elements
+        .iter()
+        .map(|elem| match elem {
+            AsmElement::Literal(data) => 1 + data.len().clamp(1, 32),
+            AsmElement::Label(_) | AsmElement::BytesPtr(_) | AsmElement::BytesSize(_) | AsmElement::Offset(_) => 3,
+            AsmElement::Placeholder(_) => 33,
+            AsmElement::Immutable(_, width) => 1 + width,
+            AsmElement::Align(alignment) => alignment / 2,
+            AsmElement::Segment(_, inner) => 1 + estimate_size(inner),
+            AsmElement::BytesSegment(_, data) => data.len(),
+            AsmElement::JumpTable(_, targets) => JUMP_TABLE_ENTRY_SIZE * targets.len(),
+            AsmElement::Let(_) | AsmElement::Anchor(_) => 0,
+            _ => 1,
+        })
+        .sum()
+}
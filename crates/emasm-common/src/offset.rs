@@ -0,0 +1,197 @@
+use crate::types::*;
+use std::fmt;
+
+/// Constant arithmetic over where segments are laid out, resolved once the
+/// layout is known and pushed at minimal width.
+///
+/// A name refers to a code segment (its JUMPDEST) or a bytes segment:
+///
+/// - `"name"` or `"bytes:name:ptr"`: where it starts;
+/// - `"name:end"` or `"bytes:name:end"`: the offset just past its last byte;
+/// - `"name:size"` or `"bytes:name:size"`: its length in bytes, `end - start`.
+///
+/// These combine with numbers, `+`, `-`, `*` and parentheses:
+/// `"bytes:data:ptr+0x20"`, `"end - start"`, `"runtime:size"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OffsetExpr {
+    Number(usize),
+    Start(String),
+    End(String),
+    Size(String),
+    Add(Box<OffsetExpr>, Box<OffsetExpr>),
+    Sub(Box<OffsetExpr>, Box<OffsetExpr>),
+    Mul(Box<OffsetExpr>, Box<OffsetExpr>),
+}
+
+impl OffsetExpr {
+    pub fn parse(s: &str) -> Result<Self, AssemblerError> {
+        let tokens = tokenize(s);
+        let mut parser = Parser { source: s, tokens: &tokens, pos: 0 };
+        let expr = parser.sum()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(parser.error(&format!("unexpected {}", token))),
+        }
+    }
+
+    /// Segments the expression refers to, in order of appearance.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Self::Number(_) => Vec::new(),
+            Self::Start(name) | Self::End(name) | Self::Size(name) => vec![name.as_str()],
+            Self::Add(a, b) | Self::Sub(a, b) | Self::Mul(a, b) => {
+                let mut names = a.names();
+                names.extend(b.names());
+                names
+            }
+        }
+    }
+
+    /// Value given the `(offset, size)` of every segment.
+    pub fn eval(
+        &self,
+        segment: &impl Fn(&str) -> Option<(usize, usize)>,
+    ) -> Result<usize, AssemblerError> {
+        let lookup = |name: &str| segment(name).ok_or_else(|| AssemblerError::LabelNotFound(name.to_string()));
+        let overflow = || AssemblerError::InvalidOffset(format!("{} is out of range", self));
+        match self {
+            Self::Number(n) => Ok(*n),
+            Self::Start(name) => Ok(lookup(name)?.0),
+            Self::End(name) => lookup(name).map(|(offset, size)| offset + size),
+            Self::Size(name) => Ok(lookup(name)?.1),
+            Self::Add(a, b) => a.eval(segment)?.checked_add(b.eval(segment)?).ok_or_else(overflow),
+            Self::Sub(a, b) => a.eval(segment)?.checked_sub(b.eval(segment)?).ok_or_else(overflow),
+            Self::Mul(a, b) => a.eval(segment)?.checked_mul(b.eval(segment)?).ok_or_else(overflow),
+        }
+    }
+}
+
+impl fmt::Display for OffsetExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{}", n),
+            Self::Start(name) => write!(f, "{}", name),
+            Self::End(name) => write!(f, "{}:end", name),
+            Self::Size(name) => write!(f, "{}:size", name),
+            Self::Add(a, b) => write!(f, "({} + {})", a, b),
+            Self::Sub(a, b) => write!(f, "({} - {})", a, b),
+            Self::Mul(a, b) => write!(f, "{} * {}", a, b),
+        }
+    }
+}
+
+/// Whether `s` reads as an offset expression. A lone `"bytes:name:ptr"` or
+/// `"bytes:name:size"` stays a plain bytes reference.
+///
+/// Names such as `"copy-loop"` read as expressions too, so parsers keep these
+/// strings until the program's labels are known and only parse the ones that
+/// are not labels, with [`parse_offset`].
+pub fn is_offset(s: &str) -> bool {
+    s.contains(|c: char| OPERATORS.contains(c) || c.is_whitespace())
+        || s.ends_with(":end")
+        || (!s.starts_with("bytes:") && s.ends_with(":size"))
+}
+
+/// An offset expression, or None for strings that are not one.
+pub fn parse_offset(s: &str) -> Option<Result<AsmElement, AssemblerError>> {
+    is_offset(s).then(|| OffsetExpr::parse(s).map(AsmElement::Offset))
+}
+
+const OPERATORS: &str = "+-*()";
+
+fn tokenize(s: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        if OPERATORS.contains(c) || c.is_whitespace() {
+            if let Some(begin) = start.take() {
+                tokens.push(&s[begin..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&s[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(begin) = start {
+        tokens.push(&s[begin..]);
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: &'a [&'a str],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn sum(&mut self) -> Result<OffsetExpr, AssemblerError> {
+        let mut expr = self.product()?;
+        while let Some(&op) = self.tokens.get(self.pos).filter(|t| **t == "+" || **t == "-") {
+            self.pos += 1;
+            let rhs = Box::new(self.product()?);
+            expr = if op == "+" { OffsetExpr::Add(Box::new(expr), rhs) } else { OffsetExpr::Sub(Box::new(expr), rhs) };
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<OffsetExpr, AssemblerError> {
+        let mut expr = self.operand()?;
+        while self.tokens.get(self.pos) == Some(&"*") {
+            self.pos += 1;
+            expr = OffsetExpr::Mul(Box::new(expr), Box::new(self.operand()?));
+        }
+        Ok(expr)
+    }
+
+    fn operand(&mut self) -> Result<OffsetExpr, AssemblerError> {
+        let token = *self.tokens.get(self.pos).ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        if token == "(" {
+            let expr = self.sum()?;
+            if self.tokens.get(self.pos) != Some(&")") {
+                return Err(self.error("missing )"));
+            }
+            self.pos += 1;
+            return Ok(expr);
+        }
+        if OPERATORS.contains(token) {
+            return Err(self.error(&format!("unexpected {}", token)));
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            let value = match token.strip_prefix("0x") {
+                Some(hex) => usize::from_str_radix(hex, 16),
+                None => token.parse(),
+            };
+            return value.map(OffsetExpr::Number).map_err(|_| self.error(&format!("invalid number {}", token)));
+        }
+        reference(token).ok_or_else(|| self.error(&format!("invalid reference {}", token)))
+    }
+
+    fn error(&self, reason: &str) -> AssemblerError {
+        AssemblerError::InvalidOffset(format!("{}: {}", self.source, reason))
+    }
+}
+
+fn reference(token: &str) -> Option<OffsetExpr> {
+    let (name, part) = match token.strip_prefix("bytes:") {
+        Some(rest) => match rest.rsplit_once(':') {
+            Some((name, "ptr")) => (name, "start"),
+            Some((name, part)) => (name, part),
+            None => (rest, "start"),
+        },
+        None => token.split_once(':').unwrap_or((token, "start")),
+    };
+    if name.is_empty() || name.contains(':') {
+        return None;
+    }
+    let name = name.to_string();
+    match part {
+        "start" => Some(OffsetExpr::Start(name)),
+        "end" => Some(OffsetExpr::End(name)),
+        "size" => Some(OffsetExpr::Size(name)),
+        _ => None,
+    }
+}
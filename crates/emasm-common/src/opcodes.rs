@@ -148,6 +148,18 @@ impl Opcode {
     pub const LOG2: Opcode = Opcode(0xa2);
     pub const LOG3: Opcode = Opcode(0xa3);
     pub const LOG4: Opcode = Opcode(0xa4);
+
+    // Only valid in EOF code sections, see `crate::eof`
+    pub const DATALOAD: Opcode = Opcode(0xd0);
+    pub const DATALOADN: Opcode = Opcode(0xd1);
+    pub const DATASIZE: Opcode = Opcode(0xd2);
+    pub const DATACOPY: Opcode = Opcode(0xd3);
+    pub const RJUMP: Opcode = Opcode(0xe0);
+    pub const RJUMPI: Opcode = Opcode(0xe1);
+    pub const RJUMPV: Opcode = Opcode(0xe2);
+    pub const CALLF: Opcode = Opcode(0xe3);
+    pub const RETF: Opcode = Opcode(0xe4);
+    pub const JUMPF: Opcode = Opcode(0xe5);
     
     pub const CREATE: Opcode = Opcode(0xf0);
     pub const CALL: Opcode = Opcode(0xf1);
@@ -161,6 +173,203 @@ impl Opcode {
     pub const SELFDESTRUCT: Opcode = Opcode(0xff);
 }
 
+/// Static description of an opcode: its mnemonic and stack effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub name: &'static str,
+    /// Number of stack items consumed.
+    pub inputs: usize,
+    /// Number of stack items produced.
+    pub outputs: usize,
+}
+
+/// Network upgrades that introduced opcodes, oldest first.
+///
+/// Upgrades that added no opcodes (Petersburg, Berlin, Paris) parse as the
+/// one before them. The default is Frontier, so that rewrites only use newer
+/// opcodes such as PUSH0 when the target fork is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Fork {
+    #[default]
+    Frontier,
+    Homestead,
+    Byzantium,
+    Constantinople,
+    Istanbul,
+    London,
+    Shanghai,
+    Cancun,
+}
+
+impl std::str::FromStr for Fork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "frontier" => Ok(Fork::Frontier),
+            "homestead" => Ok(Fork::Homestead),
+            "byzantium" => Ok(Fork::Byzantium),
+            "constantinople" | "petersburg" => Ok(Fork::Constantinople),
+            "istanbul" | "berlin" => Ok(Fork::Istanbul),
+            "london" | "paris" | "merge" => Ok(Fork::London),
+            "shanghai" => Ok(Fork::Shanghai),
+            "cancun" => Ok(Fork::Cancun),
+            other => Err(format!("unknown fork: {}", other)),
+        }
+    }
+}
+
+const PUSH_NAMES: [&str; 33] = [
+    "PUSH0", "PUSH1", "PUSH2", "PUSH3", "PUSH4", "PUSH5", "PUSH6", "PUSH7", "PUSH8",
+    "PUSH9", "PUSH10", "PUSH11", "PUSH12", "PUSH13", "PUSH14", "PUSH15", "PUSH16",
+    "PUSH17", "PUSH18", "PUSH19", "PUSH20", "PUSH21", "PUSH22", "PUSH23", "PUSH24",
+    "PUSH25", "PUSH26", "PUSH27", "PUSH28", "PUSH29", "PUSH30", "PUSH31", "PUSH32",
+];
+
+const DUP_NAMES: [&str; 16] = [
+    "DUP1", "DUP2", "DUP3", "DUP4", "DUP5", "DUP6", "DUP7", "DUP8",
+    "DUP9", "DUP10", "DUP11", "DUP12", "DUP13", "DUP14", "DUP15", "DUP16",
+];
+
+const SWAP_NAMES: [&str; 16] = [
+    "SWAP1", "SWAP2", "SWAP3", "SWAP4", "SWAP5", "SWAP6", "SWAP7", "SWAP8",
+    "SWAP9", "SWAP10", "SWAP11", "SWAP12", "SWAP13", "SWAP14", "SWAP15", "SWAP16",
+];
+
+const LOG_NAMES: [&str; 5] = ["LOG0", "LOG1", "LOG2", "LOG3", "LOG4"];
+
+impl Opcode {
+    /// Mnemonic and stack arity, or `None` for bytes that are not defined opcodes.
+    pub fn info(self) -> Option<OpcodeInfo> {
+        let (name, inputs, outputs) = match self.0 {
+            0x00 => ("STOP", 0, 0),
+            0x01 => ("ADD", 2, 1),
+            0x02 => ("MUL", 2, 1),
+            0x03 => ("SUB", 2, 1),
+            0x04 => ("DIV", 2, 1),
+            0x05 => ("SDIV", 2, 1),
+            0x06 => ("MOD", 2, 1),
+            0x07 => ("SMOD", 2, 1),
+            0x08 => ("ADDMOD", 3, 1),
+            0x09 => ("MULMOD", 3, 1),
+            0x0a => ("EXP", 2, 1),
+            0x0b => ("SIGNEXTEND", 2, 1),
+            0x10 => ("LT", 2, 1),
+            0x11 => ("GT", 2, 1),
+            0x12 => ("SLT", 2, 1),
+            0x13 => ("SGT", 2, 1),
+            0x14 => ("EQ", 2, 1),
+            0x15 => ("ISZERO", 1, 1),
+            0x16 => ("AND", 2, 1),
+            0x17 => ("OR", 2, 1),
+            0x18 => ("XOR", 2, 1),
+            0x19 => ("NOT", 1, 1),
+            0x1a => ("BYTE", 2, 1),
+            0x1b => ("SHL", 2, 1),
+            0x1c => ("SHR", 2, 1),
+            0x1d => ("SAR", 2, 1),
+            0x20 => ("SHA3", 2, 1),
+            0x30 => ("ADDRESS", 0, 1),
+            0x31 => ("BALANCE", 1, 1),
+            0x32 => ("ORIGIN", 0, 1),
+            0x33 => ("CALLER", 0, 1),
+            0x34 => ("CALLVALUE", 0, 1),
+            0x35 => ("CALLDATALOAD", 1, 1),
+            0x36 => ("CALLDATASIZE", 0, 1),
+            0x37 => ("CALLDATACOPY", 3, 0),
+            0x38 => ("CODESIZE", 0, 1),
+            0x39 => ("CODECOPY", 3, 0),
+            0x3a => ("GASPRICE", 0, 1),
+            0x3b => ("EXTCODESIZE", 1, 1),
+            0x3c => ("EXTCODECOPY", 4, 0),
+            0x3d => ("RETURNDATASIZE", 0, 1),
+            0x3e => ("RETURNDATACOPY", 3, 0),
+            0x3f => ("EXTCODEHASH", 1, 1),
+            0x40 => ("BLOCKHASH", 1, 1),
+            0x41 => ("COINBASE", 0, 1),
+            0x42 => ("TIMESTAMP", 0, 1),
+            0x43 => ("NUMBER", 0, 1),
+            0x44 => ("DIFFICULTY", 0, 1),
+            0x45 => ("GASLIMIT", 0, 1),
+            0x46 => ("CHAINID", 0, 1),
+            0x47 => ("SELFBALANCE", 0, 1),
+            0x48 => ("BASEFEE", 0, 1),
+            0x50 => ("POP", 1, 0),
+            0x51 => ("MLOAD", 1, 1),
+            0x52 => ("MSTORE", 2, 0),
+            0x53 => ("MSTORE8", 2, 0),
+            0x54 => ("SLOAD", 1, 1),
+            0x55 => ("SSTORE", 2, 0),
+            0x56 => ("JUMP", 1, 0),
+            0x57 => ("JUMPI", 2, 0),
+            0x58 => ("PC", 0, 1),
+            0x59 => ("MSIZE", 0, 1),
+            0x5a => ("GAS", 0, 1),
+            0x5b => ("JUMPDEST", 0, 0),
+            0x5f..=0x7f => (PUSH_NAMES[(self.0 - 0x5f) as usize], 0, 1),
+            0x80..=0x8f => {
+                let n = (self.0 - 0x80) as usize + 1;
+                (DUP_NAMES[n - 1], n, n + 1)
+            }
+            0x90..=0x9f => {
+                let n = (self.0 - 0x90) as usize + 1;
+                (SWAP_NAMES[n - 1], n + 1, n + 1)
+            }
+            0xa0..=0xa4 => {
+                let n = (self.0 - 0xa0) as usize;
+                (LOG_NAMES[n], n + 2, 0)
+            }
+            0xf0 => ("CREATE", 3, 1),
+            0xf1 => ("CALL", 7, 1),
+            0xf2 => ("CALLCODE", 7, 1),
+            0xf3 => ("RETURN", 2, 0),
+            0xf4 => ("DELEGATECALL", 6, 1),
+            0xf5 => ("CREATE2", 4, 1),
+            0xfa => ("STATICCALL", 6, 1),
+            0xfd => ("REVERT", 2, 0),
+            0xfe => ("INVALID", 0, 0),
+            0xff => ("SELFDESTRUCT", 1, 0),
+            _ => return None,
+        };
+        Some(OpcodeInfo { name, inputs, outputs })
+    }
+
+    /// Number of immediate bytes following the opcode (non-zero only for PUSH1-PUSH32).
+    pub fn immediate_size(self) -> usize {
+        match self.0 {
+            0x60..=0x7f => (self.0 - 0x5f) as usize,
+            _ => 0,
+        }
+    }
+
+    /// First fork in which the opcode is defined.
+    pub fn introduced_in(self) -> Fork {
+        match self.0 {
+            0xf4 => Fork::Homestead,
+            0x3d | 0x3e | 0xfa | 0xfd => Fork::Byzantium,
+            0x1b..=0x1d | 0x3f | 0xf5 => Fork::Constantinople,
+            0x46 | 0x47 => Fork::Istanbul,
+            0x48 => Fork::London,
+            0x5f => Fork::Shanghai,
+            _ => Fork::Frontier,
+        }
+    }
+
+    /// Whether the opcode can be used on `fork`.
+    pub fn available_in(self, fork: Fork) -> bool {
+        self.introduced_in() <= fork
+    }
+
+    /// Whether execution never continues past this opcode.
+    pub fn is_terminator(self) -> bool {
+        matches!(
+            self,
+            Opcode::STOP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID
+                | Opcode::SELFDESTRUCT | Opcode::JUMP
+        )
+    }
+}
+
 pub fn opcode_map() -> HashMap<&'static str, Opcode> {
     let mut map = HashMap::new();
     map.insert("stop", Opcode::STOP);
@@ -0,0 +1,254 @@
+use crate::{
+    constants::materialize,
+    gas::base_cost,
+    opcodes::{Fork, Opcode},
+    types::*,
+};
+use alloy_primitives::U256;
+use std::collections::HashMap;
+
+/// What the optimizer should prefer when a rewrite trades size for gas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Objective {
+    /// Smallest bytecode, then least gas.
+    #[default]
+    Size,
+    /// Least gas, then smallest bytecode.
+    Gas,
+}
+
+/// Which peephole rewrites [`optimize`] applies.
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    pub objective: Objective,
+    /// `PUSH x; POP` → nothing.
+    pub remove_push_pop: bool,
+    /// `SWAPn; SWAPn` → nothing.
+    pub cancel_swaps: bool,
+    /// `PUSH a; PUSH b; ADD` → `PUSH a+b`, and likewise for other pure operations.
+    pub fold_constants: bool,
+    /// `ISZERO; ISZERO; PUSH dest; JUMPI` → `PUSH dest; JUMPI`.
+    pub collapse_double_iszero: bool,
+    /// `DUP1; POP` → nothing.
+    pub remove_dup_pop: bool,
+    /// Replace literals with cheaper sequences such as `PUSH0 NOT`, see [`materialize`].
+    pub materialize_constants: bool,
+    /// Fork whose opcodes the rewrites may use, Frontier unless given.
+    pub fork: Fork,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            objective: Objective::default(),
+            remove_push_pop: true,
+            cancel_swaps: true,
+            fold_constants: true,
+            collapse_double_iszero: true,
+            remove_dup_pop: true,
+            materialize_constants: true,
+            fork: Fork::default(),
+        }
+    }
+}
+
+impl OptimizeOptions {
+    pub fn with_objective(objective: Objective) -> Self {
+        Self { objective, ..Self::default() }
+    }
+}
+
+/// Apply peephole rewrites to every straight-line sequence of the program.
+///
+/// Rewrites only match elements that are adjacent in the same segment, so
+/// they never span a JUMPDEST. Elements are fed through a window one at a
+/// time and the tail is rewritten until nothing matches, which lets
+/// `PUSH 1; PUSH 2; ADD; PUSH 3; ADD` fold all the way down to `PUSH 6`.
+/// Constants are materialized last, once nothing is left to fold.
+/// Named stack slots must be lowered first.
+pub fn optimize(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+    options: &OptimizeOptions,
+) -> Vec<AsmElement> {
+    let rewritten = rewrite(elements, opcodes, options);
+    if options.materialize_constants {
+        materialize_literals(&rewritten, options)
+    } else {
+        rewritten
+    }
+}
+
+fn rewrite(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+    options: &OptimizeOptions,
+) -> Vec<AsmElement> {
+    let mut out: Vec<AsmElement> = Vec::new();
+    for elem in elements {
+        match elem {
+            AsmElement::Segment(label, inner) => {
+                out.push(AsmElement::Segment(label.clone(), rewrite(inner, opcodes, options)));
+            }
+            other => {
+                out.push(other.clone());
+                while rewrite_tail(&mut out, opcodes, options) {}
+            }
+        }
+    }
+    out
+}
+
+fn materialize_literals(elements: &[AsmElement], options: &OptimizeOptions) -> Vec<AsmElement> {
+    let mut out = Vec::new();
+    for elem in elements {
+        match elem {
+            AsmElement::Segment(label, inner) => {
+                out.push(AsmElement::Segment(label.clone(), materialize_literals(inner, options)));
+            }
+            AsmElement::Literal(data) if data.len() <= 32 => {
+                let value = U256::from_be_slice(data);
+                out.extend(materialize(value, options.fork, options.objective).elements);
+            }
+            other => out.push(other.clone()),
+        }
+    }
+    out
+}
+
+/// Rewrite the end of `out` once; true if anything changed.
+fn rewrite_tail(
+    out: &mut Vec<AsmElement>,
+    opcodes: &HashMap<&'static str, Opcode>,
+    options: &OptimizeOptions,
+) -> bool {
+    let op = |i: usize| -> Option<Opcode> {
+        let index = out.len().checked_sub(i)?;
+        match &out[index] {
+            AsmElement::Opcode(name) => opcodes.get(name.as_str()).copied(),
+            _ => None,
+        }
+    };
+    let elem = |i: usize| out.len().checked_sub(i).map(|index| &out[index]);
+
+    // Two-element patterns that cancel out
+    let cancels = match (op(2), op(1)) {
+        (_, Some(Opcode::POP)) if options.remove_push_pop && elem(2).is_some_and(is_push) => true,
+        (Some(Opcode::DUP1), Some(Opcode::POP)) => options.remove_dup_pop,
+        (Some(a), Some(b)) if (0x90..=0x9f).contains(&a.0) && a == b => options.cancel_swaps,
+        _ => false,
+    };
+    if cancels {
+        out.truncate(out.len() - 2);
+        return true;
+    }
+
+    if options.collapse_double_iszero
+        && op(1) == Some(Opcode::JUMPI)
+        && elem(2).is_some_and(is_push)
+        && op(3) == Some(Opcode::ISZERO)
+        && op(4) == Some(Opcode::ISZERO)
+    {
+        let len = out.len();
+        out.drain(len - 4..len - 2);
+        return true;
+    }
+
+    if options.fold_constants {
+        if let Some(folded) = fold(out, op(1), options.objective) {
+            *out = folded;
+            return true;
+        }
+    }
+
+    false
+}
+
+fn fold(out: &[AsmElement], op: Option<Opcode>, objective: Objective) -> Option<Vec<AsmElement>> {
+    let op = op?;
+    let len = out.len();
+    let literal = |i: usize| match out.get(len.checked_sub(i)?)? {
+        AsmElement::Literal(data) if data.len() <= 32 => Some(U256::from_be_slice(data)),
+        _ => None,
+    };
+
+    // `top` is the last value pushed
+    let (consumed, result) = match op {
+        Opcode::ISZERO => (2, U256::from(literal(2)?.is_zero())),
+        Opcode::NOT => (2, !literal(2)?),
+        _ => {
+            let (second, top) = (literal(3)?, literal(2)?);
+            let result = match op {
+                Opcode::ADD => top.wrapping_add(second),
+                Opcode::MUL => top.wrapping_mul(second),
+                Opcode::SUB => top.wrapping_sub(second),
+                Opcode::DIV => top.checked_div(second).unwrap_or_default(),
+                Opcode::MOD => top.checked_rem(second).unwrap_or_default(),
+                Opcode::EXP => top.pow(second),
+                Opcode::AND => top & second,
+                Opcode::OR => top | second,
+                Opcode::XOR => top ^ second,
+                Opcode::LT => U256::from(top < second),
+                Opcode::GT => U256::from(top > second),
+                Opcode::EQ => U256::from(top == second),
+                Opcode::SHL => shift(second, top, |v, s| v << s),
+                Opcode::SHR => shift(second, top, |v, s| v >> s),
+                _ => return None,
+            };
+            (3, result)
+        }
+    };
+
+    let folded: Vec<u8> = result.to_be_bytes::<32>().iter().skip_while(|&&b| b == 0).copied().collect();
+    let old_cost = out[len - consumed..]
+        .iter()
+        .map(|e| match e {
+            AsmElement::Literal(data) => (push_size(data), 3),
+            _ => (1, base_cost(op)),
+        })
+        .fold((0, 0), |(s, g), (es, eg)| (s + es, g + eg));
+    let new_cost = (push_size(&folded), 3);
+    if !improves(old_cost, new_cost, objective) {
+        return None;
+    }
+
+    let mut result = out[..len - consumed].to_vec();
+    result.push(AsmElement::Literal(folded));
+    Some(result)
+}
+
+fn shift(value: U256, amount: U256, f: impl Fn(U256, usize) -> U256) -> U256 {
+    if amount >= U256::from(256) {
+        U256::ZERO
+    } else {
+        f(value, amount.to::<usize>())
+    }
+}
+
+/// Whether `(size, gas)` costs improve under the objective.
+fn improves(old: (usize, u64), new: (usize, u64), objective: Objective) -> bool {
+    match objective {
+        Objective::Size => (new.0, new.1) < (old.0, old.1),
+        Objective::Gas => (new.1, new.0) < (old.1, old.0),
+    }
+}
+
+/// Pushes the rewrites may drop or move. Immutables are left alone, since the
+/// constructor patches their slots.
+fn is_push(elem: &AsmElement) -> bool {
+    matches!(
+        elem,
+        AsmElement::Literal(_)
+            | AsmElement::Label(_)
+            | AsmElement::BytesPtr(_)
+            | AsmElement::BytesSize(_)
+            | AsmElement::Placeholder(_)
+            | AsmElement::Offset(_)
+    )
+}
+
+/// Encoded size of a literal push; zero is written as `PUSH1 0x00`.
+fn push_size(data: &[u8]) -> usize {
+    let significant = data.iter().skip_while(|&&b| b == 0).count();
+    1 + significant.clamp(1, 32)
+}
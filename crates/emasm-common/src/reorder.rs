@@ -0,0 +1,270 @@
+use crate::{
+    jumptable::JUMP_TABLE_ENTRY_SIZE,
+    offset::OffsetExpr,
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::HashMap;
+
+/// Top-level elements that have to stay together: a segment plus whatever
+/// falls through into it, or the elements that follow it before the next
+/// segment.
+#[derive(Debug, Clone)]
+struct Group {
+    elements: Vec<AsmElement>,
+    /// References to labels and bytes segments defined in the group.
+    weight: usize,
+    /// Rough encoded size, used to rank groups by weight per byte.
+    size: usize,
+    /// Label of the first element if it is a segment.
+    head: Option<String>,
+    /// Target of a trailing `"label", "jump"`.
+    jumps_to: Option<String>,
+}
+
+/// Reorder top-level segments to shrink the assembled program.
+///
+/// The elements before the first segment are the entry point and stay first.
+/// A segment is only moved together with the code that falls through into it
+/// and the code it falls through into, so fall-through is never broken.
+/// Three layouts are tried: source order, groups sorted by references per
+/// byte so that hot labels get short pushes, and that order with each group
+/// followed by the segment it ends by jumping to. In every layout, a
+/// `"label", "jump"` pair right before that label's segment is dropped.
+/// Whichever layout, or the original program, assembles smallest according
+/// to `size_of` is returned.
+///
+/// Code that runs off the end of the program relies on the implicit STOP
+/// after it, so the group holding it stays last. Programs whose offset
+/// expressions relate names of different groups, or that end in an anchor,
+/// depend on the order itself and are returned unchanged.
+///
+/// Literal jump targets are not adjusted, so programs that compute jump
+/// destinations from constants must not be reordered.
+pub fn reorder_segments(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+    size_of: impl Fn(&[AsmElement]) -> usize,
+) -> Vec<AsmElement> {
+    let mut refs = HashMap::new();
+    count_refs(elements, &mut refs);
+
+    let (mut groups, falls_off_end) = split_groups(elements, opcodes, &refs);
+    if groups.len() < 2 || ends_with_anchor(elements) || relates_groups(elements, &groups) {
+        return elements.to_vec();
+    }
+    let entry = groups.remove(0);
+    let tail = if falls_off_end { groups.pop() } else { None };
+
+    // Hottest groups first; the sort is stable, so ties keep source order
+    let mut ranked = groups.clone();
+    ranked.sort_by(|a, b| (b.weight * a.size.max(1)).cmp(&(a.weight * b.size.max(1))));
+
+    let candidates = [
+        elements.to_vec(),
+        join(&entry, &groups, tail.as_ref(), false),
+        join(&entry, &ranked, tail.as_ref(), false),
+        join(&entry, &ranked, tail.as_ref(), true),
+    ];
+    candidates
+        .into_iter()
+        .min_by_key(|c| size_of(c))
+        .expect("candidates are never empty")
+}
+
+/// Lay out groups in order, optionally pulling jump targets up behind their
+/// jumps, with `tail` last.
+fn join(entry: &Group, ranked: &[Group], tail: Option<&Group>, chain: bool) -> Vec<AsmElement> {
+    let mut placed = vec![false; ranked.len()];
+    let mut order = vec![entry];
+    let mut next = 0;
+    loop {
+        let last = order.last().expect("entry is always placed");
+        let target = last.jumps_to.as_ref().filter(|_| chain).and_then(|target| {
+            (0..ranked.len()).find(|&i| !placed[i] && ranked[i].head.as_ref() == Some(target))
+        });
+        let pick = match target {
+            Some(i) => i,
+            None => {
+                while next < ranked.len() && placed[next] {
+                    next += 1;
+                }
+                if next == ranked.len() {
+                    break;
+                }
+                next
+            }
+        };
+        placed[pick] = true;
+        order.push(&ranked[pick]);
+    }
+    order.extend(tail);
+
+    let mut result: Vec<AsmElement> = Vec::new();
+    for (i, group) in order.iter().enumerate() {
+        let falls_into_target = order.get(i + 1).is_some_and(|next| {
+            next.head.is_some() && next.head == group.jumps_to
+        });
+        let mut elements = group.elements.clone();
+        if falls_into_target {
+            strip_trailing_jump(&mut elements);
+        }
+        result.extend(elements);
+    }
+    result
+}
+
+/// Split the program into groups, and tell whether execution can run off the
+/// end of the last one.
+fn split_groups(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+    refs: &HashMap<String, usize>,
+) -> (Vec<Group>, bool) {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut falls_through = true;
+    for elem in elements {
+        // Code between segments sticks to what precedes it; a segment starts a new
+        // group unless execution can fall into it
+        let starts_group = match elem {
+            AsmElement::Segment(..) | AsmElement::BytesSegment(..) | AsmElement::JumpTable(..) => !falls_through,
+            _ => false,
+        } || groups.is_empty();
+
+        if starts_group {
+            groups.push(Group {
+                elements: Vec::new(),
+                weight: 0,
+                size: 0,
+                head: match elem {
+                    AsmElement::Segment(label, _) => Some(label.clone()),
+                    _ => None,
+                },
+                jumps_to: None,
+            });
+        }
+        let group = groups.last_mut().expect("a group was just pushed");
+        group.elements.push(elem.clone());
+        group.size += estimate_size(elem);
+        group.weight += defined_names(elem).iter().map(|n| refs.get(n).copied().unwrap_or(0)).sum::<usize>();
+
+        falls_through = match elem {
+            // Nothing should execute data; the verifier reports it if something does
+            AsmElement::BytesSegment(..) | AsmElement::JumpTable(..) => false,
+            _ => !ends_with_terminator(std::slice::from_ref(elem), opcodes),
+        };
+    }
+
+    for group in &mut groups {
+        group.jumps_to = trailing_jump(&group.elements);
+    }
+    (groups, falls_through)
+}
+
+/// Whether the program ends in an anchor, which marks the end of the code.
+fn ends_with_anchor(elements: &[AsmElement]) -> bool {
+    match elements.last() {
+        Some(AsmElement::Anchor(_)) => true,
+        Some(AsmElement::Segment(_, inner)) => ends_with_anchor(inner),
+        _ => false,
+    }
+}
+
+/// Whether an offset expression names segments of different groups, so that
+/// its value changes when the groups move.
+fn relates_groups(elements: &[AsmElement], groups: &[Group]) -> bool {
+    let owner: HashMap<String, usize> = groups.iter().enumerate()
+        .flat_map(|(i, group)| group.elements.iter().flat_map(defined_names).map(move |name| (name, i)))
+        .collect();
+    let mut exprs = Vec::new();
+    collect_offsets(elements, &mut exprs);
+    exprs.iter().any(|expr| {
+        let mut owners = expr.names().into_iter().filter_map(|name| owner.get(name));
+        owners.next().is_some_and(|first| owners.any(|other| other != first))
+    })
+}
+
+fn collect_offsets<'a>(elements: &'a [AsmElement], exprs: &mut Vec<&'a OffsetExpr>) {
+    for elem in elements {
+        match elem {
+            AsmElement::Offset(expr) => exprs.push(expr),
+            AsmElement::Segment(_, inner) => collect_offsets(inner, exprs),
+            _ => {}
+        }
+    }
+}
+
+/// Whether the last instruction executed in `elements` never falls through.
+fn ends_with_terminator(elements: &[AsmElement], opcodes: &HashMap<&'static str, Opcode>) -> bool {
+    match elements.last() {
+        Some(AsmElement::Opcode(name)) => opcodes.get(name.as_str()).is_some_and(|op| op.is_terminator()),
+        Some(AsmElement::Segment(_, inner)) => ends_with_terminator(inner, opcodes),
+        _ => false,
+    }
+}
+
+/// Label of a `"label", "jump"` pair at the very end of the group.
+fn trailing_jump(elements: &[AsmElement]) -> Option<String> {
+    match elements {
+        [.., AsmElement::Label(target), AsmElement::Opcode(op)] if op == "jump" => Some(target.clone()),
+        [.., AsmElement::Segment(_, inner)] => trailing_jump(inner),
+        _ => None,
+    }
+}
+
+fn strip_trailing_jump(elements: &mut Vec<AsmElement>) {
+    if let Some(AsmElement::Segment(_, inner)) = elements.last_mut() {
+        strip_trailing_jump(inner);
+    } else {
+        elements.truncate(elements.len().saturating_sub(2));
+    }
+}
+
+fn count_refs(elements: &[AsmElement], refs: &mut HashMap<String, usize>) {
+    for elem in elements {
+        match elem {
+            AsmElement::Label(name) | AsmElement::BytesPtr(name) | AsmElement::BytesSize(name) => {
+                *refs.entry(name.clone()).or_default() += 1;
+            }
+            AsmElement::Offset(expr) => {
+                for name in expr.names() {
+                    *refs.entry(name.to_string()).or_default() += 1;
+                }
+            }
+            AsmElement::Segment(_, inner) => count_refs(inner, refs),
+            AsmElement::JumpTable(_, targets) => {
+                for target in targets {
+                    *refs.entry(target.clone()).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn defined_names(elem: &AsmElement) -> Vec<String> {
+    match elem {
+        AsmElement::Segment(label, inner) => {
+            std::iter::once(label.clone()).chain(inner.iter().flat_map(defined_names)).collect()
+        }
+        AsmElement::BytesSegment(label, _) | AsmElement::JumpTable(label, _) | AsmElement::Anchor(label) => {
+            vec![label.clone()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn estimate_size(elem: &AsmElement) -> usize {
+    match elem {
+        AsmElement::Opcode(_) | AsmElement::DupSlot(_) | AsmElement::SwapSlot(_) => 1,
+        AsmElement::Literal(data) => 1 + data.len().clamp(1, 32),
+        AsmElement::Label(_) | AsmElement::BytesPtr(_) | AsmElement::BytesSize(_) | AsmElement::Offset(_) => 3,
+        AsmElement::Placeholder(_) => 33,
+        AsmElement::Immutable(_, width) => 1 + width,
+        AsmElement::Align(alignment) => alignment / 2,
+        AsmElement::Segment(_, inner) => 1 + inner.iter().map(estimate_size).sum::<usize>(),
+        AsmElement::BytesSegment(_, data) => data.len(),
+        AsmElement::JumpTable(_, targets) => JUMP_TABLE_ENTRY_SIZE * targets.len(),
+        AsmElement::Let(_) | AsmElement::Anchor(_) => 0,
+    }
+}
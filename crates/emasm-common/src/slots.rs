@@ -0,0 +1,184 @@
+use crate::{
+    cfg::{Cfg, InstrKind},
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::HashMap;
+
+/// Stack contents from the bottom up; `Some(name)` for items bound with `let`.
+type SlotLayout = Vec<Option<String>>;
+
+/// Replace named stack slot pseudo-instructions with plain DUPn/SWAPn opcodes.
+///
+/// The layout of named items is tracked along every static path of the
+/// program, starting from an empty stack. Names follow their values through
+/// ordinary stack operations, so `swap1` moves a name just as it moves the
+/// value. `"swap:name"` is the exception: it stores the top item into the
+/// slot, which keeps its name, and brings the previous value to the top.
+/// Where control flow joins, only names that agree on every path survive.
+pub fn lower_stack_slots(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+) -> Result<Vec<AsmElement>, AssemblerError> {
+    if !uses_slots(elements) {
+        return Ok(elements.to_vec());
+    }
+
+    let cfg = Cfg::build(elements, opcodes);
+    let mut entry: Vec<Option<SlotLayout>> = vec![None; cfg.blocks.len()];
+    let mut lowered: HashMap<ElementPath, String> = HashMap::new();
+    let mut worklist = Vec::new();
+    if !cfg.blocks.is_empty() {
+        entry[0] = Some(Vec::new());
+        worklist.push(0);
+    }
+
+    while let Some(b) = worklist.pop() {
+        let mut layout = entry[b].clone().unwrap_or_default();
+
+        for instr in cfg.block_instrs(b) {
+            match &instr.kind {
+                InstrKind::Push(_) => layout.push(None),
+                InstrKind::Op(op) => apply_opcode(&mut layout, *op),
+                InstrKind::Let(names) => {
+                    if names.len() > layout.len() {
+                        return Err(AssemblerError::InvalidStackSlot(format!(
+                            "{}: cannot name {} items with only {} on the stack",
+                            instr.path,
+                            names.len(),
+                            layout.len()
+                        )));
+                    }
+                    for slot in layout.iter_mut() {
+                        if slot.as_ref().is_some_and(|s| names.contains(s)) {
+                            *slot = None;
+                        }
+                    }
+                    let top = layout.len();
+                    for (i, name) in names.iter().enumerate() {
+                        layout[top - 1 - i] = Some(name.clone());
+                    }
+                }
+                InstrKind::DupSlot(name) => {
+                    let depth = slot_depth(&layout, name)?;
+                    if depth > 16 {
+                        return Err(AssemblerError::StackSlotTooDeep(name.clone(), depth));
+                    }
+                    lowered.insert(instr.path.clone(), format!("dup{}", depth));
+                    layout.push(None);
+                }
+                InstrKind::SwapSlot(name) => {
+                    let depth = slot_depth(&layout, name)?;
+                    if depth == 1 {
+                        return Err(AssemblerError::InvalidStackSlot(format!(
+                            "{}: {} is already on top of the stack",
+                            instr.path, name
+                        )));
+                    }
+                    if depth > 17 {
+                        return Err(AssemblerError::StackSlotTooDeep(name.clone(), depth));
+                    }
+                    lowered.insert(instr.path.clone(), format!("swap{}", depth - 1));
+                    if let Some(top) = layout.last_mut() {
+                        *top = None;
+                    }
+                }
+                InstrKind::Jumpdest(_) | InstrKind::Data(_) | InstrKind::Unknown(_) | InstrKind::Anchor(_) => {}
+            }
+        }
+
+        for &succ in &cfg.blocks[b].successors {
+            match &mut entry[succ] {
+                None => {
+                    entry[succ] = Some(layout.clone());
+                    worklist.push(succ);
+                }
+                Some(existing) => {
+                    if merge_layouts(existing, &layout) {
+                        worklist.push(succ);
+                    }
+                }
+            }
+        }
+    }
+
+    rewrite(elements, &ElementPath::default(), &lowered)
+}
+
+fn uses_slots(elements: &[AsmElement]) -> bool {
+    elements.iter().any(|elem| match elem {
+        AsmElement::Let(_) | AsmElement::DupSlot(_) | AsmElement::SwapSlot(_) => true,
+        AsmElement::Segment(_, inner) => uses_slots(inner),
+        _ => false,
+    })
+}
+
+/// Position of a named item counted from the top, 1 being the top.
+fn slot_depth(layout: &SlotLayout, name: &str) -> Result<usize, AssemblerError> {
+    layout
+        .iter()
+        .rev()
+        .position(|slot| slot.as_deref() == Some(name))
+        .map(|i| i + 1)
+        .ok_or_else(|| AssemblerError::UnknownStackSlot(name.to_string()))
+}
+
+fn apply_opcode(layout: &mut SlotLayout, op: Opcode) {
+    let Some(info) = op.info() else { return };
+    let len = layout.len();
+    match op.0 {
+        0x80..=0x8f => layout.push(None),
+        0x90..=0x9f => {
+            let n = info.inputs - 1;
+            if n < len {
+                layout.swap(len - 1, len - 1 - n);
+            }
+        }
+        _ => {
+            layout.truncate(len.saturating_sub(info.inputs));
+            layout.extend(std::iter::repeat_n(None, info.outputs));
+        }
+    }
+}
+
+/// Drop names that differ between `existing` and `incoming`; true if anything changed.
+fn merge_layouts(existing: &mut SlotLayout, incoming: &SlotLayout) -> bool {
+    if existing.len() != incoming.len() {
+        // Height mismatches are reported by the stack checker.
+        return false;
+    }
+    let mut changed = false;
+    for (slot, other) in existing.iter_mut().zip(incoming) {
+        if slot.is_some() && slot != other {
+            *slot = None;
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn rewrite(
+    elements: &[AsmElement],
+    parent: &ElementPath,
+    lowered: &HashMap<ElementPath, String>,
+) -> Result<Vec<AsmElement>, AssemblerError> {
+    let mut result = Vec::new();
+    for (i, elem) in elements.iter().enumerate() {
+        match elem {
+            AsmElement::Segment(label, inner) => {
+                let inner = rewrite(inner, &parent.segment(label), lowered)?;
+                result.push(AsmElement::Segment(label.clone(), inner));
+            }
+            AsmElement::Let(_) => {}
+            AsmElement::DupSlot(name) | AsmElement::SwapSlot(name) => {
+                // Unreachable code never gets a layout to resolve against.
+                let opcode = lowered
+                    .get(&parent.child(i))
+                    .ok_or_else(|| AssemblerError::UnknownStackSlot(name.clone()))?;
+                result.push(AsmElement::Opcode(opcode.clone()));
+            }
+            other => result.push(other.clone()),
+        }
+    }
+    Ok(result)
+}
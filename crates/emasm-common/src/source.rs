@@ -0,0 +1,372 @@
+//! JSON source format used by the CLI tools.
+//!
+//! A program is a JSON array written the same way as the `evm_asm!` input:
+//!
+//! ```json
+//! [
+//!   "0x01", "target", "jump",
+//!   ["target", [42, "0x00", "mstore", 32, "0x00", "return"]]
+//! ]
+//! ```
+//!
+//! Integers may be JSON numbers or `0x`-prefixed hex strings. Strings naming
+//! a segment defined anywhere in the program become label references, and
+//! strings naming a bytes segment or jump table push the address of its data.
+//! `"jumptable:name"` expands to the sequence that jumps to the entry whose
+//! index is on top of the stack, and `["dispatch", {"sig": "label", ...}]`
+//! expands to a function selector dispatcher. `"sig:transfer(address,uint256)"`,
+//! `"event:..."` and `"error:..."` push a function selector, an event topic and
+//! a custom error selector. `"immutable:owner"` and `"immutable:owner:20"` are
+//! PUSH32/PUSH20 slots filled in when the contract is deployed.
+//! `["child:name", [...]]` embeds another program, assembled with labels of
+//! its own, as a bytes segment; it is not written `["bytes:name", [...]]`, which
+//! would be ambiguous for `["0x00"]`. `["bytes:name", ...]` lists data directives, see
+//! [`parse_data`], and `"align:32"` pads with zeros to a 32-byte boundary.
+//! Arithmetic such as `"bytes:data:ptr+0x20"` or `"end - start"` pushes a
+//! constant computed from the layout, see [`OffsetExpr`](crate::offset::OffsetExpr).
+//! `"anchor:name"` names a position without emitting a JUMPDEST.
+//!
+//! EOF programs list their functions in an object instead, see
+//! [`parse_eof_program`] and [`crate::eof`].
+
+use crate::{
+    abi::keccak_literal,
+    contract::parse_immutable,
+    data::{parse_align, parse_data},
+    dispatch::{abi_json, Dispatcher},
+    eof::{is_eof_instruction, EofFunction, EofProgram},
+    jumptable::table_jump,
+    offset::{is_offset, parse_offset},
+    types::*,
+    Assembler,
+};
+use serde_json::Value;
+use std::{collections::HashSet, path::Path};
+
+pub fn parse_program(source: &str) -> Result<Vec<AsmElement>, AssemblerError> {
+    parse_program_in(source, Path::new("."))
+}
+
+/// Parse a program whose `"file:..."` data is relative to `base_dir`.
+pub fn parse_program_in(source: &str, base_dir: &Path) -> Result<Vec<AsmElement>, AssemblerError> {
+    let value: Value = serde_json::from_str(source)
+        .map_err(|e| AssemblerError::ParseError(e.to_string()))?;
+    let Value::Array(items) = value else {
+        return Err(AssemblerError::ParseError("program must be a JSON array".to_string()));
+    };
+
+    resolve_program(parse_elements(&items, base_dir)?)
+}
+
+pub fn parse_eof_program(source: &str) -> Result<EofProgram, AssemblerError> {
+    parse_eof_program_in(source, Path::new("."))
+}
+
+/// Parse an EOF program, `{"functions": [{"name": "main", "code": [...]}, ...]}`.
+///
+/// Functions declare their `"max_stack_height"` and may also give `"inputs"`
+/// and `"outputs"` (absent for a function that never returns). Segment and
+/// bytes names resolve across all functions.
+pub fn parse_eof_program_in(source: &str, base_dir: &Path) -> Result<EofProgram, AssemblerError> {
+    let value: Value = serde_json::from_str(source)
+        .map_err(|e| AssemblerError::ParseError(e.to_string()))?;
+    let Some(Value::Array(functions)) = value.get("functions") else {
+        return Err(AssemblerError::ParseError("EOF program must be an object with a functions array".to_string()));
+    };
+
+    let mut parsed = Vec::new();
+    for function in functions {
+        let field = |key: &str| function.get(key).filter(|v| !v.is_null());
+        let Some(name) = field("name").and_then(Value::as_str) else {
+            return Err(AssemblerError::ParseError(format!("function without a name: {}", function)));
+        };
+        let Some(Value::Array(code)) = field("code") else {
+            return Err(AssemblerError::ParseError(format!("function {} must have a code array", name)));
+        };
+        let number = |key: &str, max: u64| -> Result<Option<u64>, AssemblerError> {
+            match field(key) {
+                None => Ok(None),
+                Some(value) => value.as_u64().filter(|&n| n <= max).map(Some).ok_or_else(|| {
+                    AssemblerError::ParseError(format!("{} of function {} must be at most {}: {}", key, name, max, value))
+                }),
+            }
+        };
+        parsed.push(EofFunction {
+            name: name.to_string(),
+            inputs: number("inputs", 0x7f)?.unwrap_or(0) as u8,
+            outputs: number("outputs", 0x7f)?.map(|n| n as u8),
+            max_stack_height: number("max_stack_height", 0x3ff)?.ok_or_else(|| {
+                AssemblerError::ParseError(format!("function {} must declare its max_stack_height", name))
+            })? as u16,
+            code: parse_elements(code, base_dir)?,
+        });
+    }
+
+    let mut labels = HashSet::new();
+    let mut bytes_names = HashSet::new();
+    for function in &parsed {
+        collect_labels(&function.code, &mut labels, &mut bytes_names);
+    }
+    for function in &mut parsed {
+        function.code = resolve_labels(std::mem::take(&mut function.code), &labels, &bytes_names)?;
+    }
+    Ok(EofProgram::new(parsed))
+}
+
+fn resolve_program(elements: Vec<AsmElement>) -> Result<Vec<AsmElement>, AssemblerError> {
+    let mut labels = HashSet::new();
+    let mut bytes_names = HashSet::new();
+    collect_labels(&elements, &mut labels, &mut bytes_names);
+    resolve_labels(elements, &labels, &bytes_names)
+}
+
+/// ABI JSON for the functions routed by the program's dispatchers.
+pub fn parse_abi(source: &str) -> Result<Value, AssemblerError> {
+    let value: Value = serde_json::from_str(source)
+        .map_err(|e| AssemblerError::ParseError(e.to_string()))?;
+    let mut dispatchers = Vec::new();
+    collect_dispatchers(&value, &mut dispatchers)?;
+    abi_json(&dispatchers)
+}
+
+fn collect_dispatchers(value: &Value, dispatchers: &mut Vec<Dispatcher>) -> Result<(), AssemblerError> {
+    let Value::Array(items) = value else { return Ok(()) };
+    match parse_dispatch(items) {
+        Some(dispatcher) => dispatchers.push(dispatcher?),
+        // Child programs are separate contracts with ABIs of their own
+        None if items.first().and_then(Value::as_str).is_some_and(|s| s.starts_with("child:")) => {}
+        None => {
+            for item in items {
+                collect_dispatchers(item, dispatchers)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `["dispatch", {"sig": "label", ...}, {"fallback": "label", "mode": "binary"}]`,
+/// or None if `arr` is not a dispatcher. Both the functions and the options may
+/// also be written as `["key", "value"]` pairs.
+fn parse_dispatch(arr: &[Value]) -> Option<Result<Dispatcher, AssemblerError>> {
+    if arr.first()?.as_str()? != "dispatch" {
+        return None;
+    }
+    // A segment named "dispatch" holds code rather than string pairs
+    let functions = string_pairs(arr.get(1)?)?;
+    let options: Option<Vec<Vec<_>>> = arr[2..].iter()
+        .map(|option| match option {
+            Value::Array(_) => string_pair(option).map(|pair| vec![pair]),
+            other => string_pairs(other),
+        })
+        .collect();
+    Some(match options {
+        Some(options) => Dispatcher::from_parts(functions, options.concat()),
+        None => Err(AssemblerError::InvalidDispatch("options must be string pairs".to_string())),
+    })
+}
+
+fn string_pairs(value: &Value) -> Option<Vec<(String, String)>> {
+    match value {
+        Value::Object(map) => map.iter()
+            .map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+            .collect(),
+        Value::Array(pairs) => pairs.iter().map(string_pair).collect(),
+        _ => None,
+    }
+}
+
+fn string_pair(value: &Value) -> Option<(String, String)> {
+    match value.as_array()?.as_slice() {
+        [Value::String(key), Value::String(value)] => Some((key.clone(), value.clone())),
+        _ => None,
+    }
+}
+
+fn parse_elements(items: &[Value], base_dir: &Path) -> Result<Vec<AsmElement>, AssemblerError> {
+    let mut elements = Vec::new();
+    for item in items {
+        if let Some(dispatcher) = item.as_array().and_then(|arr| parse_dispatch(arr)) {
+            elements.extend(dispatcher?.lower()?);
+            continue;
+        }
+        match item.as_str().and_then(|s| s.strip_prefix("jumptable:")) {
+            Some(name) => elements.extend(table_jump(name)),
+            None => elements.push(parse_element(item, base_dir)?),
+        }
+    }
+    Ok(elements)
+}
+
+fn parse_element(item: &Value, base_dir: &Path) -> Result<AsmElement, AssemblerError> {
+    match item {
+        Value::Number(n) => {
+            let value = n.as_u64().ok_or_else(|| {
+                AssemblerError::ParseError(format!("integers must be unsigned 64-bit or hex strings: {}", n))
+            })?;
+            Ok(AsmElement::Literal(trim_leading_zeros(&value.to_be_bytes())))
+        }
+        Value::String(s) => parse_string(s),
+        Value::Array(arr) => parse_array(arr, base_dir),
+        other => Err(AssemblerError::ParseError(format!("unsupported element: {}", other))),
+    }
+}
+
+fn parse_string(s: &str) -> Result<AsmElement, AssemblerError> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        return Ok(AsmElement::Literal(trim_leading_zeros(&parse_hex(hex)?)));
+    }
+    if let Some(name) = s.strip_prefix("anchor:") {
+        return Ok(AsmElement::Anchor(name.to_string()));
+    }
+    // Signatures contain parentheses and spaces, so they go before offset expressions
+    if let Some(value) = keccak_literal(s) {
+        return Ok(AsmElement::Literal(value?));
+    }
+    // EOF operands such as "rjump:end" look like offset expressions
+    if is_eof_instruction(s) {
+        return Ok(AsmElement::Opcode(s.to_string()));
+    }
+    // Parsed once labels are known, since a label may look like an expression
+    if is_offset(s) {
+        return Ok(AsmElement::Opcode(s.to_string()));
+    }
+    if let Some(rest) = s.strip_prefix("bytes:") {
+        if let Some(label) = rest.strip_suffix(":ptr") {
+            return Ok(AsmElement::BytesPtr(label.to_string()));
+        }
+        if let Some(label) = rest.strip_suffix(":size") {
+            return Ok(AsmElement::BytesSize(label.to_string()));
+        }
+        return Ok(AsmElement::BytesPtr(rest.to_string()));
+    }
+    if let Some(immutable) = parse_immutable(s) {
+        return immutable;
+    }
+    if let Some(align) = parse_align(s) {
+        return align;
+    }
+    if let Some(name) = s.strip_prefix("dup:") {
+        return Ok(AsmElement::DupSlot(name.to_string()));
+    }
+    if let Some(name) = s.strip_prefix("swap:") {
+        return Ok(AsmElement::SwapSlot(name.to_string()));
+    }
+    Ok(AsmElement::Opcode(s.to_string()))
+}
+
+fn parse_array(arr: &[Value], base_dir: &Path) -> Result<AsmElement, AssemblerError> {
+    let Some(Value::String(label)) = arr.first() else {
+        return Err(AssemblerError::ParseError("segment array must start with string label".to_string()));
+    };
+
+    if label == "let" {
+        let names: Option<Vec<String>> = arr[1..].iter()
+            .map(|v| v.as_str().map(str::to_string))
+            .collect();
+        if let Some(names) = names.filter(|n| !n.is_empty()) {
+            return Ok(AsmElement::Let(names));
+        }
+    }
+
+    if label == "jumptable" {
+        let targets: Option<Vec<String>> = match arr.get(2) {
+            Some(Value::Array(targets)) => {
+                targets.iter().map(|v| v.as_str().map(str::to_string)).collect()
+            }
+            _ => None,
+        };
+        if let (Some(Value::String(name)), Some(targets), 3) = (arr.get(1), targets, arr.len()) {
+            return Ok(AsmElement::JumpTable(name.clone(), targets));
+        }
+    }
+
+    if let Some(name) = label.strip_prefix("child:") {
+        let [_, Value::Array(child)] = arr else {
+            return Err(AssemblerError::ParseError(format!("child program {} must be [\"child:name\", [...]]", name)));
+        };
+        let program = resolve_program(parse_elements(child, base_dir)?)?;
+        let bytecode = Assembler::new().assemble(&program).map_err(|e| {
+            AssemblerError::InvalidBytesSegment(format!("child program {}: {}", name, e))
+        })?;
+        return Ok(AsmElement::BytesSegment(name.to_string(), bytecode));
+    }
+
+    if let Some(name) = label.strip_prefix("bytes:") {
+        // Data is one or more directives, or ["0x.."]
+        let directives: Option<Vec<&str>> = match &arr[1..] {
+            [Value::Array(data)] if data.len() == 1 && data[0].is_string() => data[0].as_str().map(|d| vec![d]),
+            [] | [Value::Array(_)] => None,
+            parts => parts.iter().map(Value::as_str).collect(),
+        };
+        let directives = directives.ok_or_else(|| AssemblerError::InvalidBytesSegment(label.clone()))?;
+        let mut data = Vec::new();
+        for directive in directives {
+            data.extend(parse_data(directive, base_dir)?);
+        }
+        return Ok(AsmElement::BytesSegment(name.to_string(), data));
+    }
+
+    match (arr.get(1), arr.len()) {
+        (Some(Value::Array(inner)), 2) => {
+            Ok(AsmElement::Segment(label.clone(), parse_elements(inner, base_dir)?))
+        }
+        _ => Err(AssemblerError::ParseError(format!(
+            "segment {} must have an array as second element",
+            label
+        ))),
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, AssemblerError> {
+    let padded = if !hex.len().is_multiple_of(2) { format!("0{}", hex) } else { hex.to_string() };
+    hex::decode(&padded).map_err(|_| AssemblerError::InvalidHexLiteral(hex.to_string()))
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().skip_while(|&&b| b == 0).copied().collect()
+}
+
+fn collect_labels(
+    elements: &[AsmElement],
+    labels: &mut HashSet<String>,
+    bytes_names: &mut HashSet<String>,
+) {
+    for elem in elements {
+        match elem {
+            AsmElement::Segment(name, inner) => {
+                labels.insert(name.clone());
+                collect_labels(inner, labels, bytes_names);
+            }
+            AsmElement::Anchor(name) => {
+                labels.insert(name.clone());
+            }
+            AsmElement::BytesSegment(name, _) | AsmElement::JumpTable(name, _) => {
+                bytes_names.insert(name.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Bare names become labels or bytes pointers, and the remaining strings that
+/// read as offset expressions are parsed.
+fn resolve_labels(
+    elements: Vec<AsmElement>,
+    labels: &HashSet<String>,
+    bytes_names: &HashSet<String>,
+) -> Result<Vec<AsmElement>, AssemblerError> {
+    elements
+        .into_iter()
+        .map(|elem| match elem {
+            AsmElement::Opcode(name) if labels.contains(&name) => Ok(AsmElement::Label(name)),
+            AsmElement::Opcode(name) if bytes_names.contains(&name) => Ok(AsmElement::BytesPtr(name)),
+            AsmElement::Opcode(name) if !is_eof_instruction(&name) => {
+                parse_offset(&name).unwrap_or(Ok(AsmElement::Opcode(name)))
+            }
+            AsmElement::Segment(name, inner) => {
+                Ok(AsmElement::Segment(name, resolve_labels(inner, labels, bytes_names)?))
+            }
+            other => Ok(other),
+        })
+        .collect()
+}
@@ -0,0 +1,189 @@
+use crate::{
+    cfg::{Cfg, InstrKind, Terminator},
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::HashMap;
+
+/// Maximum number of items on the EVM stack.
+pub const STACK_LIMIT: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackIssueKind {
+    Underflow {
+        opcode: &'static str,
+        required: usize,
+        available: usize,
+    },
+    Overflow {
+        height: usize,
+    },
+    /// DUPn/SWAPn reaching below the bottom of the stack.
+    TooDeep {
+        opcode: &'static str,
+        depth: usize,
+        available: usize,
+    },
+    /// Jumps (or fall-through) reach the same JUMPDEST with different heights.
+    InconsistentHeight {
+        label: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Items still on the stack when execution halts normally.
+    LeftOnStack {
+        opcode: &'static str,
+        remaining: usize,
+    },
+}
+
+impl StackIssueKind {
+    /// Whether the issue makes the program misbehave, as opposed to being wasteful.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, StackIssueKind::LeftOnStack { .. })
+    }
+}
+
+impl std::fmt::Display for StackIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StackIssueKind::Underflow { opcode, required, available } => write!(
+                f,
+                "stack underflow: {} needs {} items, {} available",
+                opcode, required, available
+            ),
+            StackIssueKind::Overflow { height } => {
+                write!(f, "stack overflow: height {} exceeds {}", height, STACK_LIMIT)
+            }
+            StackIssueKind::TooDeep { opcode, depth, available } => write!(
+                f,
+                "{} reaches item {} but only {} on the stack",
+                opcode, depth, available
+            ),
+            StackIssueKind::InconsistentHeight { label, expected, found } => write!(
+                f,
+                "inconsistent stack height at {}: reached with {} and {}",
+                label, expected, found
+            ),
+            StackIssueKind::LeftOnStack { opcode, remaining } => {
+                write!(f, "{} leaves {} items on the stack", opcode, remaining)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackIssue {
+    pub path: ElementPath,
+    pub kind: StackIssueKind,
+}
+
+impl std::fmt::Display for StackIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// Track stack height through every statically reachable path of the program.
+///
+/// The program starts with an empty stack. Segments are entered with the
+/// height of the jump or fall-through that reaches them; blocks only reached
+/// through dynamic jumps are not analysed.
+pub fn check_stack(
+    elements: &[AsmElement],
+    opcodes: &HashMap<&'static str, Opcode>,
+) -> Vec<StackIssue> {
+    let cfg = Cfg::build(elements, opcodes);
+    let mut issues = Vec::new();
+    let mut entry: Vec<Option<usize>> = vec![None; cfg.blocks.len()];
+    let mut reported = vec![false; cfg.blocks.len()];
+    let mut worklist = Vec::new();
+
+    if !cfg.blocks.is_empty() {
+        entry[0] = Some(0);
+        worklist.push(0);
+    }
+
+    while let Some(b) = worklist.pop() {
+        let mut height = entry[b].unwrap_or(0);
+        let block = &cfg.blocks[b];
+
+        for instr in cfg.block_instrs(b) {
+            let mut issue = |kind| issues.push(StackIssue { path: instr.path.clone(), kind });
+            match &instr.kind {
+                InstrKind::Push(_) | InstrKind::DupSlot(_) => {
+                    height += 1;
+                    if height > STACK_LIMIT {
+                        issue(StackIssueKind::Overflow { height });
+                    }
+                }
+                InstrKind::Op(op) => {
+                    let Some(info) = op.info() else { continue };
+                    let depth = match op.0 {
+                        0x80..=0x9f => Some(info.inputs),
+                        _ => None,
+                    };
+                    match depth {
+                        Some(depth) if height < depth => issue(StackIssueKind::TooDeep {
+                            opcode: info.name,
+                            depth,
+                            available: height,
+                        }),
+                        None if height < info.inputs => issue(StackIssueKind::Underflow {
+                            opcode: info.name,
+                            required: info.inputs,
+                            available: height,
+                        }),
+                        _ => {}
+                    }
+                    height = (height + info.outputs).saturating_sub(info.inputs);
+                    if info.outputs > info.inputs && height > STACK_LIMIT {
+                        issue(StackIssueKind::Overflow { height });
+                    }
+                    if matches!(*op, Opcode::STOP | Opcode::RETURN) && height > 0 {
+                        issue(StackIssueKind::LeftOnStack { opcode: info.name, remaining: height });
+                    }
+                }
+                InstrKind::Jumpdest(_)
+                | InstrKind::Data(_)
+                | InstrKind::Unknown(_)
+                | InstrKind::Anchor(_)
+                | InstrKind::Let(_)
+                | InstrKind::SwapSlot(_) => {}
+            }
+        }
+
+        if block.terminator == Terminator::End && height > 0 {
+            if let Some(last) = cfg.block_instrs(b).last() {
+                issues.push(StackIssue {
+                    path: last.path.clone(),
+                    kind: StackIssueKind::LeftOnStack { opcode: "end of code", remaining: height },
+                });
+            }
+        }
+
+        for &succ in &block.successors {
+            match entry[succ] {
+                None => {
+                    entry[succ] = Some(height);
+                    worklist.push(succ);
+                }
+                Some(expected) if expected != height && !reported[succ] => {
+                    reported[succ] = true;
+                    let target = &cfg.blocks[succ];
+                    issues.push(StackIssue {
+                        path: cfg.instrs[target.start].path.clone(),
+                        kind: StackIssueKind::InconsistentHeight {
+                            label: target.label.clone().unwrap_or_else(|| "jumpdest".to_string()),
+                            expected,
+                            found: height,
+                        },
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    issues
+}
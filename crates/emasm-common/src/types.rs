@@ -1,3 +1,4 @@
+use crate::offset::OffsetExpr;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -22,9 +23,36 @@ pub enum AssemblerError {
     
     #[error("Invalid placeholder index: {0}")]
     InvalidPlaceholder(usize),
+
+    #[error("Unknown stack slot: {0}")]
+    UnknownStackSlot(String),
+
+    #[error("Stack slot {0} is {1} items deep, out of reach of DUP16/SWAP16")]
+    StackSlotTooDeep(String, usize),
+
+    #[error("Invalid stack slot operation: {0}")]
+    InvalidStackSlot(String),
+
+    #[error("Parse error: {0}")]
+    ParseError(String),
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Invalid dispatcher: {0}")]
+    InvalidDispatch(String),
+
+    #[error("Invalid contract: {0}")]
+    InvalidContract(String),
+
+    #[error("Invalid offset expression: {0}")]
+    InvalidOffset(String),
+
+    #[error("Invalid EOF: {0}")]
+    InvalidEof(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AsmElement {
     Opcode(String),
     Literal(Vec<u8>),
@@ -34,12 +62,34 @@ pub enum AsmElement {
     BytesPtr(String),
     BytesSize(String),
     Placeholder(usize),
+    /// Names the top stack items, topmost first: `["let", "dest", "src"]`.
+    Let(Vec<String>),
+    /// Copies a named stack item to the top (`"dup:name"`), lowered to DUPn.
+    DupSlot(String),
+    /// Moves the top item into a named slot (`"swap:name"`), lowered to SWAPn.
+    SwapSlot(String),
+    /// Table of 2-byte big-endian label offsets laid out as data:
+    /// `["jumptable", "name", ["l0", "l1", ...]]`.
+    JumpTable(String, Vec<String>),
+    /// Zero-filled PUSH of the given width that the constructor patches at
+    /// deploy time: `"immutable:owner"` (32 bytes) or `"immutable:owner:20"`.
+    Immutable(String, usize),
+    /// Zero bytes up to the next multiple of the alignment: `"align:32"`.
+    Align(usize),
+    /// Constant arithmetic over segment offsets and sizes, resolved during
+    /// layout: `"bytes:data:ptr+0x20"`, `"runtime:size"`, `"end - start"`.
+    Offset(OffsetExpr),
+    /// Names its offset without emitting a JUMPDEST, for code positions that
+    /// are read rather than jumped to: `"anchor:code_end"`.
+    Anchor(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct LabelInfo {
     pub offset: usize,
     pub size_estimate: usize,
+    /// Bytes from the segment's JUMPDEST to its end.
+    pub size: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -47,3 +97,43 @@ pub struct BytesInfo {
     pub offset: usize,
     pub size: usize,
 }
+
+/// Location of an element inside an `AsmElement` tree, used in diagnostics.
+///
+/// `segments` lists the enclosing segment labels from the outside in, and
+/// `index` is the position within the innermost one. A path without an index
+/// names the segment head itself (its JUMPDEST).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ElementPath {
+    pub segments: Vec<String>,
+    pub index: Option<usize>,
+}
+
+impl ElementPath {
+    pub fn child(&self, index: usize) -> Self {
+        Self {
+            segments: self.segments.clone(),
+            index: Some(index),
+        }
+    }
+
+    pub fn segment(&self, label: &str) -> Self {
+        let mut segments = self.segments.clone();
+        segments.push(label.to_string());
+        Self { segments, index: None }
+    }
+}
+
+impl std::fmt::Display for ElementPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.segments.is_empty() {
+            write!(f, "<root>")?;
+        } else {
+            write!(f, "{}", self.segments.join("/"))?;
+        }
+        if let Some(index) = self.index {
+            write!(f, "+{}", index)?;
+        }
+        Ok(())
+    }
+}
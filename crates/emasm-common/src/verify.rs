@@ -0,0 +1,209 @@
+use crate::{
+    cfg::{Cfg, InstrKind, PushOperand, Terminator},
+    layout::{Layout, LayoutEntry},
+    opcodes::Opcode,
+    types::*,
+};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyIssueKind {
+    /// A pushed label does not land on a JUMPDEST, e.g. because the bytes
+    /// before it decode as a PUSH whose immediate swallows it.
+    LabelNotJumpdest { label: String, offset: usize },
+    /// A value consumed by JUMP/JUMPI is not a valid jump destination.
+    InvalidJumpTarget { opcode: &'static str, target: usize },
+    /// The address of a bytes segment is used as a jump target.
+    JumpToData { opcode: &'static str, label: String },
+    /// Execution can fall through into a bytes segment.
+    FallIntoData { label: String },
+    /// An anchor, which has no JUMPDEST, is used as a jump target.
+    JumpToAnchor { opcode: &'static str, label: String },
+}
+
+impl std::fmt::Display for VerifyIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyIssueKind::LabelNotJumpdest { label, offset } => write!(
+                f,
+                "label {} at offset {:#x} is not a valid JUMPDEST",
+                label, offset
+            ),
+            VerifyIssueKind::InvalidJumpTarget { opcode, target } => {
+                write!(f, "{} to {:#x}, which is not a valid JUMPDEST", opcode, target)
+            }
+            VerifyIssueKind::JumpToData { opcode, label } => {
+                write!(f, "{} to bytes segment {}", opcode, label)
+            }
+            VerifyIssueKind::FallIntoData { label } => {
+                write!(f, "execution falls into bytes segment {}", label)
+            }
+            VerifyIssueKind::JumpToAnchor { opcode, label } => {
+                write!(f, "{} to anchor {}, which is not a JUMPDEST", opcode, label)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyIssue {
+    pub path: ElementPath,
+    pub kind: VerifyIssueKind,
+}
+
+impl std::fmt::Display for VerifyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// Valid jump destinations of `code`, as computed by the EVM: a JUMPDEST
+/// byte counts only if it is not part of a PUSH immediate.
+pub fn jumpdest_analysis(code: &[u8]) -> Vec<bool> {
+    let mut valid = vec![false; code.len()];
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = Opcode(code[pc]);
+        if op == Opcode::JUMPDEST {
+            valid[pc] = true;
+        }
+        pc += 1 + op.immediate_size();
+    }
+    valid
+}
+
+/// Verify jump targets and data placement of assembled code.
+///
+/// `cfg` must be built from the same (lowered) elements as `layout`. Values
+/// consumed by JUMP/JUMPI are traced back to the push that produced them
+/// through ordinary stack operations; values that cannot be traced are not
+/// checked.
+pub fn verify(code: &[u8], layout: &Layout, cfg: &Cfg) -> Vec<VerifyIssue> {
+    let valid = jumpdest_analysis(code);
+    let is_jumpdest = |offset: usize| valid.get(offset).copied().unwrap_or(false);
+    let mut issues = Vec::new();
+    let anchors: HashSet<&str> = layout
+        .entries
+        .iter()
+        .filter_map(|e| match &e.kind {
+            InstrKind::Anchor(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    for entry in &layout.entries {
+        if let InstrKind::Push(PushOperand::Label(label)) = &entry.kind {
+            if anchors.contains(label.as_str()) {
+                continue;
+            }
+            let offset = layout.labels.get(label).copied().unwrap_or(usize::MAX);
+            if !is_jumpdest(offset) {
+                issues.push(VerifyIssue {
+                    path: entry.path.clone(),
+                    kind: VerifyIssueKind::LabelNotJumpdest { label: label.clone(), offset },
+                });
+            }
+        }
+    }
+
+    let entries: HashMap<&ElementPath, usize> = layout
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (&e.path, i))
+        .collect();
+    let reachable = cfg.reachable();
+
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !reachable[b] {
+            continue;
+        }
+        if block.terminator == Terminator::Data {
+            if let InstrKind::Data(label) = &cfg.instrs[block.start].kind {
+                issues.push(VerifyIssue {
+                    path: cfg.instrs[block.start].path.clone(),
+                    kind: VerifyIssueKind::FallIntoData { label: label.clone() },
+                });
+            }
+            continue;
+        }
+
+        // Stack of producers (indices into `cfg.instrs`) for values pushed in this block
+        let mut stack: Vec<Option<usize>> = Vec::new();
+        for (i, instr) in cfg.instrs.iter().enumerate().take(block.end).skip(block.start) {
+            match &instr.kind {
+                InstrKind::Push(_) => stack.push(Some(i)),
+                InstrKind::Op(op) if *op == Opcode::JUMP || *op == Opcode::JUMPI => {
+                    let name = if *op == Opcode::JUMP { "JUMP" } else { "JUMPI" };
+                    let producer = stack.pop().flatten();
+                    if *op == Opcode::JUMPI {
+                        stack.pop();
+                    }
+                    if let Some(p) = producer {
+                        let entry = entries.get(&cfg.instrs[p].path).map(|&e| &layout.entries[e]);
+                        if let Some(kind) = entry.and_then(|e| check_target(code, e, name, &anchors, &is_jumpdest)) {
+                            issues.push(VerifyIssue { path: instr.path.clone(), kind });
+                        }
+                    }
+                }
+                InstrKind::Op(op) => apply_opcode(&mut stack, *op),
+                _ => {}
+            }
+        }
+    }
+
+    issues
+}
+
+fn check_target(
+    code: &[u8],
+    entry: &LayoutEntry,
+    opcode: &'static str,
+    anchors: &HashSet<&str>,
+    is_jumpdest: &impl Fn(usize) -> bool,
+) -> Option<VerifyIssueKind> {
+    match &entry.kind {
+        InstrKind::Push(PushOperand::Label(label)) if anchors.contains(label.as_str()) => {
+            Some(VerifyIssueKind::JumpToAnchor { opcode, label: label.clone() })
+        }
+        // Checked for every label push regardless of how it is consumed
+        InstrKind::Push(PushOperand::Label(_)) => None,
+        InstrKind::Push(PushOperand::BytesPtr(label)) => Some(VerifyIssueKind::JumpToData {
+            opcode,
+            label: label.clone(),
+        }),
+        InstrKind::Push(_) => {
+            let immediate = &code[entry.offset + 1..entry.offset + entry.size];
+            let target = immediate
+                .iter()
+                .try_fold(0usize, |acc, &b| acc.checked_mul(256).map(|v| v + b as usize))
+                .unwrap_or(usize::MAX);
+            (!is_jumpdest(target)).then_some(VerifyIssueKind::InvalidJumpTarget { opcode, target })
+        }
+        _ => None,
+    }
+}
+
+/// Track where stack values came from through DUP/SWAP; anything else is opaque.
+fn apply_opcode(stack: &mut Vec<Option<usize>>, op: Opcode) {
+    let Some(info) = op.info() else { return };
+    let len = stack.len();
+    match op.0 {
+        0x80..=0x8f => {
+            let n = info.inputs;
+            stack.push(if n <= len { stack[len - n] } else { None });
+        }
+        0x90..=0x9f => {
+            let n = info.inputs - 1;
+            if n < len {
+                stack.swap(len - 1, len - 1 - n);
+            } else if let Some(top) = stack.last_mut() {
+                *top = None;
+            }
+        }
+        _ => {
+            stack.truncate(len.saturating_sub(info.inputs));
+            stack.extend(std::iter::repeat_n(None, info.outputs));
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{parse_macro_input, ExprArray};
+use syn::{parse_macro_input, punctuated::Punctuated, Expr, ExprArray, Token};
 use std::collections::HashSet;
+use emasm_common::{
+    contract::{initcode, ContractOptions, DEPLOY_LABEL},
+    offset::{parse_offset, OffsetExpr},
+    peephole::Objective,
+    AsmElement, Assembler,
+};
 
+mod options;
 mod parser;
-use parser::{parse_asm_elements, AsmToken};
+use options::{ContractInput, MacroInput, MacroOptions};
+use parser::{collect_dispatchers, parse_asm_elements, AsmToken};
 
 /// Convert an AsmToken to a TokenStream2 for code generation (non-interpolator version)
 fn token_to_quote(elem: AsmToken, defined_labels: &HashSet<String>) -> TokenStream2 {
@@ -31,9 +39,6 @@ fn token_to_quote(elem: AsmToken, defined_labels: &HashSet<String>) -> TokenStre
         AsmToken::HexLiteral(hex) => {
             quote! { emasm_common::AsmElement::Literal(vec![#(#hex),*]) }
         }
-        AsmToken::Label(name) => {
-            quote! { emasm_common::AsmElement::Label(#name.to_string()) }
-        }
         AsmToken::Segment(name, inner) => {
             // Recursively convert inner elements
             let inner_tokens: Vec<TokenStream2> = inner.into_iter()
@@ -57,6 +62,39 @@ fn token_to_quote(elem: AsmToken, defined_labels: &HashSet<String>) -> TokenStre
         AsmToken::BytesSize(name) => {
             quote! { emasm_common::AsmElement::BytesSize(#name.to_string()) }
         }
+        AsmToken::Let(names) => {
+            quote! { emasm_common::AsmElement::Let(vec![#(#names.to_string()),*]) }
+        }
+        AsmToken::DupSlot(name) => {
+            quote! { emasm_common::AsmElement::DupSlot(#name.to_string()) }
+        }
+        AsmToken::SwapSlot(name) => {
+            quote! { emasm_common::AsmElement::SwapSlot(#name.to_string()) }
+        }
+        AsmToken::JumpTable(name, targets) => {
+            quote! {
+                emasm_common::AsmElement::JumpTable(#name.to_string(), vec![#(#targets.to_string()),*])
+            }
+        }
+        AsmToken::Immutable(name, width) => {
+            quote! { emasm_common::AsmElement::Immutable(#name.to_string(), #width) }
+        }
+        AsmToken::BytesExpr(name, expr) => {
+            quote! {
+                emasm_common::AsmElement::BytesSegment(#name.to_string(), AsRef::<[u8]>::as_ref(&#expr).to_vec())
+            }
+        }
+        AsmToken::Child(..) => unreachable!("child programs are assembled when parsed"),
+        AsmToken::Align(alignment) => {
+            quote! { emasm_common::AsmElement::Align(#alignment) }
+        }
+        AsmToken::Offset(expr) => {
+            let expr = offset_to_quote(&expr);
+            quote! { emasm_common::AsmElement::Offset(#expr) }
+        }
+        AsmToken::Anchor(name) => {
+            quote! { emasm_common::AsmElement::Anchor(#name.to_string()) }
+        }
     }
 }
 
@@ -84,9 +122,6 @@ fn token_to_quote_interp(elem: AsmToken, defined_labels: &HashSet<String>) -> To
         AsmToken::HexLiteral(hex) => {
             quote! { emasm_common::AsmElement::Literal(vec![#(#hex),*]) }
         }
-        AsmToken::Label(name) => {
-            quote! { emasm_common::AsmElement::Label(#name.to_string()) }
-        }
         AsmToken::Segment(name, inner) => {
             // Recursively convert inner elements
             let inner_tokens: Vec<TokenStream2> = inner.into_iter()
@@ -110,10 +145,160 @@ fn token_to_quote_interp(elem: AsmToken, defined_labels: &HashSet<String>) -> To
         AsmToken::BytesSize(name) => {
             quote! { emasm_common::AsmElement::BytesSize(#name.to_string()) }
         }
+        AsmToken::Let(names) => {
+            quote! { emasm_common::AsmElement::Let(vec![#(#names.to_string()),*]) }
+        }
+        AsmToken::DupSlot(name) => {
+            quote! { emasm_common::AsmElement::DupSlot(#name.to_string()) }
+        }
+        AsmToken::SwapSlot(name) => {
+            quote! { emasm_common::AsmElement::SwapSlot(#name.to_string()) }
+        }
+        AsmToken::JumpTable(name, targets) => {
+            quote! {
+                emasm_common::AsmElement::JumpTable(#name.to_string(), vec![#(#targets.to_string()),*])
+            }
+        }
+        AsmToken::Immutable(name, width) => {
+            quote! { emasm_common::AsmElement::Immutable(#name.to_string(), #width) }
+        }
+        AsmToken::BytesExpr(name, expr) => {
+            quote! {
+                emasm_common::AsmElement::BytesSegment(#name.to_string(), AsRef::<[u8]>::as_ref(&#expr).to_vec())
+            }
+        }
+        AsmToken::Child(..) => unreachable!("child programs are assembled when parsed"),
+        AsmToken::Align(alignment) => {
+            quote! { emasm_common::AsmElement::Align(#alignment) }
+        }
+        AsmToken::Offset(expr) => {
+            let expr = offset_to_quote(&expr);
+            quote! { emasm_common::AsmElement::Offset(#expr) }
+        }
+        AsmToken::Anchor(name) => {
+            quote! { emasm_common::AsmElement::Anchor(#name.to_string()) }
+        }
+    }
+}
+
+fn offset_to_quote(expr: &OffsetExpr) -> TokenStream2 {
+    let boxed = |a: &OffsetExpr, b: &OffsetExpr| {
+        let (a, b) = (offset_to_quote(a), offset_to_quote(b));
+        quote! { Box::new(#a), Box::new(#b) }
+    };
+    match expr {
+        OffsetExpr::Number(n) => quote! { emasm_common::offset::OffsetExpr::Number(#n) },
+        OffsetExpr::Start(name) => quote! { emasm_common::offset::OffsetExpr::Start(#name.to_string()) },
+        OffsetExpr::End(name) => quote! { emasm_common::offset::OffsetExpr::End(#name.to_string()) },
+        OffsetExpr::Size(name) => quote! { emasm_common::offset::OffsetExpr::Size(#name.to_string()) },
+        OffsetExpr::Add(a, b) => {
+            let operands = boxed(a, b);
+            quote! { emasm_common::offset::OffsetExpr::Add(#operands) }
+        }
+        OffsetExpr::Sub(a, b) => {
+            let operands = boxed(a, b);
+            quote! { emasm_common::offset::OffsetExpr::Sub(#operands) }
+        }
+        OffsetExpr::Mul(a, b) => {
+            let operands = boxed(a, b);
+            quote! { emasm_common::offset::OffsetExpr::Mul(#operands) }
+        }
+    }
+}
+
+/// Convert an AsmToken to the AsmElement it expands to, for compile-time analysis
+fn token_to_element(elem: &AsmToken, defined_labels: &HashSet<String>) -> AsmElement {
+    match elem {
+        AsmToken::Placeholder(idx) => AsmElement::Placeholder(*idx),
+        AsmToken::Opcode(name) => {
+            if defined_labels.contains(name) {
+                AsmElement::Label(name.clone())
+            } else {
+                AsmElement::Opcode(name.clone())
+            }
+        }
+        AsmToken::Literal(val) => {
+            let bytes = val.to_be_bytes();
+            AsmElement::Literal(bytes.iter().skip_while(|&&b| b == 0).copied().collect())
+        }
+        AsmToken::HexLiteral(hex) => AsmElement::Literal(hex.clone()),
+        AsmToken::Segment(name, inner) => AsmElement::Segment(
+            name.clone(),
+            inner.iter().map(|e| token_to_element(e, defined_labels)).collect(),
+        ),
+        AsmToken::BytesSegment(name, data) => AsmElement::BytesSegment(name.clone(), data.clone()),
+        AsmToken::BytesPtr(name) => AsmElement::BytesPtr(name.clone()),
+        AsmToken::BytesSize(name) => AsmElement::BytesSize(name.clone()),
+        AsmToken::Let(names) => AsmElement::Let(names.clone()),
+        AsmToken::DupSlot(name) => AsmElement::DupSlot(name.clone()),
+        AsmToken::SwapSlot(name) => AsmElement::SwapSlot(name.clone()),
+        AsmToken::JumpTable(name, targets) => AsmElement::JumpTable(name.clone(), targets.clone()),
+        AsmToken::Immutable(name, width) => AsmElement::Immutable(name.clone(), *width),
+        // The data is only known at runtime, and its size changes no jump or stack effect
+        AsmToken::BytesExpr(name, _) => AsmElement::BytesSegment(name.clone(), Vec::new()),
+        AsmToken::Child(..) => unreachable!("child programs are assembled when parsed"),
+        AsmToken::Align(alignment) => AsmElement::Align(*alignment),
+        AsmToken::Offset(expr) => AsmElement::Offset(expr.clone()),
+        AsmToken::Anchor(name) => AsmElement::Anchor(name.clone()),
+    }
+}
+
+/// Reject jump table entries that do not name a segment
+fn jump_table_error(elem: &AsmToken, defined_labels: &HashSet<String>) -> Option<TokenStream2> {
+    match elem {
+        AsmToken::JumpTable(name, targets) => {
+            let missing = targets.iter().find(|t| !defined_labels.contains(*t))?;
+            let msg = format!("Jump table {} refers to unknown label: {}", name, missing);
+            Some(quote! { compile_error!(#msg) })
+        }
+        AsmToken::Segment(_, inner) => inner.iter().find_map(|e| jump_table_error(e, defined_labels)),
+        _ => None,
+    }
+}
+
+/// Resolve named stack slots now so that bad slot references fail the build
+fn stack_slot_error(program: &[AsmElement]) -> Option<TokenStream2> {
+    let err = Assembler::new().lower_stack_slots(program).err()?;
+    let msg = format!("Stack slot error: {}", err);
+    Some(quote! { compile_error!(#msg) })
+}
+
+/// Run the static stack checker and turn its errors into a `compile_error!`
+fn stack_check_error(program: &[AsmElement]) -> Option<TokenStream2> {
+    let errors: Vec<String> = Assembler::new()
+        .check_stack(program)
+        .into_iter()
+        .filter(|issue| issue.kind.is_error())
+        .map(|issue| issue.to_string())
+        .collect();
+    if errors.is_empty() {
+        return None;
     }
+    let msg = format!(
+        "Stack check failed (use `stack_check = false` to disable):\n{}",
+        errors.join("\n")
+    );
+    Some(quote! { compile_error!(#msg) })
 }
 
-/// Collect all defined labels recursively
+/// Assemble the program now and reject unsafe jumps or executable data
+fn verify_error(program: &[AsmElement]) -> Option<TokenStream2> {
+    let errors: Vec<String> = match Assembler::new().verify(program) {
+        Ok(issues) => issues.into_iter().map(|issue| issue.to_string()).collect(),
+        // Assembly errors are reported when the program is assembled
+        Err(_) => return None,
+    };
+    if errors.is_empty() {
+        return None;
+    }
+    let msg = format!(
+        "Verification failed:\n{}",
+        errors.join("\n")
+    );
+    Some(quote! { compile_error!(#msg) })
+}
+
+/// Collect all defined labels and anchors recursively
 fn collect_labels(elem: &AsmToken, labels: &mut HashSet<String>) {
     match elem {
         AsmToken::Segment(name, inner) => {
@@ -122,13 +307,104 @@ fn collect_labels(elem: &AsmToken, labels: &mut HashSet<String>) {
                 collect_labels(e, labels);
             }
         }
-        AsmToken::BytesSegment(name, _) => {
+        AsmToken::Anchor(name) => {
             labels.insert(name.clone());
         }
         _ => {}
     }
 }
 
+/// Collect all bytes segment and jump table names recursively
+fn collect_bytes_names(elem: &AsmToken, names: &mut HashSet<String>) {
+    match elem {
+        AsmToken::Segment(_, inner) => {
+            for e in inner {
+                collect_bytes_names(e, names);
+            }
+        }
+        AsmToken::BytesSegment(name, _) | AsmToken::BytesExpr(name, _) | AsmToken::JumpTable(name, _) => {
+            names.insert(name.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Turn bare references to bytes segments into pointers to their data, and
+/// parse the strings that read as offset expressions and are not labels
+fn resolve_bytes_refs(
+    elements: Vec<AsmToken>,
+    labels: &HashSet<String>,
+    bytes_names: &HashSet<String>,
+) -> Result<Vec<AsmToken>, String> {
+    elements
+        .into_iter()
+        .map(|elem| match elem {
+            AsmToken::Opcode(name) if bytes_names.contains(&name) => Ok(AsmToken::BytesPtr(name)),
+            AsmToken::Opcode(name) if !labels.contains(&name) => match parse_offset(&name) {
+                Some(Ok(AsmElement::Offset(expr))) => Ok(AsmToken::Offset(expr)),
+                Some(Ok(other)) => unreachable!("offset expressions parse to {:?}", other),
+                Some(Err(e)) => Err(e.to_string()),
+                None => Ok(AsmToken::Opcode(name)),
+            },
+            AsmToken::Segment(name, inner) => {
+                Ok(AsmToken::Segment(name, resolve_bytes_refs(inner, labels, bytes_names)?))
+            }
+            other => Ok(other),
+        })
+        .collect()
+}
+
+/// Parse a program and resolve bytes segment references
+fn parse_program(exprs: &Punctuated<Expr, Token![,]>) -> Result<Vec<AsmToken>, String> {
+    resolve_program(assemble_children(parse_asm_elements(exprs)?)?)
+}
+
+fn resolve_program(elements: Vec<AsmToken>) -> Result<Vec<AsmToken>, String> {
+    let mut labels = HashSet::new();
+    let mut bytes_names = HashSet::new();
+    for elem in &elements {
+        collect_labels(elem, &mut labels);
+        collect_bytes_names(elem, &mut bytes_names);
+    }
+    resolve_bytes_refs(elements, &labels, &bytes_names)
+}
+
+/// Assemble child programs now, each with its own labels, into plain bytes segments
+fn assemble_children(elements: Vec<AsmToken>) -> Result<Vec<AsmToken>, String> {
+    elements
+        .into_iter()
+        .map(|elem| match elem {
+            AsmToken::Child(name, inner) => {
+                if inner.iter().any(has_bytes_expr) {
+                    return Err(format!("child program {} cannot embed Rust expressions", name));
+                }
+                let inner = resolve_program(assemble_children(inner)?)?;
+                let mut defined_labels = HashSet::new();
+                for elem in &inner {
+                    collect_labels(elem, &mut defined_labels);
+                }
+                let program: Vec<AsmElement> = inner.iter()
+                    .map(|e| token_to_element(e, &defined_labels))
+                    .collect();
+                let bytecode = Assembler::new()
+                    .assemble(&program)
+                    .map_err(|e| format!("child program {}: {}", name, e))?;
+                Ok(AsmToken::BytesSegment(name, bytecode))
+            }
+            AsmToken::Segment(name, inner) => Ok(AsmToken::Segment(name, assemble_children(inner)?)),
+            other => Ok(other),
+        })
+        .collect()
+}
+
+fn has_bytes_expr(elem: &AsmToken) -> bool {
+    match elem {
+        AsmToken::BytesExpr(..) => true,
+        AsmToken::Segment(_, inner) | AsmToken::Child(_, inner) => inner.iter().any(has_bytes_expr),
+        _ => false,
+    }
+}
+
 /// Count the maximum placeholder index recursively
 fn count_placeholders(elem: &AsmToken) -> usize {
     match elem {
@@ -140,31 +416,180 @@ fn count_placeholders(elem: &AsmToken) -> usize {
     }
 }
 
+/// Parse a program into the elements it expands to and the tokens that build them.
+/// `outer_labels` are defined by code the program is wrapped in.
+fn compile_program(
+    exprs: &Punctuated<Expr, Token![,]>,
+    outer_labels: &[&str],
+) -> Result<(Vec<AsmElement>, Vec<TokenStream2>), TokenStream2> {
+    let elements = parse_program(exprs).map_err(|e| {
+        let error_msg = format!("Parse error: {}", e);
+        quote! { compile_error!(#error_msg) }
+    })?;
+
+    // Collect all defined labels
+    let mut defined_labels: HashSet<String> = outer_labels.iter().map(|l| l.to_string()).collect();
+    for elem in &elements {
+        collect_labels(elem, &mut defined_labels);
+    }
+
+    if let Some(error) = elements.iter().find_map(|e| jump_table_error(e, &defined_labels)) {
+        return Err(error);
+    }
+
+    let program: Vec<AsmElement> = elements.iter()
+        .map(|e| token_to_element(e, &defined_labels))
+        .collect();
+    if let Some(error) = stack_slot_error(&program) {
+        return Err(error);
+    }
+
+    let element_tokens: Vec<TokenStream2> = elements.into_iter()
+        .map(|elem| token_to_quote(elem, &defined_labels))
+        .collect();
+    Ok((program, element_tokens))
+}
+
+/// Run the stack checker and verifier the options ask for
+fn check_program(program: &[AsmElement], options: &MacroOptions) -> Option<TokenStream2> {
+    if options.stack_check {
+        if let Some(error) = stack_check_error(program) {
+            return Some(error);
+        }
+    }
+    if options.verify {
+        return verify_error(program);
+    }
+    None
+}
+
+/// Statement that runs the peephole optimizer on the `elements` variable, if enabled
+fn optimize_step(options: &MacroOptions, elements: &syn::Ident) -> Option<TokenStream2> {
+    options.optimize.map(|objective| {
+        let objective = match objective {
+            Objective::Size => quote! { emasm_common::peephole::Objective::Size },
+            Objective::Gas => quote! { emasm_common::peephole::Objective::Gas },
+        };
+        let fork = syn::Ident::new(&format!("{:?}", options.fork), proc_macro2::Span::call_site());
+        quote! {
+            let optimize_options = emasm_common::peephole::OptimizeOptions {
+                objective: #objective,
+                fork: emasm_common::opcodes::Fork::#fork,
+                ..Default::default()
+            };
+            let #elements = assembler
+                .optimize(&#elements, &optimize_options)
+                .expect("Optimization failed");
+        }
+    })
+}
+
+/// `include_bytes!` of every `file:` the program read, so Cargo rebuilds when they change
+fn track_included_files() -> TokenStream2 {
+    let paths = parser::take_included_files().into_iter().map(|path| path.display().to_string());
+    quote! { #(const _: &[u8] = include_bytes!(#paths);)* }
+}
+
 #[proc_macro]
 pub fn evm_asm(input: TokenStream) -> TokenStream {
-    let input_array = parse_macro_input!(input as ExprArray);
+    let MacroInput { options, program } = parse_macro_input!(input as MacroInput);
+    parser::take_included_files();
 
-    match parse_asm_elements(&input_array.elems) {
-        Ok(elements) => {
-            // Collect all defined labels
-            let mut defined_labels = HashSet::new();
-            for elem in &elements {
-                collect_labels(elem, &mut defined_labels);
-            }
+    let (program, element_tokens) = match compile_program(&program.elems, &[]) {
+        Ok(compiled) => compiled,
+        Err(error) => return TokenStream::from(error),
+    };
+    if let Some(error) = check_program(&program, &options) {
+        return TokenStream::from(error);
+    }
 
-            let element_tokens: Vec<TokenStream2> = elements.into_iter()
-                .map(|elem| token_to_quote(elem, &defined_labels))
-                .collect();
+    let elements = syn::Ident::new("elements", proc_macro2::Span::call_site());
+    let optimize = optimize_step(&options, &elements);
+    let included = track_included_files();
 
-            let expanded = quote! {
-                {
-                    let elements = vec![#(#element_tokens),*];
-                    let assembler = emasm_common::Assembler::new();
-                    assembler.assemble(&elements).expect("Assembly failed")
-                }
+    let expanded = quote! {
+        {
+            #included
+            let elements = vec![#(#element_tokens),*];
+            let assembler = emasm_common::Assembler::new();
+            #optimize
+            assembler.assemble(&elements).expect("Assembly failed")
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Initcode that runs a constructor and deploys a runtime program
+#[proc_macro]
+pub fn evm_contract(input: TokenStream) -> TokenStream {
+    let ContractInput { options, constructor_args, immutables, constructor, runtime } =
+        parse_macro_input!(input as ContractInput);
+    parser::take_included_files();
+
+    let compiled = compile_program(&constructor.elems, &[DEPLOY_LABEL])
+        .and_then(|constructor| Ok((constructor, compile_program(&runtime.elems, &[])?)));
+    let ((constructor, constructor_tokens), (runtime, runtime_tokens)) = match compiled {
+        Ok(compiled) => compiled,
+        Err(error) => return TokenStream::from(error),
+    };
+
+    // The runtime is opaque data in the initcode, so only its immutables matter here
+    let slots = Assembler::new()
+        .assemble_with_layout(&runtime)
+        .map(|(_, layout)| layout.immutable_slots())
+        .unwrap_or_default();
+    let contract_options = ContractOptions { constructor_args, immutables: immutables.clone() };
+    let initcode = match initcode(&constructor, Vec::new(), &slots, &contract_options) {
+        Ok(initcode) => initcode,
+        Err(e) => {
+            let error_msg = e.to_string();
+            return TokenStream::from(quote! { compile_error!(#error_msg) });
+        }
+    };
+    if let Some(error) = check_program(&runtime, &options).or_else(|| check_program(&initcode, &options)) {
+        return TokenStream::from(error);
+    }
+
+    let constructor_ident = syn::Ident::new("constructor", proc_macro2::Span::call_site());
+    let runtime_ident = syn::Ident::new("runtime", proc_macro2::Span::call_site());
+    let optimize_constructor = optimize_step(&options, &constructor_ident);
+    let optimize_runtime = optimize_step(&options, &runtime_ident);
+    let included = track_included_files();
+
+    let expanded = quote! {
+        {
+            #included
+            let constructor = vec![#(#constructor_tokens),*];
+            let runtime = vec![#(#runtime_tokens),*];
+            let assembler = emasm_common::Assembler::new();
+            #optimize_constructor
+            #optimize_runtime
+            let options = emasm_common::contract::ContractOptions {
+                constructor_args: #constructor_args,
+                immutables: vec![#(#immutables.to_string()),*],
             };
+            assembler
+                .assemble_contract(&constructor, &runtime, &options)
+                .expect("Assembly failed")
+        }
+    };
 
-            TokenStream::from(expanded)
+    TokenStream::from(expanded)
+}
+
+/// ABI JSON for the functions routed by the program's `["dispatch", ...]` elements
+#[proc_macro]
+pub fn evm_abi(input: TokenStream) -> TokenStream {
+    let input_array = parse_macro_input!(input as ExprArray);
+
+    let abi = collect_dispatchers(&input_array.elems).and_then(|dispatchers| {
+        emasm_common::dispatch::abi_json(&dispatchers).map_err(|e| e.to_string())
+    });
+    match abi {
+        Ok(abi) => {
+            let json = serde_json::to_string_pretty(&abi).expect("ABI JSON always serializes");
+            TokenStream::from(quote! { #json })
         }
         Err(e) => {
             let error_msg = format!("Parse error: {}", e);
@@ -178,8 +603,9 @@ pub fn evm_asm(input: TokenStream) -> TokenStream {
 #[proc_macro]
 pub fn evm_asm_interpolator(input: TokenStream) -> TokenStream {
     let input_array = parse_macro_input!(input as ExprArray);
+    parser::take_included_files();
 
-    match parse_asm_elements(&input_array.elems) {
+    match parse_program(&input_array.elems) {
         Ok(elements) => {
             // Collect all defined labels
             let mut defined_labels = HashSet::new();
@@ -187,6 +613,17 @@ pub fn evm_asm_interpolator(input: TokenStream) -> TokenStream {
                 collect_labels(elem, &mut defined_labels);
             }
 
+            if let Some(error) = elements.iter().find_map(|e| jump_table_error(e, &defined_labels)) {
+                return TokenStream::from(error);
+            }
+
+            let program: Vec<AsmElement> = elements.iter()
+                .map(|e| token_to_element(e, &defined_labels))
+                .collect();
+            if let Some(error) = stack_slot_error(&program) {
+                return TokenStream::from(error);
+            }
+
             // Count placeholders
             let placeholder_count = elements.iter()
                 .map(count_placeholders)
@@ -201,8 +638,11 @@ pub fn evm_asm_interpolator(input: TokenStream) -> TokenStream {
                 .map(|i| syn::Ident::new(&format!("arg{}", i), proc_macro2::Span::call_site()))
                 .collect();
 
+            let included = track_included_files();
+
             let expanded = quote! {
                 {
+                    #included
                     use emasm_common::EVMEncodable;
 
                     let template = vec![#(#element_tokens),*];
@@ -0,0 +1,170 @@
+use emasm_common::{opcodes::Fork, peephole::Objective};
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Expr, ExprArray, ExprAssign, ExprLit, ExprPath, Lit, Token,
+};
+
+/// Settings given before the program, e.g. `evm_asm!(stack_check = false, [...])`.
+#[derive(Debug, Clone)]
+pub struct MacroOptions {
+    /// Reject programs with stack errors at compile time.
+    pub stack_check: bool,
+    /// Reject programs with unsafe jumps or executable data at compile time.
+    /// Off unless asked for with `verify = true`.
+    pub verify: bool,
+    /// Run the peephole optimizer before assembling: `optimize = "size"` or `"gas"`.
+    pub optimize: Option<Objective>,
+    /// Fork the optimizer may target, e.g. `fork = "cancun"` to allow PUSH0.
+    /// Frontier when not given, so optimized code runs on any chain.
+    pub fork: Fork,
+}
+
+impl Default for MacroOptions {
+    fn default() -> Self {
+        Self { stack_check: true, verify: false, optimize: None, fork: Fork::default() }
+    }
+}
+
+/// Macro input: zero or more `key = value` options followed by the program array.
+pub struct MacroInput {
+    pub options: MacroOptions,
+    pub program: ExprArray,
+}
+
+impl Parse for MacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let exprs = Punctuated::<Expr, Token![,]>::parse_terminated(input)?;
+        let mut options = MacroOptions::default();
+        let mut program = None;
+
+        for expr in exprs {
+            match expr {
+                Expr::Array(arr) if program.is_none() => program = Some(arr),
+                Expr::Assign(assign) if program.is_none() => apply_option(&mut options, &assign)?,
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "expected `option = value` settings followed by one program array",
+                    ))
+                }
+            }
+        }
+
+        let program = program.ok_or_else(|| input.error("expected a program array"))?;
+        Ok(Self { options, program })
+    }
+}
+
+/// `evm_contract!` input: options, then the constructor and runtime arrays.
+pub struct ContractInput {
+    pub options: MacroOptions,
+    /// Copy ABI-encoded arguments appended to the initcode to memory: `constructor_args = true`.
+    pub constructor_args: bool,
+    /// Immutables the constructor leaves values for, first on top: `immutables = ["owner"]`.
+    pub immutables: Vec<String>,
+    pub constructor: ExprArray,
+    pub runtime: ExprArray,
+}
+
+impl Parse for ContractInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let exprs = Punctuated::<Expr, Token![,]>::parse_terminated(input)?;
+        let mut options = MacroOptions::default();
+        let mut constructor_args = false;
+        let mut immutables = Vec::new();
+        let mut programs = Vec::new();
+
+        for expr in exprs {
+            match expr {
+                Expr::Array(arr) if programs.len() < 2 => programs.push(arr),
+                Expr::Assign(assign) if programs.is_empty() => {
+                    match option_name(&assign)?.as_str() {
+                        "constructor_args" => constructor_args = parse_bool(&assign.right)?,
+                        "immutables" => immutables = parse_names(&assign.right)?,
+                        _ => apply_option(&mut options, &assign)?,
+                    }
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "expected `option = value` settings followed by constructor and runtime arrays",
+                    ))
+                }
+            }
+        }
+
+        let mut programs = programs.into_iter();
+        match (programs.next(), programs.next()) {
+            (Some(constructor), Some(runtime)) => {
+                Ok(Self { options, constructor_args, immutables, constructor, runtime })
+            }
+            _ => Err(input.error("expected constructor and runtime arrays")),
+        }
+    }
+}
+
+fn option_name(assign: &ExprAssign) -> syn::Result<String> {
+    match &*assign.left {
+        Expr::Path(ExprPath { path, .. }) if path.get_ident().is_some() => {
+            Ok(path.get_ident().unwrap().to_string())
+        }
+        other => Err(syn::Error::new_spanned(other, "expected an option name")),
+    }
+}
+
+fn apply_option(options: &mut MacroOptions, assign: &ExprAssign) -> syn::Result<()> {
+    let key = option_name(assign)?;
+
+    match key.as_str() {
+        "stack_check" => options.stack_check = parse_bool(&assign.right)?,
+        "verify" => options.verify = parse_bool(&assign.right)?,
+        "optimize" => options.optimize = parse_objective(&assign.right)?,
+        "fork" => options.fork = parse_fork(&assign.right)?,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &assign.left,
+                format!("unknown option `{}`", key),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn parse_objective(expr: &Expr) -> syn::Result<Option<Objective>> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => match s.value().as_str() {
+            "size" => Ok(Some(Objective::Size)),
+            "gas" => Ok(Some(Objective::Gas)),
+            _ => Err(syn::Error::new_spanned(s, "expected \"size\" or \"gas\"")),
+        },
+        other => Ok(parse_bool(other)?.then_some(Objective::Size)),
+    }
+}
+
+fn parse_fork(expr: &Expr) -> syn::Result<Fork> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => {
+            s.value().parse().map_err(|e: String| syn::Error::new_spanned(s, e))
+        }
+        other => Err(syn::Error::new_spanned(other, "expected a fork name such as \"cancun\"")),
+    }
+}
+
+fn parse_names(expr: &Expr) -> syn::Result<Vec<String>> {
+    let names = match expr {
+        Expr::Array(arr) => arr.elems.iter().map(|e| match e {
+            Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Some(s.value()),
+            _ => None,
+        }).collect(),
+        _ => None,
+    };
+    names.ok_or_else(|| syn::Error::new_spanned(expr, "expected an array of names such as [\"owner\"]"))
+}
+
+fn parse_bool(expr: &Expr) -> syn::Result<bool> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Bool(b), .. }) => Ok(b.value),
+        other => Err(syn::Error::new_spanned(other, "expected `true` or `false`")),
+    }
+}
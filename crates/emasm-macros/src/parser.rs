@@ -1,16 +1,57 @@
-use syn::{Expr, ExprLit, ExprReference, Lit, punctuated::Punctuated, Token};
+use emasm_common::{
+    abi::keccak_literal,
+    contract::parse_immutable,
+    data::{parse_align, parse_data},
+    dispatch::Dispatcher,
+    jumptable::table_jump,
+    offset::{is_offset, OffsetExpr},
+    AsmElement,
+};
+use std::{cell::RefCell, path::PathBuf};
+use syn::{Expr, ExprArray, ExprLit, ExprReference, Lit, punctuated::Punctuated, Token};
 
 #[derive(Debug, Clone)]
 pub enum AsmToken {
     Opcode(String),
     Literal(u128),
     HexLiteral(Vec<u8>),
-    Label(String),
     Segment(String, Vec<AsmToken>),
     BytesSegment(String, Vec<u8>),
     BytesPtr(String),
     BytesSize(String),
     Placeholder(usize),
+    Let(Vec<String>),
+    DupSlot(String),
+    SwapSlot(String),
+    JumpTable(String, Vec<String>),
+    Immutable(String, usize),
+    /// Program assembled on its own and embedded as a bytes segment, written
+    /// `["child:name", [...]]` so that it cannot be mistaken for hex data
+    Child(String, Vec<AsmToken>),
+    /// Bytes segment holding the value of a Rust expression, e.g. another `evm_asm!` output
+    BytesExpr(String, Expr),
+    Align(usize),
+    Offset(OffsetExpr),
+    Anchor(String),
+}
+
+thread_local! {
+    /// Files read by `"file:..."` data while expanding the current macro
+    static INCLUDED_FILES: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Files read since the last call, so the expansion can make Cargo track them
+pub fn take_included_files() -> Vec<PathBuf> {
+    INCLUDED_FILES.with(|files| files.take())
+}
+
+/// Bytes of a data directive; files are relative to the crate being compiled, like `include_bytes!`
+fn bytes_data(directive: &str) -> Result<Vec<u8>, String> {
+    let base_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+    if let Some(path) = directive.strip_prefix("file:") {
+        INCLUDED_FILES.with(|files| files.borrow_mut().push(base_dir.join(path)));
+    }
+    parse_data(directive, &base_dir).map_err(|e| e.to_string())
 }
 
 pub fn parse_asm_elements(
@@ -19,31 +60,151 @@ pub fn parse_asm_elements(
     let mut result = Vec::new();
     
     for expr in exprs {
-        result.push(parse_single_element(expr)?);
+        if let Some(dispatcher) = parse_dispatch(expr) {
+            // Selectors are hashed here, when the macro expands
+            let elements = dispatcher?.lower().map_err(|e| e.to_string())?;
+            result.extend(elements.into_iter().map(element_to_token));
+            continue;
+        }
+        match expr {
+            Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) if s.value().starts_with("jumptable:") => {
+                // Expands to the sequence that jumps to entry i of the table
+                let value = s.value();
+                let name = value.strip_prefix("jumptable:").unwrap();
+                result.extend(table_jump(name).into_iter().map(element_to_token));
+            }
+            _ => result.push(parse_single_element(expr)?),
+        }
     }
     
     Ok(result)
 }
 
+/// Token for an element produced by a built-in expansion
+fn element_to_token(elem: AsmElement) -> AsmToken {
+    match elem {
+        AsmElement::Opcode(name) => AsmToken::Opcode(name),
+        // Resolved back into a label reference once all labels are known
+        AsmElement::Label(name) => AsmToken::Opcode(name),
+        AsmElement::Literal(data) => AsmToken::HexLiteral(data),
+        AsmElement::BytesPtr(name) => AsmToken::BytesPtr(name),
+        AsmElement::Segment(name, inner) => {
+            AsmToken::Segment(name, inner.into_iter().map(element_to_token).collect())
+        }
+        AsmElement::JumpTable(name, targets) => AsmToken::JumpTable(name, targets),
+        other => unreachable!("expansions do not produce {:?}", other),
+    }
+}
+
+/// Dispatchers defined anywhere in the program, in source order
+pub fn collect_dispatchers(exprs: &Punctuated<Expr, Token![,]>) -> Result<Vec<Dispatcher>, String> {
+    let mut dispatchers = Vec::new();
+    for expr in exprs {
+        match (parse_dispatch(expr), expr) {
+            (Some(dispatcher), _) => dispatchers.push(dispatcher?),
+            // Child programs are separate contracts with ABIs of their own
+            (None, Expr::Array(arr)) if !is_child_program(arr) => {
+                if let Some(Expr::Array(inner)) = arr.elems.iter().nth(1) {
+                    dispatchers.extend(collect_dispatchers(&inner.elems)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(dispatchers)
+}
+
+fn is_child_program(arr: &ExprArray) -> bool {
+    matches!(arr.elems.first(), Some(Expr::Lit(ExprLit { lit: Lit::Str(s), .. })) if s.value().starts_with("child:"))
+}
+
+/// `["dispatch", [["sig", "label"], ...], ["fallback", "label"], ["mode", "binary"]]`,
+/// or None if `expr` is not a dispatcher
+fn parse_dispatch(expr: &Expr) -> Option<Result<Dispatcher, String>> {
+    let Expr::Array(arr) = expr else { return None };
+    let mut elems = arr.elems.iter();
+    match elems.next()? {
+        Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) if s.value() == "dispatch" => {}
+        _ => return None,
+    }
+    // A segment named "dispatch" holds code rather than string pairs
+    let functions = match elems.next()? {
+        Expr::Array(functions) => string_pairs(functions)?,
+        _ => return None,
+    };
+    let options: Option<Vec<_>> = elems.map(|e| match e {
+        Expr::Array(pair) => string_pair(pair),
+        _ => None,
+    }).collect();
+    Some(match options {
+        Some(options) => Dispatcher::from_parts(functions, options).map_err(|e| e.to_string()),
+        None => Err("Dispatcher options must be [\"key\", \"value\"] pairs".to_string()),
+    })
+}
+
+fn string_pairs(arr: &ExprArray) -> Option<Vec<(String, String)>> {
+    arr.elems.iter().map(|e| match e {
+        Expr::Array(pair) => string_pair(pair),
+        _ => None,
+    }).collect()
+}
+
+fn string_pair(arr: &ExprArray) -> Option<(String, String)> {
+    match parse_slot_names(arr.elems.iter())?.as_slice() {
+        [key, value] => Some((key.clone(), value.clone())),
+        _ => None,
+    }
+}
+
 fn parse_single_element(expr: &Expr) -> Result<AsmToken, String> {
     match expr {
         Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => {
             let value = s.value();
-            
-            if value.starts_with("bytes:") {
-                if value.ends_with(":ptr") {
-                    let label = value.strip_prefix("bytes:")
-                        .and_then(|s| s.strip_suffix(":ptr"))
-                        .unwrap()
-                        .to_string();
-                    return Ok(AsmToken::BytesPtr(label));
-                } else if value.ends_with(":size") {
-                    let label = value.strip_prefix("bytes:")
-                        .and_then(|s| s.strip_suffix(":size"))
-                        .unwrap()
-                        .to_string();
-                    return Ok(AsmToken::BytesSize(label));
+
+            // Hashed now, so the program carries the selector or topic as a literal
+            if let Some(hash) = keccak_literal(&value) {
+                return hash.map(AsmToken::HexLiteral).map_err(|e| e.to_string());
+            }
+
+            if let Some(name) = value.strip_prefix("anchor:") {
+                return Ok(AsmToken::Anchor(name.to_string()));
+            }
+
+            // Parsed once labels are known, since a label may look like an expression
+            if is_offset(&value) {
+                return Ok(AsmToken::Opcode(value));
+            }
+
+            if let Some(rest) = value.strip_prefix("bytes:") {
+                if let Some(label) = rest.strip_suffix(":ptr") {
+                    return Ok(AsmToken::BytesPtr(label.to_string()));
                 }
+                if let Some(label) = rest.strip_suffix(":size") {
+                    return Ok(AsmToken::BytesSize(label.to_string()));
+                }
+                // A bare bytes segment name refers to its address
+                return Ok(AsmToken::BytesPtr(rest.to_string()));
+            }
+
+            if let Some(immutable) = parse_immutable(&value) {
+                return match immutable.map_err(|e| e.to_string())? {
+                    AsmElement::Immutable(name, width) => Ok(AsmToken::Immutable(name, width)),
+                    other => unreachable!("immutables parse to {:?}", other),
+                };
+            }
+
+            if let Some(align) = parse_align(&value) {
+                return match align.map_err(|e| e.to_string())? {
+                    AsmElement::Align(alignment) => Ok(AsmToken::Align(alignment)),
+                    other => unreachable!("alignments parse to {:?}", other),
+                };
+            }
+
+            if let Some(name) = value.strip_prefix("dup:") {
+                return Ok(AsmToken::DupSlot(name.to_string()));
+            }
+            if let Some(name) = value.strip_prefix("swap:") {
+                return Ok(AsmToken::SwapSlot(name.to_string()));
             }
             
             Ok(AsmToken::Opcode(value))
@@ -83,13 +244,57 @@ fn parse_single_element(expr: &Expr) -> Result<AsmToken, String> {
             if let Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) = first {
                 let label = s.value();
                 
-                if label.starts_with("bytes:") {
-                    let second = &arr.elems[1];
-                    if let Expr::Lit(ExprLit { lit: Lit::Str(hex_str), .. }) = second {
-                        let hex_data = parse_hex_string(&hex_str.value())?;
-                        return Ok(AsmToken::BytesSegment(label, hex_data));
+                if label == "let" {
+                    if let Some(names) = parse_slot_names(arr.elems.iter().skip(1)) {
+                        return Ok(AsmToken::Let(names));
+                    }
+                }
+
+                // A segment may still be called "jumptable"; tables have a name string second
+                if let (true, Expr::Lit(ExprLit { lit: Lit::Str(name), .. })) = (label == "jumptable", &arr.elems[1]) {
+                    let targets = match arr.elems.iter().nth(2) {
+                        Some(Expr::Array(targets)) if arr.elems.len() == 3 => {
+                            parse_slot_names(targets.elems.iter())
+                        }
+                        _ => None,
+                    };
+                    return targets
+                        .map(|targets| AsmToken::JumpTable(name.value(), targets))
+                        .ok_or_else(|| "Jump table must be [\"jumptable\", \"name\", [\"label\", ...]]".to_string());
+                }
+                
+                if let Some(name) = label.strip_prefix("child:") {
+                    return match arr.elems.iter().skip(1).collect::<Vec<_>>().as_slice() {
+                        [Expr::Array(program)] => {
+                            Ok(AsmToken::Child(name.to_string(), parse_asm_elements(&program.elems)?))
+                        }
+                        _ => Err("Child program must be [\"child:name\", [...]]".to_string()),
+                    };
+                }
+
+                if let Some(name) = label.strip_prefix("bytes:") {
+                    let parts: Vec<&Expr> = arr.elems.iter().skip(1).collect();
+                    let directives = match parts.as_slice() {
+                        [Expr::Array(data)] => match data.elems.iter().collect::<Vec<_>>().as_slice() {
+                            [Expr::Lit(ExprLit { lit: Lit::Str(hex_str), .. })] => vec![hex_str.value()],
+                            _ => {
+                                return Err("Bytes segment array must hold one hex string; embed programs \
+                                    with [\"child:name\", [...]]".to_string());
+                            }
+                        },
+                        [expr] if !matches!(expr, Expr::Lit(_)) => {
+                            return Ok(AsmToken::BytesExpr(name.to_string(), (*expr).clone()));
+                        }
+                        parts => parts.iter().map(|part| match part {
+                            Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Ok(s.value()),
+                            _ => Err("Bytes segment data must be strings such as \"0x..\" or \"utf8:...\"".to_string()),
+                        }).collect::<Result<_, _>>()?,
+                    };
+                    let mut data = Vec::new();
+                    for directive in &directives {
+                        data.extend(bytes_data(directive)?);
                     }
-                    return Err("Bytes segment must have hex string as second element".to_string());
+                    return Ok(AsmToken::BytesSegment(name.to_string(), data));
                 }
                 
                 let second = &arr.elems[1];
@@ -117,14 +322,24 @@ fn parse_single_element(expr: &Expr) -> Result<AsmToken, String> {
             Err("Invalid placeholder syntax, expected &[index]".to_string())
         }
         
-        _ => Err(format!("Unsupported expression type in assembly")),
+        _ => Err("Unsupported expression type in assembly".to_string()),
     }
 }
 
+/// Names in `["let", "a", "b", ...]` or a jump table, or None if any of them is not a string
+fn parse_slot_names<'a>(exprs: impl Iterator<Item = &'a Expr>) -> Option<Vec<String>> {
+    exprs
+        .map(|expr| match expr {
+            Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Some(s.value()),
+            _ => None,
+        })
+        .collect()
+}
+
 fn parse_hex_string(s: &str) -> Result<Vec<u8>, String> {
     let s = s.strip_prefix("0x").unwrap_or(s);
     
-    let s = if s.len() % 2 != 0 {
+    let s = if !s.len().is_multiple_of(2) {
         format!("0{}", s)
     } else {
         s.to_string()
@@ -1,5 +1,8 @@
 pub use emasm_common::{Assembler, AsmElement, AssemblerError, EVMEncodable};
-pub use emasm_macros::{evm_asm, evm_asm_interpolator};
+pub use emasm_macros::{evm_abi, evm_asm, evm_asm_interpolator, evm_contract};
+
+#[cfg(feature = "testing")]
+pub mod testing;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,392 @@
+//! revm-backed harness for running assembled programs in tests, behind the
+//! `testing` feature.
+//!
+//! ```ignore
+//! use emasm::testing::{Runner, Tx};
+//!
+//! let mut runner = Runner::new();
+//! let counter = runner.deploy(evm_contract!([], [/* runtime */]));
+//! let outcome = runner.call(counter, Tx::default().data(calldata));
+//! assert!(outcome.is_success(), "{}", outcome);
+//! ```
+//!
+//! State persists between transactions, so contracts deployed or installed
+//! earlier can call each other. [`trace`] records transactions step by step,
+//! and [`snapshot`] keeps gas use of named scenarios in a checked-in file.
+
+use emasm_common::abi::error_selector;
+use revm::{
+    db::InMemoryDB,
+    inspector_handle_register,
+    primitives::{
+        AccountInfo, Address, BlockEnv, Bytecode, Bytes, ExecutionResult, Log, Output, SpecId, TxKind, U256,
+    },
+    DatabaseCommit, DatabaseRef, Evm,
+};
+use std::fmt;
+
+pub mod snapshot;
+pub mod trace;
+
+pub use revm;
+pub use trace::{Trace, Tracer};
+
+/// Account that sends transactions unless a [`Tx`] names another.
+pub const DEFAULT_CALLER: Address = Address::repeat_byte(0x41);
+
+/// In-memory chain that deploys and calls contracts, one committed transaction at a time.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    db: InMemoryDB,
+    block: BlockEnv,
+    spec: SpecId,
+    caller: Address,
+}
+
+/// Optional settings of a transaction.
+#[derive(Debug, Clone, Default)]
+pub struct Tx {
+    pub from: Option<Address>,
+    pub data: Bytes,
+    pub value: U256,
+    pub gas_limit: Option<u64>,
+}
+
+impl Tx {
+    pub fn from(mut self, caller: Address) -> Self {
+        self.from = Some(caller);
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    pub fn value(mut self, value: U256) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+}
+
+/// How a transaction ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Revert,
+    /// Exceptional halt, e.g. out of gas or an invalid jump.
+    Halt(String),
+}
+
+/// A storage slot a transaction changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageChange {
+    pub address: Address,
+    pub slot: U256,
+    pub old: U256,
+    pub new: U256,
+}
+
+/// Everything observable about a transaction.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub status: Status,
+    /// Return data, or revert data if the transaction reverted.
+    pub output: Bytes,
+    pub gas_used: u64,
+    pub logs: Vec<Log>,
+    /// Changed slots, sorted by address and slot.
+    pub storage_changes: Vec<StorageChange>,
+    /// Address of the contract a successful deployment created.
+    pub created: Option<Address>,
+}
+
+/// Revert data, decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revert {
+    /// `revert(0, 0)`.
+    Empty,
+    /// `Error(string)`, as written by Solidity's `require` and `revert("...")`.
+    Message(String),
+    /// `Panic(uint256)`.
+    Panic(U256),
+    /// A custom error: its selector and ABI-encoded arguments.
+    Custom { selector: [u8; 4], args: Bytes },
+    /// Data too short to start with a selector.
+    Raw(Bytes),
+}
+
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+impl Runner {
+    pub fn new() -> Self {
+        Self {
+            db: InMemoryDB::default(),
+            block: BlockEnv::default(),
+            spec: SpecId::LATEST,
+            caller: DEFAULT_CALLER,
+        }
+    }
+
+    /// Hardfork rules to execute under, the latest by default.
+    pub fn with_spec(mut self, spec: SpecId) -> Self {
+        self.spec = spec;
+        self
+    }
+
+    /// Sender of transactions that do not set [`Tx::from`].
+    pub fn set_caller(&mut self, caller: Address) {
+        self.caller = caller;
+    }
+
+    /// Block number, timestamp, coinbase, base fee and so on of every transaction.
+    pub fn block(&mut self) -> &mut BlockEnv {
+        &mut self.block
+    }
+
+    /// Put runtime code at `address` without running any initcode.
+    pub fn install(&mut self, address: Address, code: impl Into<Bytes>) {
+        let mut info = self.account(address);
+        let bytecode = Bytecode::new_raw(code.into());
+        info.code_hash = bytecode.hash_slow();
+        info.code = Some(bytecode);
+        self.db.insert_account_info(address, info);
+    }
+
+    pub fn set_balance(&mut self, address: Address, balance: U256) {
+        let mut info = self.account(address);
+        info.balance = balance;
+        self.db.insert_account_info(address, info);
+    }
+
+    pub fn set_storage(&mut self, address: Address, slot: U256, value: U256) {
+        self.db
+            .insert_account_storage(address, slot, value)
+            .expect("the in-memory database is infallible");
+    }
+
+    pub fn balance(&self, address: Address) -> U256 {
+        self.account(address).balance
+    }
+
+    pub fn storage(&self, address: Address, slot: U256) -> U256 {
+        self.db.storage_ref(address, slot).expect("the in-memory database is infallible")
+    }
+
+    /// Deployed code at `address`, empty for accounts without code.
+    pub fn code(&self, address: Address) -> Bytes {
+        self.account(address).code.map(|code| code.original_bytes()).unwrap_or_default()
+    }
+
+    /// Run `initcode` from the default caller and return the new contract's address.
+    ///
+    /// # Panics
+    ///
+    /// If the deployment fails; use [`Runner::create`] to inspect failures.
+    pub fn deploy(&mut self, initcode: impl Into<Bytes>) -> Address {
+        let outcome = self.create(initcode, Tx::default());
+        match outcome.created {
+            Some(address) => address,
+            None => panic!("Deployment failed: {}", outcome),
+        }
+    }
+
+    /// Run `initcode` in a contract creation transaction; constructor
+    /// arguments are appended to it.
+    pub fn create(&mut self, initcode: impl Into<Bytes>, tx: Tx) -> Outcome {
+        let tx = Tx { data: initcode.into(), ..tx };
+        self.transact(TxKind::Create, tx, None)
+    }
+
+    pub fn call(&mut self, to: Address, tx: Tx) -> Outcome {
+        self.transact(TxKind::Call(to), tx, None)
+    }
+
+    /// [`Runner::create`], recording every step into `tracer`.
+    pub fn trace_create(&mut self, initcode: impl Into<Bytes>, tx: Tx, tracer: &mut Tracer) -> Outcome {
+        let tx = Tx { data: initcode.into(), ..tx };
+        self.transact(TxKind::Create, tx, Some(tracer))
+    }
+
+    /// [`Runner::call`], recording every step into `tracer`.
+    pub fn trace_call(&mut self, to: Address, tx: Tx, tracer: &mut Tracer) -> Outcome {
+        self.transact(TxKind::Call(to), tx, Some(tracer))
+    }
+
+    fn transact(&mut self, kind: TxKind, tx: Tx, tracer: Option<&mut Tracer>) -> Outcome {
+        let caller = tx.from.unwrap_or(self.caller);
+        let block = self.block.clone();
+        let builder = Evm::builder()
+            .with_db(&mut self.db)
+            .with_spec_id(self.spec)
+            .modify_block_env(|env| *env = block)
+            .modify_tx_env(|env| {
+                env.caller = caller;
+                env.transact_to = kind;
+                env.data = tx.data;
+                env.value = tx.value;
+                if let Some(gas_limit) = tx.gas_limit {
+                    env.gas_limit = gas_limit;
+                }
+            });
+        let result = match tracer {
+            Some(tracer) => {
+                let mut evm = builder
+                    .with_external_context(std::mem::take(tracer))
+                    .append_handler_register(inspector_handle_register)
+                    .build();
+                let result = evm.transact();
+                *tracer = std::mem::take(&mut evm.context.external);
+                result
+            }
+            None => builder.build().transact(),
+        };
+        let result = result.unwrap_or_else(|e| panic!("Invalid transaction: {:?}", e));
+
+        let mut storage_changes: Vec<StorageChange> = result
+            .state
+            .iter()
+            .flat_map(|(address, account)| {
+                account.storage.iter().filter(|(_, slot)| slot.is_changed()).map(|(key, slot)| StorageChange {
+                    address: *address,
+                    slot: *key,
+                    old: slot.original_value,
+                    new: slot.present_value,
+                })
+            })
+            .collect();
+        storage_changes.sort_by_key(|change| (change.address, change.slot));
+        self.db.commit(result.state);
+
+        let gas_used = result.result.gas_used();
+        match result.result {
+            ExecutionResult::Success { output, logs, .. } => {
+                let (output, created) = match output {
+                    Output::Call(data) => (data, None),
+                    Output::Create(data, address) => (data, address),
+                };
+                Outcome { status: Status::Success, output, gas_used, logs, storage_changes, created }
+            }
+            ExecutionResult::Revert { output, .. } => Outcome {
+                status: Status::Revert,
+                output,
+                gas_used,
+                logs: Vec::new(),
+                storage_changes,
+                created: None,
+            },
+            ExecutionResult::Halt { reason, .. } => Outcome {
+                status: Status::Halt(format!("{:?}", reason)),
+                output: Bytes::new(),
+                gas_used,
+                logs: Vec::new(),
+                storage_changes,
+                created: None,
+            },
+        }
+    }
+
+    fn account(&self, address: Address) -> AccountInfo {
+        self.db
+            .basic_ref(address)
+            .expect("the in-memory database is infallible")
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        self.status == Status::Success
+    }
+
+    /// Decoded revert data, or None unless the transaction reverted.
+    pub fn revert(&self) -> Option<Revert> {
+        (self.status == Status::Revert).then(|| Revert::decode(&self.output))
+    }
+
+    /// Return data as a word, e.g. the `uint256` a call returned.
+    ///
+    /// # Panics
+    ///
+    /// If the output is longer than 32 bytes.
+    pub fn word(&self) -> U256 {
+        U256::from_be_slice(&self.output)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            Status::Success => write!(f, "success ({} gas): {}", self.gas_used, self.output),
+            Status::Revert => {
+                write!(f, "reverted ({} gas): {}", self.gas_used, Revert::decode(&self.output))
+            }
+            Status::Halt(reason) => write!(f, "halted ({} gas): {}", self.gas_used, reason),
+        }
+    }
+}
+
+impl Revert {
+    pub fn decode(data: &[u8]) -> Self {
+        let Some((selector, args)) = data.split_first_chunk::<4>() else {
+            return if data.is_empty() { Revert::Empty } else { Revert::Raw(Bytes::copy_from_slice(data)) };
+        };
+        match *selector {
+            ERROR_SELECTOR => {
+                if let Some(message) = decode_string(args) {
+                    return Revert::Message(message);
+                }
+            }
+            PANIC_SELECTOR if args.len() == 32 => return Revert::Panic(U256::from_be_slice(args)),
+            _ => {}
+        }
+        Revert::Custom { selector: *selector, args: Bytes::copy_from_slice(args) }
+    }
+
+    /// Whether this is the custom error with `signature`, e.g. `"Unauthorized(address)"`.
+    pub fn is(&self, signature: &str) -> bool {
+        match self {
+            Revert::Custom { selector, .. } => error_selector(signature).is_ok_and(|s| s == *selector),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Revert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revert::Empty => write!(f, "no data"),
+            Revert::Message(message) => write!(f, "Error({:?})", message),
+            Revert::Panic(code) => write!(f, "Panic({:#x})", code),
+            Revert::Custom { selector, args } => {
+                write!(f, "custom error {}: {}", Bytes::copy_from_slice(selector), args)
+            }
+            Revert::Raw(data) => write!(f, "{}", data),
+        }
+    }
+}
+
+/// ABI-encoded `string` arguments: an offset, a length and the bytes.
+fn decode_string(args: &[u8]) -> Option<String> {
+    let word = |at: usize| -> Option<usize> {
+        let word = U256::from_be_slice(args.get(at..at.checked_add(32)?)?);
+        word.try_into().ok()
+    };
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let bytes = args.get(start..start.checked_add(len)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
@@ -0,0 +1,280 @@
+//! Gas snapshots: named scenarios whose gas use and code size are recorded
+//! in a checked-in file, so changes show up in review.
+//!
+//! ```ignore
+//! use emasm::testing::snapshot::{Scenario, Snapshot};
+//!
+//! let snapshot = Snapshot::run(&[
+//!     Scenario::call("transfer", runtime, Tx::default().data(calldata)),
+//!     Scenario::deploy("deploy", initcode, Tx::default()),
+//! ])?;
+//! let comparison = snapshot.compare(&Snapshot::read(".gas-snapshot")?, 0.0);
+//! assert!(comparison.is_ok(), "{}", comparison);
+//! snapshot.write(".gas-snapshot")?;
+//! ```
+//!
+//! The file has one `name (gas: 21124, size: 31)` line per scenario, sorted
+//! by name.
+
+use super::{Outcome, Runner, Tx};
+use revm::primitives::{Address, Bytes};
+use std::{collections::BTreeMap, fmt, io, path::Path};
+
+/// Account a called scenario's code is installed at.
+const SCENARIO_ADDRESS: Address = Address::repeat_byte(0x42);
+
+/// A transaction to measure, run on a fresh [`Runner`].
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    /// Runtime code to call, or initcode to deploy.
+    pub code: Bytes,
+    pub deploy: bool,
+    pub tx: Tx,
+}
+
+/// What a snapshot records per scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    pub gas: u64,
+    /// Size of the scenario's code in bytes.
+    pub size: usize,
+}
+
+/// Measurements by scenario name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub entries: BTreeMap<String, Measurement>,
+}
+
+/// A scenario whose transaction did not succeed.
+#[derive(Debug, Clone)]
+pub struct ScenarioFailed {
+    pub name: String,
+    pub outcome: Box<Outcome>,
+}
+
+/// A snapshot file line that is not `name (gas: N, size: N)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSnapshotError {
+    /// 1-based line number.
+    pub line: usize,
+}
+
+/// One scenario's measurements before and after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff {
+    pub name: String,
+    /// None for a scenario the baseline does not have.
+    pub old: Option<Measurement>,
+    /// None for a scenario that was removed.
+    pub new: Option<Measurement>,
+}
+
+/// A snapshot compared against its baseline.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// Every scenario of both snapshots, by name.
+    pub diffs: Vec<Diff>,
+    /// Gas increase, in percent, above which a scenario regressed.
+    pub tolerance: f64,
+}
+
+impl Scenario {
+    /// Install `runtime` and call it with `tx`.
+    pub fn call(name: impl Into<String>, runtime: impl Into<Bytes>, tx: Tx) -> Self {
+        Self { name: name.into(), code: runtime.into(), deploy: false, tx }
+    }
+
+    /// Deploy `initcode`, with `tx.data` appended as constructor arguments.
+    pub fn deploy(name: impl Into<String>, initcode: impl Into<Bytes>, tx: Tx) -> Self {
+        Self { name: name.into(), code: initcode.into(), deploy: true, tx }
+    }
+
+    pub fn run(&self) -> Outcome {
+        let mut runner = Runner::new();
+        let sender = self.tx.from.unwrap_or(super::DEFAULT_CALLER);
+        runner.set_balance(sender, self.tx.value);
+        if self.deploy {
+            let mut initcode = self.code.to_vec();
+            initcode.extend_from_slice(&self.tx.data);
+            runner.create(initcode, self.tx.clone())
+        } else {
+            runner.install(SCENARIO_ADDRESS, self.code.clone());
+            runner.call(SCENARIO_ADDRESS, self.tx.clone())
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run every scenario and record its measurements.
+    pub fn run(scenarios: &[Scenario]) -> Result<Self, ScenarioFailed> {
+        let mut snapshot = Self::new();
+        for scenario in scenarios {
+            let outcome = scenario.run();
+            if !outcome.is_success() {
+                return Err(ScenarioFailed { name: scenario.name.clone(), outcome: Box::new(outcome) });
+            }
+            snapshot.record(&scenario.name, Measurement { gas: outcome.gas_used, size: scenario.code.len() });
+        }
+        Ok(snapshot)
+    }
+
+    pub fn record(&mut self, name: &str, measurement: Measurement) {
+        self.entries.insert(name.to_string(), measurement);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Measurement> {
+        self.entries.get(name).copied()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseSnapshotError> {
+        let mut snapshot = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (name, measurement) = parse_line(line).ok_or(ParseSnapshotError { line: i + 1 })?;
+            snapshot.record(name, measurement);
+        }
+        Ok(snapshot)
+    }
+
+    /// The snapshot file at `path`, or an empty snapshot if there is none yet.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Compare against `baseline`; a scenario regresses when its gas grows by
+    /// more than `tolerance` percent.
+    pub fn compare(&self, baseline: &Snapshot, tolerance: f64) -> Comparison {
+        let mut names: Vec<&String> = self.entries.keys().chain(baseline.entries.keys()).collect();
+        names.sort();
+        names.dedup();
+        let diffs = names
+            .into_iter()
+            .map(|name| Diff { name: name.clone(), old: baseline.get(name), new: self.get(name) })
+            .collect();
+        Comparison { diffs, tolerance }
+    }
+}
+
+/// `name (gas: N, size: N)`
+fn parse_line(line: &str) -> Option<(&str, Measurement)> {
+    let (name, rest) = line.rsplit_once(" (gas: ")?;
+    let (gas, rest) = rest.split_once(", size: ")?;
+    let size = rest.strip_suffix(')')?;
+    Some((name, Measurement { gas: gas.parse().ok()?, size: size.parse().ok()? }))
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, measurement) in &self.entries {
+            writeln!(f, "{} (gas: {}, size: {})", name, measurement.gas, measurement.size)?;
+        }
+        Ok(())
+    }
+}
+
+impl Diff {
+    pub fn is_changed(&self) -> bool {
+        self.old != self.new
+    }
+
+    /// Gas change in gas units, for scenarios in both snapshots.
+    pub fn gas_delta(&self) -> Option<i128> {
+        Some(self.new?.gas as i128 - self.old?.gas as i128)
+    }
+
+    /// Whether gas grew by more than `tolerance` percent.
+    pub fn is_regression(&self, tolerance: f64) -> bool {
+        match (self.old, self.new) {
+            (Some(old), Some(new)) if new.gas > old.gas => {
+                (new.gas - old.gas) as f64 * 100.0 > tolerance * old.gas as f64
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.old, self.new) {
+            (None, Some(new)) => write!(f, "{}: new (gas: {}, size: {})", self.name, new.gas, new.size),
+            (Some(_), None) => write!(f, "{}: removed", self.name),
+            (Some(old), Some(new)) => {
+                write!(f, "{}: gas {} -> {} ({:+}", self.name, old.gas, new.gas, new.gas as i128 - old.gas as i128)?;
+                if old.gas > 0 {
+                    let percent = (new.gas as f64 - old.gas as f64) * 100.0 / old.gas as f64;
+                    write!(f, ", {:+.2}%", percent)?;
+                }
+                write!(f, "), size {} -> {} ({:+})", old.size, new.size, new.size as i128 - old.size as i128)
+            }
+            (None, None) => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl Comparison {
+    /// Scenarios whose gas grew past the tolerance.
+    pub fn regressions(&self) -> impl Iterator<Item = &Diff> {
+        self.diffs.iter().filter(|diff| diff.is_regression(self.tolerance))
+    }
+
+    pub fn changes(&self) -> impl Iterator<Item = &Diff> {
+        self.diffs.iter().filter(|diff| diff.is_changed())
+    }
+
+    /// Whether no scenario regressed.
+    pub fn is_ok(&self) -> bool {
+        self.regressions().next().is_none()
+    }
+}
+
+/// Changed scenarios, one per line, regressions marked, then a summary.
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diff in self.changes() {
+            let marker = if diff.is_regression(self.tolerance) { "  [regression]" } else { "" };
+            writeln!(f, "{}{}", diff, marker)?;
+        }
+        let changed = self.changes().count();
+        let regressed = self.regressions().count();
+        write!(
+            f,
+            "{} scenarios, {} changed, {} regressed over {}%",
+            self.diffs.len(),
+            changed,
+            regressed,
+            self.tolerance
+        )
+    }
+}
+
+impl fmt::Display for ScenarioFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "scenario {} failed: {}", self.name, self.outcome)
+    }
+}
+
+impl std::error::Error for ScenarioFailed {}
+
+impl fmt::Display for ParseSnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: expected `name (gas: N, size: N)`", self.line)
+    }
+}
+
+impl std::error::Error for ParseSnapshotError {}
@@ -0,0 +1,228 @@
+//! Opcode-level execution traces whose steps point back at the assembly.
+//!
+//! ```ignore
+//! use emasm::testing::{trace::Tracer, Runner, Tx};
+//!
+//! let (code, layout) = Assembler::new().assemble_with_layout(&program)?;
+//! let mut tracer = Tracer::new().with_layout(&code, layout);
+//! runner.install(address, code);
+//! runner.trace_call(address, Tx::default(), &mut tracer);
+//! println!("{}", tracer.trace());
+//! ```
+//!
+//! which prints one line per executed instruction, e.g.
+//! `copy_loop+3: DUP2 [0x40, 0x1000, 0x100]`, with the stack top first.
+
+use emasm_common::{layout::Layout, opcodes::Opcode, ElementPath};
+use revm::{
+    interpreter::Interpreter,
+    primitives::{keccak256, Address, Bytes, B256, U256},
+    Database, EvmContext, Inspector,
+};
+use serde_json::{json, Value};
+use std::{collections::HashMap, fmt};
+
+/// Inspector that records every step and maps PCs to element paths through
+/// the layouts it was given.
+#[derive(Debug, Clone, Default)]
+pub struct Tracer {
+    /// Layouts by the hash of the code they were assembled into.
+    layouts: HashMap<B256, Layout>,
+    /// Hash of the code running at each call depth.
+    frames: Vec<B256>,
+    /// Memory region the step in progress writes to.
+    pending_write: Option<(usize, usize)>,
+    trace: Trace,
+}
+
+/// Steps in execution order, across every call frame of a transaction.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Call depth, 0 for the frame the transaction started.
+    pub depth: usize,
+    /// Account whose code runs; for initcode, the account being created.
+    pub address: Address,
+    pub pc: usize,
+    pub opcode: u8,
+    /// Element the PC belongs to, if the code's layout is known.
+    pub path: Option<ElementPath>,
+    /// Stack before the step, top first.
+    pub stack: Vec<U256>,
+    /// Gas left before the step.
+    pub gas_remaining: u64,
+    /// Gas the step consumed; for calls and creates this includes the gas
+    /// handed to the new frame.
+    pub gas_cost: u64,
+    pub memory: Option<MemoryWrite>,
+}
+
+/// Bytes a step wrote to memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub offset: usize,
+    pub data: Bytes,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map steps through `code` back to `layout`. The code is matched by its
+    /// contents, so one layout covers every account the code runs at.
+    pub fn with_layout(mut self, code: &[u8], layout: Layout) -> Self {
+        self.layouts.insert(keccak256(code), layout);
+        self
+    }
+
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Take the steps recorded so far, leaving the tracer ready for another transaction.
+    pub fn take_trace(&mut self) -> Trace {
+        std::mem::take(&mut self.trace)
+    }
+}
+
+impl<DB: Database> Inspector<DB> for Tracer {
+    fn initialize_interp(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let depth = frame_depth(context);
+        self.frames.truncate(depth);
+        self.frames.push(keccak256(interp.contract.bytecode.original_byte_slice()));
+    }
+
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let depth = frame_depth(context);
+        let pc = interp.program_counter();
+        let opcode = interp.current_opcode();
+        let stack: Vec<U256> = interp.stack.data().iter().rev().copied().collect();
+        let path = self
+            .frames
+            .get(depth)
+            .and_then(|hash| self.layouts.get(hash))
+            .and_then(|layout| layout.entry_at(pc))
+            .map(|entry| entry.path.clone());
+
+        self.pending_write = memory_write(opcode, &stack);
+        self.trace.steps.push(Step {
+            depth,
+            address: interp.contract.target_address,
+            pc,
+            opcode,
+            path,
+            stack,
+            gas_remaining: interp.gas.remaining(),
+            gas_cost: 0,
+            memory: None,
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Some(step) = self.trace.steps.last_mut() else { return };
+        step.gas_cost = step.gas_remaining.saturating_sub(interp.gas.remaining());
+        if let Some((offset, len)) = self.pending_write.take() {
+            let memory = interp.shared_memory.context_memory();
+            step.memory = memory.get(offset..offset + len).map(|data| MemoryWrite {
+                offset,
+                data: Bytes::copy_from_slice(data),
+            });
+        }
+    }
+}
+
+/// Depth of the running frame, counting from 0.
+fn frame_depth<DB: Database>(context: &EvmContext<DB>) -> usize {
+    (context.journaled_state.depth() as usize).saturating_sub(1)
+}
+
+/// Memory region an opcode writes, from its stack arguments (top first).
+/// Calls write their return data only after the new frame returns, so they
+/// are left out.
+fn memory_write(opcode: u8, stack: &[U256]) -> Option<(usize, usize)> {
+    let arg = |i: usize| -> Option<usize> { (*stack.get(i)?).try_into().ok() };
+    let (offset, len) = match Opcode(opcode) {
+        Opcode::MSTORE => (arg(0)?, 32),
+        Opcode::MSTORE8 => (arg(0)?, 1),
+        Opcode::CALLDATACOPY | Opcode::CODECOPY | Opcode::RETURNDATACOPY => (arg(0)?, arg(2)?),
+        Opcode::EXTCODECOPY => (arg(1)?, arg(3)?),
+        _ => return None,
+    };
+    (len > 0 && offset.checked_add(len).is_some()).then_some((offset, len))
+}
+
+impl Step {
+    /// Mnemonic of the opcode, e.g. `DUP2`.
+    pub fn name(&self) -> String {
+        match Opcode(self.opcode).info() {
+            Some(info) => info.name.to_string(),
+            None => format!("INVALID({:#04x})", self.opcode),
+        }
+    }
+
+    /// Element path, or the PC when the code's layout is unknown.
+    pub fn location(&self) -> String {
+        match &self.path {
+            Some(path) => path.to_string(),
+            None => format!("pc {:#x}", self.pc),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "depth": self.depth,
+            "address": self.address.to_string(),
+            "pc": self.pc,
+            "op": self.name(),
+            "path": self.path.as_ref().map(|path| path.to_string()),
+            "stack": self.stack.iter().map(|word| format!("{:#x}", word)).collect::<Vec<_>>(),
+            "gas": self.gas_remaining,
+            "gasCost": self.gas_cost,
+            "memory": self.memory.as_ref().map(|write| json!({
+                "offset": write.offset,
+                "data": write.data.to_string(),
+            })),
+        })
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [", self.location(), self.name())?;
+        for (i, word) in self.stack.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:#x}", word)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl Trace {
+    /// Steps whose element path displays as `path`, e.g. `"copy_loop+3"`.
+    pub fn at<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a Step> + 'a {
+        self.steps.iter().filter(move |step| step.path.as_ref().is_some_and(|p| p.to_string() == path))
+    }
+
+    /// Array of step objects, with words and bytes as 0x-prefixed hex.
+    pub fn to_json(&self) -> Value {
+        Value::Array(self.steps.iter().map(Step::to_json).collect())
+    }
+}
+
+/// One step per line, indented by call depth.
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{:indent$}{}", "", step, indent = 2 * step.depth)?;
+        }
+        Ok(())
+    }
+}
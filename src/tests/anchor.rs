@@ -0,0 +1,60 @@
+use crate::*;
+use super::deploy_and_call;
+use emasm_common::{source::parse_program, verify::VerifyIssueKind};
+use revm::primitives::U256;
+
+#[test]
+fn test_anchor_emits_no_bytes() {
+    let bytecode = evm_asm!(["code_end", "stop", "anchor:code_end"]);
+
+    // PUSH1 3, STOP
+    assert_eq!(hex::encode(bytecode), "600300");
+}
+
+#[test]
+fn test_anchors_delimit_runtime() {
+    let initcode = evm_asm!([
+        "runtime_end - runtime_start", "dup1", "runtime_start", 0x00, "codecopy",
+        0x00, "return",
+        "anchor:runtime_start",
+        0x2a, 0x00, "mstore", 0x20, 0x00, "return",
+        "anchor:runtime_end"
+    ]);
+    let runtime = evm_asm!([0x2a, 0x00, "mstore", 0x20, 0x00, "return"]);
+
+    let (code, output) = deploy_and_call(initcode);
+    assert_eq!(code.to_vec(), runtime);
+    assert_eq!(U256::from_be_slice(&output), U256::from(42));
+}
+
+#[test]
+fn test_jump_to_anchor_is_rejected() {
+    let elements = parse_program(r#"["target", "jump", "anchor:target", "stop"]"#).unwrap();
+    assert_eq!(elements[0], AsmElement::Label("target".to_string()));
+    assert_eq!(elements[2], AsmElement::Anchor("target".to_string()));
+
+    let issues = Assembler::new().verify(&elements).unwrap();
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(
+        issues[0].kind,
+        VerifyIssueKind::JumpToAnchor { opcode: "JUMP", label: "target".to_string() }
+    );
+    assert_eq!(issues[0].path.to_string(), "<root>+1");
+}
+
+#[test]
+fn test_pushed_anchor_is_not_a_jumpdest_issue() {
+    let elements = parse_program(r#"["here", "pop", "anchor:here", "stop"]"#).unwrap();
+
+    assert!(Assembler::new().verify(&elements).unwrap().is_empty());
+}
+
+#[test]
+fn test_dead_code_keeps_anchors() {
+    let elements = parse_program(r#"["end", "pop", "stop", "0x01", "anchor:end"]"#).unwrap();
+
+    let (stripped, report) = Assembler::new().eliminate_dead_code(&elements).unwrap();
+    assert_eq!(report.removed_instructions, 1);
+    assert_eq!(stripped.last(), Some(&AsmElement::Anchor("end".to_string())));
+    assert_eq!(Assembler::new().assemble(&stripped).unwrap(), vec![0x60, 0x04, 0x50, 0x00]);
+}
@@ -36,7 +36,7 @@ fn test_with_labels() {
     
 
     // Should have PUSH for jump target, JUMP, then JUMPDEST at target
-    assert!(bytecode.len() > 0);
+    assert!(!bytecode.is_empty());
     assert!(bytecode.contains(&0x5b)); // JUMPDEST opcode
     
     // Check that JUMP target matches JUMPDEST position
@@ -74,7 +74,7 @@ fn test_nested_segments() {
 
 #[test]
 fn test_push_zero() {
-    let bytecode = evm_asm!([0x00, "mstore"]);
+    let bytecode = evm_asm!(stack_check = false, [0x00, "mstore"]);
     
     // Should use PUSH1 0x00 for compatibility
     assert_eq!(bytecode[0], 0x60); // PUSH1
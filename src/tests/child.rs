@@ -0,0 +1,135 @@
+use crate::*;
+use super::word;
+use emasm_common::{
+    create2::{create2_address, initcode_hash},
+    source::{parse_abi, parse_program},
+};
+use revm::{
+    primitives::{AccountInfo, Address, Bytecode, Bytes, ExecutionResult, Output, TxKind, U256},
+    Evm,
+    InMemoryDB,
+};
+
+const FACTORY: Address = Address::new([0x42; 20]);
+
+/// Call `factory`, which returns the address of a contract it created, then call that contract
+fn create_and_call(factory: Vec<u8>) -> (Address, Bytes) {
+    let mut db = InMemoryDB::default();
+    let bytecode = Bytecode::new_raw(Bytes::from(factory));
+    db.insert_account_info(FACTORY, AccountInfo {
+        balance: U256::ZERO,
+        nonce: 1,
+        code_hash: bytecode.hash_slow(),
+        code: Some(bytecode),
+    });
+
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .modify_tx_env(|tx| {
+            tx.caller = Address::from([0x41; 20]);
+            tx.transact_to = TxKind::Call(FACTORY);
+        })
+        .build();
+
+    let child = match evm.transact_commit().expect("Factory call failed") {
+        ExecutionResult::Success { output: Output::Call(data), .. } => Address::from_slice(&data[12..]),
+        other => panic!("Factory call failed: {:?}", other),
+    };
+    evm.tx_mut().transact_to = TxKind::Call(child);
+    match evm.transact().expect("Child call failed").result {
+        ExecutionResult::Success { output: Output::Call(data), .. } => (child, data),
+        other => panic!("Child call failed: {:?}", other),
+    }
+}
+
+#[test]
+fn test_factory_creates_child_program() {
+    let factory = evm_asm!([
+        "bytes:initcode:size", "dup1", "bytes:initcode:ptr", 0x00, "codecopy",
+        0x00, 0x00, "create",
+        0x00, "mstore", 0x20, 0x00, "return",
+        // Initcode of the child, which embeds its own runtime
+        ["child:initcode", [
+            "bytes:runtime:size", "dup1", "bytes:runtime:ptr", 0x00, "codecopy", 0x00, "return",
+            ["child:runtime", [0x2a, 0x00, "mstore", 0x20, 0x00, "return"]]
+        ]]
+    ]);
+
+    let (child, output) = create_and_call(factory);
+    assert_ne!(child, Address::ZERO);
+    assert_eq!(output.to_vec(), word(42));
+}
+
+#[test]
+fn test_child_has_its_own_labels() {
+    // Both programs define "main"; the child's label is relative to the child
+    let parent = evm_asm!([
+        "main", "jump",
+        ["main", ["stop"]],
+        ["child:child", ["main", "jump", ["main", ["stop"]]]]
+    ]);
+    let child = evm_asm!(["main", "jump", ["main", ["stop"]]]);
+    assert_eq!(parent[parent.len() - child.len()..], child[..]);
+    assert_eq!(child, hex::decode("6003565b00").unwrap());
+}
+
+#[test]
+fn test_one_element_arrays() {
+    // One element is a program under "child:" and hex data under "bytes:"
+    assert_eq!(evm_asm!(["stop", ["child:c", ["stop"]]]), vec![0x00, 0x00]);
+    assert_eq!(evm_asm!(["stop", ["child:c", [0x00]]]), vec![0x00, 0x60, 0x00]);
+    assert_eq!(evm_asm!(["stop", ["bytes:data", ["0xdeadbeef"]]]), hex::decode("00deadbeef").unwrap());
+
+    let bytes = |source: &str| match parse_program(source).map(|elements| elements[0].clone()) {
+        Ok(AsmElement::BytesSegment(_, data)) => Ok(data),
+        other => Err(format!("{:?}", other)),
+    };
+    assert_eq!(bytes(r#"[["child:c", ["stop"]]]"#), Ok(vec![0x00]));
+    assert_eq!(bytes(r#"[["child:c", ["0x00"]]]"#), Ok(vec![0x60, 0x00]));
+    assert_eq!(bytes(r#"[["bytes:c", ["0x00"]]]"#), Ok(vec![0x00]));
+    assert!(bytes(r#"[["bytes:c", ["stop"]]]"#).is_err());
+}
+
+#[test]
+fn test_embed_other_macro_output() {
+    let child = evm_contract!([], [0x07, 0x00, "mstore", 0x20, 0x00, "return"]);
+    let factory = evm_asm!([
+        0x99, "bytes:child:size", "dup1", "bytes:child:ptr", 0x00, "codecopy",
+        0x00, 0x00, "create2",
+        0x00, "mstore", 0x20, 0x00, "return",
+        ["bytes:child", child]
+    ]);
+
+    let (address, output) = create_and_call(factory);
+    let mut salt = [0u8; 32];
+    salt[31] = 0x99;
+    let predicted = create2_address(FACTORY.into_array().into(), salt.into(), initcode_hash(&child));
+    assert_eq!(address.into_array(), predicted.into_array());
+    assert_eq!(output.to_vec(), word(7));
+}
+
+#[test]
+fn test_json_child_program() {
+    let elements = parse_program(r#"[
+        "bytes:child:ptr",
+        ["child:child", ["main", "jump", ["main", ["stop"]]]]
+    ]"#).unwrap();
+    assert_eq!(elements[1], AsmElement::BytesSegment("child".to_string(), hex::decode("6003565b00").unwrap()));
+
+    let err = parse_program(r#"[["child:child", ["nonsense", "stop"]]]"#).unwrap_err();
+    assert!(matches!(err, AssemblerError::InvalidBytesSegment(_)));
+    // Programs are only embedded with "child:"
+    let err = parse_program(r#"[["bytes:child", ["main", "jump", ["main", ["stop"]]]]]"#).unwrap_err();
+    assert!(matches!(err, AssemblerError::InvalidBytesSegment(_)));
+}
+
+#[test]
+fn test_child_dispatchers_are_not_in_parent_abi() {
+    let abi = parse_abi(r#"[
+        ["dispatch", {"owner()": "owner"}],
+        ["owner", ["stop"]],
+        ["child:child", [["dispatch", {"name()": "name"}], ["name", ["stop"]]]]
+    ]"#).unwrap();
+    assert_eq!(abi.as_array().unwrap().len(), 1);
+    assert_eq!(abi[0]["name"], "owner");
+}
@@ -0,0 +1,79 @@
+use crate::*;
+use super::{execute, op};
+use emasm_common::{
+    constants::materialize,
+    opcodes::Fork,
+    peephole::Objective,
+};
+use revm::primitives::U256;
+
+fn literal(value: &[u8]) -> AsmElement {
+    AsmElement::Literal(value.to_vec())
+}
+
+/// Materialize `value`, check its size and that revm computes the same value
+fn check(value: U256, fork: Fork, objective: Objective, expected: Vec<AsmElement>) {
+    let m = materialize(value, fork, objective);
+    assert_eq!(m.elements, expected);
+
+    let mut program = m.elements.clone();
+    program.extend([literal(&[0x00]), op("mstore"), literal(&[0x20]), literal(&[0x00]), op("return")]);
+    let assembler = Assembler::new();
+    let code = assembler.assemble(&program).unwrap();
+    assert_eq!(code.len() - 8, m.size, "size of {:?}", m.elements);
+    assert_eq!(U256::from_be_slice(&execute(code, &[])), value);
+}
+
+#[test]
+fn test_all_ones_is_not_of_zero() {
+    check(U256::MAX, Fork::Cancun, Objective::Size, vec![op("push0"), op("not")]);
+    check(U256::MAX, Fork::London, Objective::Size, vec![literal(&[]), op("not")]);
+    // PUSH32 costs 3 gas, PUSH0 NOT costs 5
+    check(U256::MAX, Fork::Cancun, Objective::Gas, vec![literal(&[0xff; 32])]);
+}
+
+#[test]
+fn test_high_bit_is_shifted() {
+    let value = U256::from(1) << 255;
+    check(value, Fork::Cancun, Objective::Size, vec![literal(&[0x01]), literal(&[0xff]), op("shl")]);
+
+    // SHL/SHR arrived in Constantinople
+    let mut push32 = vec![0u8; 32];
+    push32[0] = 0x80;
+    check(value, Fork::Byzantium, Objective::Size, vec![literal(&push32)]);
+}
+
+#[test]
+fn test_mostly_ones_is_not_of_short_push() {
+    let value = !U256::from(0x1234);
+    check(value, Fork::Cancun, Objective::Size, vec![literal(&[0x12, 0x34]), op("not")]);
+}
+
+#[test]
+fn test_low_mask_is_shifted_ones() {
+    // 2^160 - 1, the address mask
+    let value = (U256::from(1) << 160) - U256::from(1);
+    check(
+        value,
+        Fork::Cancun,
+        Objective::Size,
+        vec![op("push0"), op("not"), literal(&[0x60]), op("shr")],
+    );
+}
+
+#[test]
+fn test_short_values_stay_plain_pushes() {
+    check(U256::from(0x42), Fork::Cancun, Objective::Size, vec![literal(&[0x42])]);
+    check(U256::ZERO, Fork::Cancun, Objective::Gas, vec![op("push0")]);
+    check(U256::ZERO, Fork::London, Objective::Gas, vec![literal(&[])]);
+}
+
+#[test]
+fn test_macro_fork_option_avoids_push0() {
+    let bytecode = evm_asm!(
+        optimize = "size",
+        fork = "london",
+        [0x00, 0x00, "return"]
+    );
+    assert_eq!(bytecode, vec![0x60, 0x00, 0x60, 0x00, 0xf3]);
+}
@@ -0,0 +1,84 @@
+use crate::*;
+use super::{deploy_and_call, word};
+use emasm_common::contract::{initcode, ContractOptions};
+
+#[test]
+fn test_deploys_runtime() {
+    let initcode = evm_contract!([], [0x2a, 0x00, "mstore", 0x20, 0x00, "return"]);
+    let runtime = evm_asm!([0x2a, 0x00, "mstore", 0x20, 0x00, "return"]);
+
+    let (code, output) = deploy_and_call(initcode);
+    assert_eq!(code.to_vec(), runtime);
+    assert_eq!(output.to_vec(), word(42));
+}
+
+#[test]
+fn test_constructor_runs_before_deploy() {
+    let initcode = evm_contract!(
+        [0x07, 0x00, "sstore"],
+        [0x00, "sload", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    let (_, output) = deploy_and_call(initcode);
+    assert_eq!(output.to_vec(), word(7));
+}
+
+#[test]
+fn test_constructor_jumps_to_deploy() {
+    let initcode = evm_contract!(
+        ["init", "jump", ["init", [0x09, 0x00, "sstore", "deploy", "jump"]]],
+        [0x00, "sload", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    let (_, output) = deploy_and_call(initcode);
+    assert_eq!(output.to_vec(), word(9));
+}
+
+#[test]
+fn test_constructor_args_are_copied_to_memory() {
+    let mut initcode = evm_contract!(
+        constructor_args = true,
+        [0x00, "mload", 0x00, "sstore"],
+        [0x00, "sload", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+    initcode.extend(word(0x1234));
+
+    let (_, output) = deploy_and_call(initcode);
+    assert_eq!(output.to_vec(), word(0x1234));
+}
+
+#[test]
+fn test_constructor_reads_runtime_size() {
+    // Stores the runtime size, which is 11 bytes
+    let initcode = evm_contract!(
+        ["bytes:runtime:size", 0x00, "sstore"],
+        [0x00, "sload", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    let (_, output) = deploy_and_call(initcode);
+    assert_eq!(output.to_vec(), word(11));
+}
+
+#[test]
+fn test_assembler_api_matches_macro() {
+    let constructor = vec![
+        AsmElement::Literal(vec![0x07]),
+        AsmElement::Literal(vec![]),
+        AsmElement::Opcode("sstore".to_string()),
+    ];
+    let runtime = vec![AsmElement::Opcode("stop".to_string())];
+    let bytecode = Assembler::new()
+        .assemble_contract(&constructor, &runtime, &ContractOptions::default())
+        .unwrap();
+
+    assert_eq!(bytecode, evm_contract!([0x07, 0x00, "sstore"], ["stop"]));
+    // Constructor, JUMPDEST PUSH1 1 DUP1 PUSH1 0x11 PUSH1 0 CODECOPY PUSH1 0 RETURN, runtime
+    assert_eq!(bytecode, hex::decode("60076000555b60018060116000396000f300").unwrap());
+}
+
+#[test]
+fn test_reserved_names_are_rejected() {
+    let constructor = vec![AsmElement::Segment("deploy".to_string(), vec![])];
+    let err = initcode(&constructor, vec![], &[], &ContractOptions::default()).unwrap_err();
+    assert!(matches!(err, AssemblerError::InvalidContract(_)));
+}
@@ -0,0 +1,122 @@
+use crate::*;
+use emasm_common::create2::{create2_address, initcode_hash, mine_salt, AddressPattern, SaltSearch};
+use alloy_primitives::{address, b256, Address, B256, U256};
+use revm::{
+    primitives::{AccountInfo, Bytecode, Bytes, ExecutionResult, Output, TxKind},
+    Evm,
+    InMemoryDB,
+};
+
+/// Call `factory` with `initcode` as calldata; it deploys with CREATE2 and returns the address
+fn deploy_through_factory(factory: Vec<u8>, initcode: Vec<u8>) -> Address {
+    let mut db = InMemoryDB::default();
+    let factory_address = revm::primitives::Address::from([0x42; 20]);
+    let bytecode = Bytecode::new_raw(Bytes::from(factory));
+    db.insert_account_info(factory_address, AccountInfo {
+        balance: revm::primitives::U256::ZERO,
+        nonce: 1,
+        code_hash: bytecode.hash_slow(),
+        code: Some(bytecode),
+    });
+
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .modify_tx_env(|tx| {
+            tx.caller = revm::primitives::Address::from([0x41; 20]);
+            tx.transact_to = TxKind::Call(factory_address);
+            tx.data = Bytes::from(initcode);
+        })
+        .build();
+
+    match evm.transact().expect("Transaction failed").result {
+        ExecutionResult::Success { output: Output::Call(data), .. } => Address::from_slice(&data[12..]),
+        other => panic!("Execution failed: {:?}", other),
+    }
+}
+
+#[test]
+fn test_eip1014_examples() {
+    assert_eq!(
+        create2_address(Address::ZERO, B256::ZERO, initcode_hash(&[0x00])),
+        address!("4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38")
+    );
+    assert_eq!(
+        create2_address(
+            address!("00000000000000000000000000000000deadbeef"),
+            b256!("00000000000000000000000000000000000000000000000000000000cafebabe"),
+            initcode_hash(&hex::decode("deadbeef").unwrap()),
+        ),
+        address!("60f3f640a8508fC6a86d45DF051962668E1e8AC7")
+    );
+}
+
+#[test]
+fn test_predicted_address_matches_deployment() {
+    let factory = evm_asm!([
+        // CREATE2 with salt 0x42 of the initcode given as calldata
+        0x42, "calldatasize", 0x00, 0x00, "calldatacopy",
+        0x42, "calldatasize", 0x00, 0x00, "create2",
+        0x00, "mstore", 0x20, 0x00, "return"
+    ]);
+    let template = evm_asm_interpolator!([&[0], 0x00, "mstore", 0x20, 0x00, "return"]);
+    let runtime = template(Box::new(U256::from(7)));
+    let initcode = Assembler::new()
+        .assemble_contract(&[], &[AsmElement::BytesSegment("code".to_string(), runtime)], &Default::default())
+        .unwrap();
+
+    let mut salt = B256::ZERO;
+    salt[31] = 0x42;
+    let predicted = create2_address(Address::from([0x42; 20]), salt, initcode_hash(&initcode));
+    assert_eq!(deploy_through_factory(factory, initcode), predicted);
+}
+
+#[test]
+fn test_address_pattern() {
+    let address = address!("dEAD369ee0d2aBFb1564Dc5d07bd0C7eDc3b3979");
+    assert!(AddressPattern::prefix("0xdead").unwrap().matches(&address));
+    assert!(AddressPattern::prefix("dea").unwrap().matches(&address));
+    assert!(!AddressPattern::prefix("0xbeef").unwrap().matches(&address));
+    assert!(AddressPattern::prefix("0xdeadg").is_err());
+
+    let zeros = AddressPattern::default().with_leading_zero_bytes(2);
+    assert!(zeros.matches(&address!("0000ff0000000000000000000000000000000000")));
+    assert!(!zeros.matches(&address!("00ff000000000000000000000000000000000000")));
+}
+
+#[test]
+fn test_mined_salt_does_not_depend_on_threads() {
+    let hash = initcode_hash(&[0x00]);
+    let deployer = Address::from([0x11; 20]);
+    let search = |threads| SaltSearch {
+        pattern: AddressPattern::prefix("0xab").unwrap(),
+        threads,
+        ..SaltSearch::default()
+    };
+
+    let (salt, address) = mine_salt(deployer, hash, &search(1)).unwrap();
+    assert_eq!(address.as_slice()[0], 0xab);
+    assert_eq!(create2_address(deployer, salt, hash), address);
+    for threads in [2, 3, 8] {
+        assert_eq!(mine_salt(deployer, hash, &search(threads)), Some((salt, address)));
+    }
+}
+
+#[test]
+fn test_mining_keeps_salt_base_and_gives_up() {
+    let hash = initcode_hash(&[0x00]);
+    let base = B256::repeat_byte(0x77);
+    let search = SaltSearch {
+        pattern: AddressPattern::prefix("0x1").unwrap(),
+        base,
+        ..SaltSearch::default()
+    };
+    let (salt, _) = mine_salt(Address::ZERO, hash, &search).unwrap();
+    assert_eq!(salt[..24], base[..24]);
+
+    let hopeless = SaltSearch {
+        pattern: AddressPattern::default().with_leading_zero_bytes(8),
+        max_attempts: 100,
+        ..SaltSearch::default()
+    };
+    assert_eq!(mine_salt(Address::ZERO, hash, &hopeless), None);
+}
@@ -0,0 +1,96 @@
+use crate::*;
+use emasm_common::{
+    data::parse_data,
+    source::{parse_program, parse_program_in},
+    AssemblerError,
+};
+use std::path::Path;
+
+#[test]
+fn test_utf8_and_fill_directives() {
+    let bytecode = evm_asm!([
+        "stop",
+        ["bytes:message", "utf8:Not authorized"],
+        ["bytes:padding", "fill:4", "fill:2:0xff"]
+    ]);
+
+    let mut expected = vec![0x00];
+    expected.extend(b"Not authorized");
+    expected.extend([0x00, 0x00, 0x00, 0x00, 0xff, 0xff]);
+    assert_eq!(bytecode, expected);
+}
+
+#[test]
+fn test_directives_are_concatenated() {
+    let bytecode = evm_asm!([
+        "bytes:table:size", "stop",
+        ["bytes:table", "0xdead", "utf8:ok", "beef"]
+    ]);
+
+    // PUSH1 6, STOP, then the data
+    assert_eq!(hex::encode(bytecode), "600600dead6f6bbeef");
+}
+
+#[test]
+fn test_file_directive_is_relative_to_the_crate() {
+    let bytecode = evm_asm!([
+        "bytes:table:size", "stop",
+        ["bytes:table", "file:src/tests/fixtures/table.bin"]
+    ]);
+
+    assert_eq!(hex::encode(bytecode), "6008000102030405060708");
+}
+
+#[test]
+fn test_align_pads_to_a_multiple() {
+    let bytecode = evm_asm!([
+        "bytes:word:ptr", "stop",
+        ["bytes:flag", "0xaa"],
+        "align:32",
+        ["bytes:word", "utf8:hi"]
+    ]);
+
+    assert_eq!(bytecode.len(), 34);
+    assert_eq!(&bytecode[..4], &[0x60, 0x20, 0x00, 0xaa]);
+    assert!(bytecode[4..32].iter().all(|&b| b == 0));
+    assert_eq!(&bytecode[32..], b"hi");
+}
+
+#[test]
+fn test_align_already_aligned_adds_nothing() {
+    let bytecode = evm_asm!(["stop", ["bytes:a", "fill:31"], "align:32", "align:8", ["bytes:b", "0x01"]]);
+
+    assert_eq!(bytecode.len(), 33);
+    assert_eq!(bytecode[32], 0x01);
+}
+
+#[test]
+fn test_json_directives_and_files() {
+    let source = r#"[
+        "bytes:table:ptr", "stop",
+        ["bytes:message", "utf8:hi"],
+        "align:16",
+        ["bytes:table", "file:fixtures/table.bin", "fill:2:0x09"]
+    ]"#;
+
+    let program = parse_program_in(source, Path::new("src/tests")).unwrap();
+    let bytecode = Assembler::new().assemble(&program).unwrap();
+
+    assert_eq!(bytecode.len(), 26);
+    assert_eq!(&bytecode[..5], &[0x60, 0x10, 0x00, b'h', b'i']);
+    assert_eq!(hex::encode(&bytecode[16..]), "01020304050607080909");
+}
+
+#[test]
+fn test_invalid_directives() {
+    let base = Path::new(".");
+    assert!(matches!(parse_data("fill:many", base), Err(AssemblerError::InvalidBytesSegment(_))));
+    assert!(matches!(parse_data("fill:2:0x100", base), Err(AssemblerError::InvalidBytesSegment(_))));
+    assert_eq!(parse_data("fill:0xffff", base).unwrap().len(), 0xffff);
+    assert!(matches!(parse_data("fill:4294967295", base), Err(AssemblerError::InvalidBytesSegment(_))));
+    assert!(matches!(parse_data("file:no/such/file", base), Err(AssemblerError::InvalidBytesSegment(_))));
+    assert!(matches!(parse_data("0xzz", base), Err(AssemblerError::InvalidHexLiteral(_))));
+
+    assert!(parse_program(r#"["stop", "align:0"]"#).is_err());
+    assert!(parse_program(r#"["stop", ["bytes:data", "utf8:ok", 1]]"#).is_err());
+}
@@ -0,0 +1,82 @@
+use crate::*;
+use super::{execute, label, lit, op, segment};
+
+#[test]
+fn test_unused_helpers_are_removed() {
+    let elements = vec![
+        label("main"),
+        op("jump"),
+        segment("unused_helper", vec![lit(0x01), op("add"), op("swap1"), op("jump")]),
+        segment("main", vec![
+            lit(0x42), lit(0x00), op("mstore"),
+            lit(0x20), lit(0x00), op("return"),
+            // Nothing jumps here
+            lit(0xff), op("pop"),
+        ]),
+        AsmElement::BytesSegment("unused_data".to_string(), vec![0xde, 0xad]),
+    ];
+
+    let assembler = Assembler::new();
+    let (stripped, report) = assembler.eliminate_dead_code(&elements).unwrap();
+    assert_eq!(report.removed_segments, vec!["unused_helper".to_string()]);
+    assert_eq!(report.removed_bytes_segments, vec!["unused_data".to_string()]);
+    assert_eq!(report.removed_instructions, 6);
+
+    let before = assembler.assemble(&elements).unwrap();
+    let after = assembler.assemble(&stripped).unwrap();
+    assert_eq!(report.bytes_saved, before.len() - after.len());
+    assert_eq!(report.bytes_saved, 6 + 3 + 2);
+    assert_eq!(execute(before, &[]), execute(after, &[]));
+}
+
+#[test]
+fn test_fall_through_segment_loses_jumpdest() {
+    let elements = vec![
+        lit(0x01),
+        segment("body", vec![lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")]),
+    ];
+
+    let (stripped, report) = Assembler::new().eliminate_dead_code(&elements).unwrap();
+    assert_eq!(report.inlined_segments, vec!["body".to_string()]);
+    assert_eq!(report.bytes_saved, 1);
+    assert_eq!(stripped, vec![
+        lit(0x01), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return"),
+    ]);
+}
+
+#[test]
+fn test_code_reachable_only_from_dead_code_is_removed() {
+    let elements = vec![
+        op("stop"),
+        segment("a", vec![label("b"), op("jump")]),
+        segment("b", vec![label("a"), op("jump")]),
+    ];
+
+    let (stripped, report) = Assembler::new().eliminate_dead_code(&elements).unwrap();
+    assert_eq!(stripped, vec![op("stop")]);
+    assert_eq!(report.removed_segments, vec!["a".to_string(), "b".to_string()]);
+}
+
+#[test]
+fn test_nested_jump_target_survives_dead_parent() {
+    let elements = vec![
+        label("inner"),
+        op("jump"),
+        segment("outer", vec![lit(0x01), segment("inner", vec![op("stop")])]),
+    ];
+
+    let (stripped, report) = Assembler::new().eliminate_dead_code(&elements).unwrap();
+    assert_eq!(stripped, vec![label("inner"), op("jump"), segment("inner", vec![op("stop")])]);
+    assert_eq!(report.removed_segments, vec!["outer".to_string()]);
+    assert_eq!(report.removed_instructions, 1);
+}
+
+#[test]
+fn test_explicit_jumpdest_is_kept() {
+    // The JUMPDEST may be the target of a computed jump
+    let elements = vec![lit(0x03), op("jump"), op("jumpdest"), op("stop")];
+
+    let (stripped, report) = Assembler::new().eliminate_dead_code(&elements).unwrap();
+    assert_eq!(stripped, elements);
+    assert_eq!(report.bytes_saved, 0);
+}
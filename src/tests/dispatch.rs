@@ -0,0 +1,166 @@
+use crate::*;
+use crate::testing::{Outcome, Revert};
+use super::{lit, op, run};
+use emasm_common::{
+    abi::{selector, Signature},
+    dispatch::{DispatchMode, Dispatcher},
+    source::{parse_abi, parse_program},
+};
+use revm::primitives::U256;
+
+/// Handler that drops the selector and returns `value` as a word
+fn handler(name: &str, value: u8) -> AsmElement {
+    AsmElement::Segment(name.to_string(), vec![
+        op("pop"), lit(value), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return"),
+    ])
+}
+
+fn call(code: &[u8], signature: &str) -> Outcome {
+    let mut calldata = selector(signature).unwrap().to_vec();
+    calldata.extend([0u8; 32]);
+    run(code.to_vec(), &calldata)
+}
+
+const ERC20: [&str; 9] = [
+    "name()",
+    "symbol()",
+    "decimals()",
+    "totalSupply()",
+    "balanceOf(address)",
+    "transfer(address,uint256)",
+    "allowance(address,address)",
+    "approve(address,uint256)",
+    "transferFrom(address,address,uint256)",
+];
+
+#[test]
+fn test_selectors() {
+    assert_eq!(selector("transfer(address,uint256)").unwrap(), [0xa9, 0x05, 0x9c, 0xbb]);
+    assert_eq!(selector("balanceOf(address)").unwrap(), [0x70, 0xa0, 0x82, 0x31]);
+    // Whitespace and the uint alias are normalized away
+    assert_eq!(selector("transfer( address, uint )").unwrap(), [0xa9, 0x05, 0x9c, 0xbb]);
+    assert_eq!(
+        Signature::parse("f((uint,bytes32)[],int8[2])").unwrap().canonical(),
+        "f((uint256,bytes32)[],int8[2])"
+    );
+
+    for invalid in ["transfer", "transfer(address to)", "f(uint7)", "f(bytes33)", "f((uint256)", "1f()"] {
+        assert!(
+            matches!(selector(invalid), Err(AssemblerError::InvalidSignature(_))),
+            "{} should be rejected",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_macro_dispatch_with_fallback() {
+    let bytecode = evm_asm!([
+        ["dispatch",
+            [["transfer(address,uint256)", "do_transfer"], ["balanceOf(address)", "do_balance"]],
+            ["fallback", "fallback"]],
+        ["do_transfer", ["pop", 0x01, 0x00, "mstore", 0x20, 0x00, "return"]],
+        ["do_balance", ["pop", 0x02, 0x00, "mstore", 0x20, 0x00, "return"]],
+        ["fallback", ["pop", 0xff, 0x00, "mstore", 0x20, 0x00, "return"]]
+    ]);
+
+    assert_eq!(call(&bytecode, "transfer(address,uint256)").word(), U256::from(0x01));
+    assert_eq!(call(&bytecode, "balanceOf(address)").word(), U256::from(0x02));
+    assert_eq!(call(&bytecode, "approve(address,uint256)").word(), U256::from(0xff));
+    // Too short to hold a selector
+    assert_eq!(run(bytecode, &[0xa9, 0x05, 0x9c]).word(), U256::from(0xff));
+}
+
+#[test]
+fn test_all_modes_route_the_same() {
+    let functions: Vec<(String, String)> = ERC20
+        .iter()
+        .enumerate()
+        .map(|(i, sig)| (sig.to_string(), format!("h{}", i)))
+        .collect();
+
+    for mode in [DispatchMode::Linear, DispatchMode::Binary, DispatchMode::JumpTable] {
+        let dispatcher = Dispatcher { functions: functions.clone(), fallback: None, mode };
+        let mut program = dispatcher.lower().unwrap();
+        program.extend((0..ERC20.len()).map(|i| handler(&format!("h{}", i), i as u8 + 1)));
+
+        let assembler = Assembler::new();
+        assert!(assembler.verify(&program).unwrap().is_empty(), "{:?}", mode);
+        assert!(assembler.check_stack(&program).iter().all(|i| !i.kind.is_error()), "{:?}", mode);
+
+        let code = assembler.assemble(&program).unwrap();
+        for (i, sig) in ERC20.iter().enumerate() {
+            assert_eq!(call(&code, sig).word(), U256::from(i + 1), "{} in {:?}", sig, mode);
+        }
+        // Without a fallback, unknown selectors revert with no data
+        assert_eq!(call(&code, "mint(address,uint256)").revert(), Some(Revert::Empty), "{:?}", mode);
+        assert_eq!(run(code, &[]).revert(), Some(Revert::Empty), "{:?}", mode);
+    }
+}
+
+#[test]
+fn test_json_source_and_abi() {
+    let source = r#"[
+        ["dispatch",
+            {"transfer(address,uint256)": "do_transfer", "totalSupply()": "do_supply"},
+            {"mode": "binary"}],
+        ["do_transfer", ["pop", 1, 0, "mstore", 32, 0, "return"]],
+        ["do_supply", ["pop", 2, 0, "mstore", 32, 0, "return"]]
+    ]"#;
+    let code = Assembler::new().assemble(&parse_program(source).unwrap()).unwrap();
+    assert_eq!(call(&code, "totalSupply()").word(), U256::from(0x02));
+
+    let abi = parse_abi(source).unwrap();
+    assert_eq!(abi, serde_json::json!([
+        {
+            "type": "function",
+            "name": "transfer",
+            "inputs": [{"name": "", "type": "address"}, {"name": "", "type": "uint256"}],
+            "outputs": [],
+            "stateMutability": "nonpayable"
+        },
+        {
+            "type": "function",
+            "name": "totalSupply",
+            "inputs": [],
+            "outputs": [],
+            "stateMutability": "nonpayable"
+        }
+    ]));
+}
+
+#[test]
+fn test_macro_abi_matches_source_abi() {
+    let abi = evm_abi!([
+        ["dispatch", [["f((uint256,address)[])", "f"]]],
+        ["f", ["pop", "stop"]]
+    ]);
+    let abi: serde_json::Value = serde_json::from_str(abi).unwrap();
+    assert_eq!(abi, serde_json::json!([{
+        "type": "function",
+        "name": "f",
+        "inputs": [{
+            "name": "",
+            "type": "tuple[]",
+            "components": [{"name": "", "type": "uint256"}, {"name": "", "type": "address"}]
+        }],
+        "outputs": [],
+        "stateMutability": "nonpayable"
+    }]));
+}
+
+#[test]
+fn test_invalid_dispatchers() {
+    let duplicate = Dispatcher::new(vec![
+        ("transfer(address,uint256)".to_string(), "a".to_string()),
+        ("transfer(address, uint)".to_string(), "b".to_string()),
+    ]);
+    assert!(matches!(duplicate.lower(), Err(AssemblerError::InvalidDispatch(_))));
+
+    let err = parse_program(r#"[["dispatch", {"f()": "f"}, {"mode": "hash"}]]"#).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid dispatcher: unknown mode: hash");
+
+    // A segment called "dispatch" is still a segment
+    let program = parse_program(r#"[["dispatch", ["stop"]]]"#).unwrap();
+    assert_eq!(program, vec![AsmElement::Segment("dispatch".to_string(), vec![op("stop")])]);
+}
@@ -0,0 +1,224 @@
+use crate::*;
+use super::{lit, op, word};
+use crate::testing::{Runner, Tx};
+use emasm_common::{
+    eof::{EofContainer, EofFunction, EofProgram, EofType, NON_RETURNING},
+    source::parse_eof_program,
+};
+use revm::{
+    interpreter::analysis::validate_raw_eof_inner,
+    primitives::{Address, SpecId, U256},
+};
+
+const CONTRACT: Address = Address::repeat_byte(0x42);
+
+/// Check that revm accepts `code` as runtime code (plain `validate_raw_eof` expects initcode).
+fn validate(code: &[u8]) {
+    if let Err(e) = validate_raw_eof_inner(code.to_vec().into(), None) {
+        panic!("invalid EOF {:?}: 0x{}", e, hex::encode(code));
+    }
+}
+
+/// Assemble an EOF program from JSON and check that revm accepts it.
+fn assemble(source: &str) -> Vec<u8> {
+    let program = parse_eof_program(source).unwrap();
+    let code = Assembler::new().assemble_eof(&program).unwrap();
+    validate(&code);
+    code
+}
+
+fn assemble_err(source: &str) -> String {
+    let program = parse_eof_program(source).unwrap();
+    Assembler::new().assemble_eof(&program).unwrap_err().to_string()
+}
+
+/// Call the container with `calldata` and return the word it returns.
+fn call(code: &[u8], calldata: Vec<u8>) -> U256 {
+    let mut runner = Runner::new().with_spec(SpecId::PRAGUE_EOF);
+    runner.install(CONTRACT, code.to_vec());
+    let outcome = runner.call(CONTRACT, Tx::default().data(calldata));
+    assert!(outcome.is_success(), "{}", outcome);
+    outcome.word()
+}
+
+#[test]
+fn test_header_and_sections() {
+    let program = EofProgram::new(vec![EofFunction::new(
+        "main",
+        0,
+        None,
+        2,
+        vec![
+            lit(0x2a), lit(0), op("mstore"), lit(0x20), lit(0), op("return"),
+            AsmElement::BytesSegment("d".to_string(), vec![0xaa, 0xbb]),
+        ],
+    )]);
+    let code = Assembler::new().assemble_eof(&program).unwrap();
+
+    assert_eq!(hex::encode(&code), [
+        "ef0001",       // magic and version
+        "010004",       // types section of 4 bytes
+        "020001000a",   // one code section of 10 bytes
+        "040002",       // data section of 2 bytes
+        "00",           // header terminator
+        "00800002",     // no inputs, non-returning, max stack 2
+        "602a600052",   // PUSH1 0x2a, PUSH1 0x00, MSTORE
+        "60206000f3",   // PUSH1 0x20, PUSH1 0x00, RETURN
+        "aabb",
+    ].concat());
+    validate(&code);
+    assert_eq!(call(&code, vec![]), U256::from(0x2a));
+}
+
+#[test]
+fn test_functions_with_callf_and_retf() {
+    let code = assemble(r#"{"functions": [
+        {"name": "main", "max_stack_height": 2, "code": [
+            "0x00", "calldataload", "callf:double", "callf:double",
+            "0x00", "mstore", "0x20", "0x00", "return"
+        ]},
+        {"name": "double", "inputs": 1, "outputs": 1, "max_stack_height": 2, "code": ["dup1", "add", "retf"]}
+    ]}"#);
+
+    let container = EofContainer::decode(&code).unwrap();
+    assert_eq!(container.types, [
+        EofType { inputs: 0, outputs: NON_RETURNING, max_stack_height: 2 },
+        EofType { inputs: 1, outputs: 1, max_stack_height: 2 },
+    ]);
+    assert_eq!(container.code[0][3..6], [0xe3, 0x00, 0x01]);
+    assert_eq!(call(&code, word(5)), U256::from(20));
+}
+
+#[test]
+fn test_rjumpi_loop() {
+    // Sum of 1..=n: the loop body keeps [acc, n] on the stack
+    let code = assemble(r#"{"functions": [{"name": "main", "max_stack_height": 3, "code": [
+        "0x00", "calldataload", "0x00",
+        ["loop", [
+            "dup2", "iszero", "rjumpi:done",
+            "dup2", "add", "swap1", "0x01", "swap1", "sub", "swap1",
+            "rjump:loop"
+        ]],
+        ["done", ["0x00", "mstore", "0x20", "0x00", "return"]]
+    ]}]}"#);
+
+    let container = EofContainer::decode(&code).unwrap();
+    assert_eq!(container.types[0].max_stack_height, 3);
+    assert_eq!(call(&code, word(10)), U256::from(55));
+    assert_eq!(call(&code, word(0)), U256::ZERO);
+}
+
+#[test]
+fn test_rjumpv_switch() {
+    let code = assemble(r#"{"functions": [{"name": "main", "max_stack_height": 2, "code": [
+        "0x00", "calldataload", "rjumpv:zero,one",
+        "0xee", "rjump:out",
+        ["zero", ["0x0a", "rjump:out"]],
+        ["one", ["0x0b"]],
+        ["out", ["0x00", "mstore", "0x20", "0x00", "return"]]
+    ]}]}"#);
+
+    assert_eq!(call(&code, word(0)), U256::from(0x0a));
+    assert_eq!(call(&code, word(1)), U256::from(0x0b));
+    assert_eq!(call(&code, word(7)), U256::from(0xee));
+}
+
+#[test]
+fn test_jumpf_tail_call() {
+    let code = assemble(r#"{"functions": [
+        {"name": "main", "max_stack_height": 2, "code": ["0x00", "calldataload", "0x01", "add", "jumpf:finish"]},
+        {"name": "finish", "inputs": 1, "max_stack_height": 2, "code": ["0x00", "mstore", "0x20", "0x00", "return"]}
+    ]}"#);
+
+    let container = EofContainer::decode(&code).unwrap();
+    assert_eq!(container.types[1], EofType { inputs: 1, outputs: NON_RETURNING, max_stack_height: 2 });
+    assert_eq!(call(&code, word(41)), U256::from(42));
+}
+
+#[test]
+fn test_data_section() {
+    // Bytes segments of both functions land in the data section in order: a at 0, b at 32
+    let code = assemble(r#"{"functions": [
+        {"name": "main", "max_stack_height": 3, "code": [
+            "bytes:b:size", "bytes:b", "0x00", "datacopy", "0x00", "mload",
+            "dataloadn:a", "add", "b", "dataload", "add", "callf:size", "add",
+            "0x00", "mstore", "0x20", "0x00", "return",
+            ["bytes:a", "fill:31", "0x01"]
+        ]},
+        {"name": "size", "outputs": 1, "max_stack_height": 1, "code": ["datasize", "retf", ["bytes:b", "fill:31", "0x02"]]}
+    ]}"#);
+
+    let container = EofContainer::decode(&code).unwrap();
+    assert_eq!(container.data.len(), 64);
+    assert_eq!(container.data[63], 0x02);
+    // 2 copied + 1 loaded + 2 loaded + 64 bytes of data
+    assert_eq!(call(&code, vec![]), U256::from(69));
+}
+
+#[test]
+fn test_decode_and_display() {
+    let code = assemble(r#"{"functions": [
+        {"name": "main", "max_stack_height": 2, "code": [
+            "0x00", "calldataload", "rjumpi:skip", "callf:one", "pop",
+            ["skip", ["dataloadn:d", "0x00", "mstore", "0x20", "0x00", "return"]],
+            ["bytes:d", "fill:32:0x11"]
+        ]},
+        {"name": "one", "outputs": 1, "max_stack_height": 1, "code": ["0x01", "retf"]}
+    ]}"#);
+
+    let container = EofContainer::decode(&code).unwrap();
+    assert_eq!(container.encode().unwrap(), code);
+    assert_eq!(container.to_string(), [
+        "EOF v1: 2 code sections, 0 containers, 32 data bytes",
+        "code section 0: 0 inputs, non-returning, max stack 2",
+        "  0x0000: PUSH1 0x00",
+        "  0x0002: CALLDATALOAD",
+        "  0x0003: RJUMPI +4 (-> 0x000a)",
+        "  0x0006: CALLF 1",
+        "  0x0009: POP",
+        "  0x000a: DATALOADN 0x0000",
+        "  0x000d: PUSH1 0x00",
+        "  0x000f: MSTORE",
+        "  0x0010: PUSH1 0x20",
+        "  0x0012: PUSH1 0x00",
+        "  0x0014: RETURN",
+        "code section 1: 0 inputs, 1 outputs, max stack 1",
+        "  0x0000: PUSH1 0x01",
+        "  0x0002: RETF",
+        &format!("data: 0x{}", "11".repeat(32)),
+        "",
+    ].join("\n"));
+
+    // Containers nest, indented under their parent
+    let outer = EofContainer { containers: vec![container.clone()], ..container.clone() };
+    let decoded = EofContainer::decode(&outer.encode().unwrap()).unwrap();
+    assert_eq!(decoded, outer);
+    assert!(decoded.to_string().contains("container 0:\n  EOF v1: 2 code sections"), "{}", decoded);
+
+    assert!(EofContainer::decode(&code[..code.len() - 1]).is_err());
+    assert!(EofContainer::decode(&[code.clone(), vec![0]].concat()).is_err());
+    assert!(EofContainer::decode(&[0x60, 0x00]).is_err());
+}
+
+#[test]
+fn test_eof_errors() {
+    let err = assemble_err(r#"{"functions": [{"name": "main", "max_stack_height": 1, "code": ["end", "jump", ["end", ["stop"]]]}]}"#);
+    assert!(err.contains("rjump:end"), "{}", err);
+
+    let err = assemble_err(r#"{"functions": [{"name": "main", "max_stack_height": 1, "code": ["callf:missing", "stop"]}]}"#);
+    assert_eq!(err, "Invalid EOF: unknown function missing");
+
+    let err = assemble_err(r#"{"functions": [
+        {"name": "main", "max_stack_height": 0, "code": ["stop"]},
+        {"name": "main", "max_stack_height": 0, "code": ["stop"]}
+    ]}"#);
+    assert_eq!(err, "Invalid EOF: duplicate function main");
+
+    let err = assemble_err(r#"{"functions": [{"name": "main", "max_stack_height": 0, "code": [
+        "rjump:x", ["x", ["stop"]], "anchor:x"
+    ]}]}"#);
+    assert_eq!(err, "Invalid EOF: duplicate label x in function main");
+
+    let err = parse_eof_program(r#"{"functions": [{"name": "main", "code": ["stop"]}]}"#).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: function main must declare its max_stack_height");
+}
@@ -0,0 +1,123 @@
+use crate::*;
+use emasm_common::{
+    eof::{EofContainer, EofType, NON_RETURNING},
+    eof_verify::{validate_eof, EofIssue, EofIssueKind},
+    source::parse_eof_program,
+};
+use revm::interpreter::analysis::validate_raw_eof_inner;
+
+fn issues(source: &str) -> Vec<String> {
+    let program = parse_eof_program(source).unwrap();
+    Assembler::new().validate_eof(&program).unwrap().iter().map(|issue| issue.to_string()).collect()
+}
+
+fn main(max_stack_height: u16, code: &str) -> String {
+    format!(r#"{{"functions": [{{"name": "main", "max_stack_height": {}, "code": {}}}]}}"#, max_stack_height, code)
+}
+
+#[test]
+fn test_valid_program() {
+    let source = r#"{"functions": [
+        {"name": "main", "max_stack_height": 2, "code": [
+            "0x00", "calldataload", "rjumpi:skip", "0x01", "callf:inc", "pop",
+            ["skip", ["dataloadn:d", "0x00", "mstore", "0x20", "0x00", "return"]],
+            ["bytes:d", "fill:32"]
+        ]},
+        {"name": "inc", "inputs": 1, "outputs": 1, "max_stack_height": 2, "code": ["0x01", "add", "retf"]}
+    ]}"#;
+    assert!(issues(source).is_empty());
+}
+
+#[test]
+fn test_opcode_issues() {
+    assert_eq!(issues(&main(1, r#"["0x00", "jumpi", "stop"]"#)), [
+        "code section 0 at pc 0x2 (main+1): JUMPI is not valid in EOF code",
+    ]);
+    assert_eq!(issues(&main(1, r#"["stop", "0x01", "stop"]"#)), [
+        "code section 0 at pc 0x1 (main+1): PUSH1 is unreachable",
+    ]);
+    // The last instruction falls off the end of the section
+    assert_eq!(issues(&main(2, r#"["0x01", ["end", ["0x02"]]]"#)), [
+        "code section 0 at pc 0x4 (main/end+0): code section does not end in a terminating instruction",
+    ]);
+    assert_eq!(issues(&main(1, r#"["dataloadn:d", "stop", ["bytes:d", "0x01"]]"#)), [
+        "code section 0 at pc 0x0 (main+0): DATALOADN 0x0 reads past the 1 byte data section",
+    ]);
+}
+
+#[test]
+fn test_stack_issues() {
+    assert_eq!(issues(&main(1, r#"["0x01", ["body", ["add", "stop"]]]"#)), [
+        "code section 0 at pc 0x2 (main/body+0): stack underflow: ADD needs 2 items, 1 available",
+    ]);
+    // Looping back to top leaves one more item on the stack than the first entry had
+    assert_eq!(issues(&main(2, r#"[["top", ["0x00", "0x00", "rjumpv:top,end"]], ["end", ["stop"]]]"#)), [
+        "code section 0 at pc 0x4 (main/top+2): stack height differs at 0x0, the target of a backward jump",
+    ]);
+    assert_eq!(issues(r#"{"functions": [
+        {"name": "main", "max_stack_height": 1, "code": ["callf:two", "pop", "stop"]},
+        {"name": "two", "outputs": 1, "max_stack_height": 2, "code": ["0x01", "0x02", "retf"]}
+    ]}"#), [
+        "code section 1 at pc 0x4 (two+2): 2 items on the stack at RETF, expected 1",
+    ]);
+}
+
+#[test]
+fn test_section_issues() {
+    assert_eq!(issues(r#"{"functions": [{"name": "main", "max_stack_height": 7, "code": ["stop"]}]}"#), [
+        "code section 0 (main): declared max stack height 7, but the code reaches 0",
+    ]);
+    assert_eq!(issues(r#"{"functions": [
+        {"name": "main", "max_stack_height": 0, "code": ["stop"]},
+        {"name": "unused", "max_stack_height": 0, "code": ["stop"]}
+    ]}"#), [
+        "code section 1 (unused): code section is never called from the first one",
+    ]);
+    assert_eq!(issues(r#"{"functions": [
+        {"name": "main", "max_stack_height": 0, "code": ["callf:done", "stop"]},
+        {"name": "done", "max_stack_height": 0, "code": ["stop"]}
+    ]}"#), [
+        "code section 0 at pc 0x0 (main+0): CALLF to non-returning code section 1",
+    ]);
+    assert_eq!(issues(r#"{"functions": [{"name": "main", "outputs": 0, "max_stack_height": 0, "code": ["retf"]}]}"#), [
+        "code section 0 (main): first code section must take no inputs and not return",
+    ]);
+}
+
+#[test]
+fn test_assemble_rejects_invalid_code() {
+    let program = parse_eof_program(&main(1, r#"["0x01", ["body", ["add", "stop"]]]"#)).unwrap();
+    assert_eq!(
+        Assembler::new().assemble_eof(&program).unwrap_err().to_string(),
+        "Invalid EOF: code section 0 at pc 0x2 (main/body+0): stack underflow: ADD needs 2 items, 1 available"
+    );
+}
+
+#[test]
+fn test_validate_decoded_container() {
+    // RJUMPI +1 lands on the immediate of the PUSH1 after it
+    let code = hex::decode("6001e10001600000").unwrap();
+    let container = EofContainer {
+        types: vec![EofType { inputs: 0, outputs: NON_RETURNING, max_stack_height: 1 }],
+        code: vec![code],
+        ..EofContainer::default()
+    };
+
+    assert_eq!(validate_eof(&container), [EofIssue {
+        section: 0,
+        pc: Some(5),
+        path: None,
+        kind: EofIssueKind::JumpIntoImmediate { target: 6 },
+    }]);
+    assert!(validate_raw_eof_inner(container.encode().unwrap().into(), None).is_err());
+
+    // Sections are only analyzed when each has its types entry
+    let extra_type = EofContainer { types: vec![container.types[0]; 2], ..container.clone() };
+    assert_eq!(validate_eof(&extra_type)[0].to_string(), "code section 0: 2 types entries for 1 code sections");
+    let empty = EofContainer { code: vec![], ..container.clone() };
+    assert_eq!(validate_eof(&empty)[0].to_string(), "code section 0: container has no code sections");
+
+    let fixed = EofContainer { code: vec![hex::decode("6001e1000000").unwrap()], ..container };
+    assert!(validate_eof(&fixed).is_empty());
+    assert!(validate_raw_eof_inner(fixed.encode().unwrap().into(), None).is_ok());
+}
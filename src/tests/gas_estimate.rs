@@ -0,0 +1,94 @@
+use crate::*;
+use super::{lit, op, run};
+use emasm_common::gas::{GasRange, PathEnd};
+
+const INTRINSIC_GAS: u64 = 21000;
+
+/// Gas used by the contract code itself when called with empty calldata
+fn measure_gas(code: Vec<u8>) -> u64 {
+    let outcome = run(code, &[]);
+    assert!(outcome.is_success(), "{}", outcome);
+    outcome.gas_used - INTRINSIC_GAS
+}
+
+#[test]
+fn test_straight_line_estimate_matches_revm() {
+    let elements = vec![
+        lit(0x42), lit(0x00), op("mstore"),
+        // Memory expansion to 0x80 bytes
+        lit(0x01), lit(0x60), op("mstore"),
+        lit(0x20), lit(0x00), op("sha3"),
+        op("pop"),
+        lit(0x20), lit(0x00), op("return"),
+    ];
+
+    let assembler = Assembler::new();
+    let report = assembler.estimate_gas(&elements).unwrap();
+    let measured = measure_gas(assembler.assemble(&elements).unwrap());
+
+    assert_eq!(report.paths.len(), 1);
+    assert_eq!(report.paths[0].gas, GasRange::exact(measured));
+    assert_eq!(report.total(), GasRange::exact(measured));
+}
+
+#[test]
+fn test_second_sload_of_same_slot_is_warm() {
+    let elements = vec![
+        lit(0x01), op("sload"), op("pop"),
+        lit(0x01), op("sload"), op("pop"),
+        op("stop"),
+    ];
+
+    let assembler = Assembler::new();
+    let report = assembler.estimate_gas(&elements).unwrap();
+    // 2 * (PUSH1 + POP) + cold SLOAD + warm SLOAD
+    assert_eq!(report.total(), GasRange::exact(2 * (3 + 2) + 2100 + 100));
+    assert_eq!(report.total().min, measure_gas(assembler.assemble(&elements).unwrap()));
+}
+
+#[test]
+fn test_unknown_slot_gives_warm_to_cold_range() {
+    let elements = vec![op("caller"), op("sload"), op("pop"), op("stop")];
+
+    let report = Assembler::new().estimate_gas(&elements).unwrap();
+    assert_eq!(report.total(), GasRange::between(2 + 100 + 2, 2 + 2100 + 2));
+}
+
+#[test]
+fn test_each_branch_is_a_path() {
+    let elements = vec![
+        op("calldatasize"),
+        AsmElement::Label("empty".to_string()),
+        op("jumpi"),
+        lit(0x00), lit(0x00), op("revert"),
+        AsmElement::Segment("empty".to_string(), vec![lit(0x20), lit(0x00), op("return")]),
+    ];
+    let report = Assembler::new().estimate_gas(&elements).unwrap();
+
+    let ends: Vec<_> = report.paths.iter().map(|p| p.end.clone()).collect();
+    assert!(ends.contains(&PathEnd::Halt("RETURN")));
+    assert!(ends.contains(&PathEnd::Halt("REVERT")));
+    assert!(report.loops.is_empty());
+
+    let empty = report.segments.iter().find(|s| s.label == "empty").unwrap();
+    // JUMPDEST + 2 pushes, memory expansion from an unknown size up to one word
+    assert_eq!(empty.gas, GasRange::between(1 + 3 + 3, 1 + 3 + 3 + 3));
+}
+
+#[test]
+fn test_loop_is_unbounded() {
+    let elements = vec![
+        lit(0x03),
+        AsmElement::Segment("loop_start".to_string(), vec![
+            lit(0x01), op("swap1"), op("sub"),
+            op("dup1"), AsmElement::Label("loop_start".to_string()), op("jumpi"),
+            op("stop"),
+        ]),
+    ];
+
+    let report = Assembler::new().estimate_gas(&elements).unwrap();
+    assert_eq!(report.loops, vec!["loop_start".to_string()]);
+    assert!(!report.total().is_bounded());
+    assert!(report.paths.iter().any(|p| p.end == PathEnd::Loop("loop_start".to_string())));
+    assert!(report.to_string().contains("unbounded loops: loop_start"));
+}
@@ -0,0 +1,117 @@
+use crate::*;
+use crate::testing::DEFAULT_CALLER;
+use super::{deploy_and_call, word};
+use emasm_common::{
+    contract::ContractOptions,
+    layout::ImmutableSlot,
+    source::parse_program,
+};
+use revm::primitives::Address;
+
+fn address_word(address: Address) -> Vec<u8> {
+    address.into_word().to_vec()
+}
+
+#[test]
+fn test_immutable_assembles_as_zero_push() {
+    let bytecode = evm_asm!(["immutable:owner", "immutable:owner:20", "pop", "pop"]);
+    let mut expected = vec![0x7f];
+    expected.extend([0; 32]);
+    expected.push(0x73);
+    expected.extend([0; 20]);
+    expected.extend([0x50, 0x50]);
+    assert_eq!(bytecode, expected);
+}
+
+#[test]
+fn test_layout_reports_immutable_slots() {
+    let program = vec![
+        AsmElement::Literal(vec![0x01]),
+        AsmElement::Immutable("owner".to_string(), 20),
+        AsmElement::Segment("main".to_string(), vec![AsmElement::Immutable("fee".to_string(), 32)]),
+    ];
+    let (_, layout) = Assembler::new().assemble_with_layout(&program).unwrap();
+    assert_eq!(layout.immutable_slots(), vec![
+        ImmutableSlot { name: "owner".to_string(), offset: 3, width: 20 },
+        ImmutableSlot { name: "fee".to_string(), offset: 25, width: 32 },
+    ]);
+}
+
+#[test]
+fn test_constructor_fills_full_word_immutable() {
+    let initcode = evm_contract!(
+        immutables = ["owner"],
+        ["caller"],
+        ["immutable:owner", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    let (code, output) = deploy_and_call(initcode);
+    assert_eq!(code[0], 0x7f);
+    assert_eq!(output.to_vec(), address_word(DEFAULT_CALLER));
+}
+
+#[test]
+fn test_narrow_immutables_keep_surrounding_code() {
+    // owner is used twice; fee is a 2-byte slot right after the first owner slot
+    let initcode = evm_contract!(
+        immutables = ["owner", "fee"],
+        [0x1234, "caller"],
+        [
+            "immutable:owner:20", 0x00, "mstore",
+            "immutable:fee:2", 0x20, "mstore",
+            "immutable:owner:20", 0x40, "mstore",
+            0x60, 0x00, "return"
+        ]
+    );
+
+    let (_, output) = deploy_and_call(initcode);
+    let expected = [address_word(DEFAULT_CALLER), word(0x1234), address_word(DEFAULT_CALLER)].concat();
+    assert_eq!(output.to_vec(), expected);
+}
+
+#[test]
+fn test_oversized_value_is_cut_to_its_slot() {
+    let initcode = evm_contract!(
+        immutables = ["fee"],
+        [0xabcdef1234],
+        [0x2a, "pop", "immutable:fee:2", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    let (code, output) = deploy_and_call(initcode);
+    // PUSH1 0x2a, POP and the PUSH2 opcode before the slot are untouched
+    assert_eq!(code[..6], [0x60, 0x2a, 0x50, 0x61, 0x12, 0x34]);
+    assert_eq!(output.to_vec(), word(0x1234));
+}
+
+#[test]
+fn test_unused_immutable_value_is_dropped() {
+    let initcode = evm_contract!(
+        immutables = ["owner", "unused"],
+        [0x07, "caller"],
+        ["immutable:owner:20", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    let (_, output) = deploy_and_call(initcode);
+    assert_eq!(output.to_vec(), address_word(DEFAULT_CALLER));
+}
+
+#[test]
+fn test_undeclared_immutable_is_rejected() {
+    let runtime = vec![AsmElement::Immutable("owner".to_string(), 32)];
+    let err = Assembler::new()
+        .assemble_contract(&[], &runtime, &ContractOptions::default())
+        .unwrap_err();
+    assert!(matches!(err, AssemblerError::InvalidContract(_)));
+}
+
+#[test]
+fn test_json_source_immutables() {
+    let elements = parse_program(r#"["immutable:owner:20", "immutable:fee"]"#).unwrap();
+    assert_eq!(elements, vec![
+        AsmElement::Immutable("owner".to_string(), 20),
+        AsmElement::Immutable("fee".to_string(), 32),
+    ]);
+
+    let err = parse_program(r#"["immutable:owner:33"]"#).unwrap_err();
+    assert!(matches!(err, AssemblerError::InvalidContract(_)));
+}
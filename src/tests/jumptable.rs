@@ -0,0 +1,126 @@
+use crate::*;
+use super::{execute, lit, op, segment};
+use emasm_common::{jumptable::table_jump, source::parse_program};
+
+fn return_byte(value: u8) -> Vec<AsmElement> {
+    vec![lit(value), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")]
+}
+
+#[test]
+fn test_macro_dispatches_on_first_calldata_byte() {
+    let bytecode = evm_asm!([
+        0x00, "calldataload", 0xf8, "shr",
+        "jumptable:ops",
+        ["jumptable", "ops", ["op_a", "op_b", "op_c"]],
+        ["op_a", [0x0a, 0x00, "mstore", 0x20, 0x00, "return"]],
+        ["op_b", [0x0b, 0x00, "mstore", 0x20, 0x00, "return"]],
+        ["op_c", [0x0c, 0x00, "mstore", 0x20, 0x00, "return"]]
+    ]);
+
+    for (index, expected) in [(0u8, 0x0au8), (1, 0x0b), (2, 0x0c)] {
+        let output = execute(bytecode.clone(), &[index]);
+        assert_eq!(output[31], expected, "entry {}", index);
+    }
+}
+
+#[test]
+fn test_table_holds_two_byte_label_offsets() {
+    // Padding pushes the targets past offset 255
+    let elements = [
+        vec![op("calldatasize")],
+        table_jump("ops"),
+        vec![
+            AsmElement::BytesSegment("padding".to_string(), vec![0xfe; 300]),
+            AsmElement::JumpTable("ops".to_string(), vec!["b".to_string(), "a".to_string(), "b".to_string()]),
+            segment("a", return_byte(0x0a)),
+            segment("b", return_byte(0x0b)),
+        ],
+    ]
+    .concat();
+
+    let (bytecode, layout) = Assembler::new().assemble_with_layout(&elements).unwrap();
+    let table = &layout.bytes["ops"];
+    assert_eq!(table.size, 6);
+    let (a, b) = (layout.labels["a"] as u16, layout.labels["b"] as u16);
+    assert!(a > 0xff);
+    let expected: Vec<u8> = [b, a, b].iter().flat_map(|offset| offset.to_be_bytes()).collect();
+    assert_eq!(&bytecode[table.offset..table.offset + table.size], &expected);
+
+    // CALLDATASIZE is the index
+    assert_eq!(execute(bytecode.clone(), &[])[31], 0x0b);
+    assert_eq!(execute(bytecode, &[0xff])[31], 0x0a);
+}
+
+#[test]
+fn test_json_source() {
+    let program = parse_program(r#"[
+        "calldatasize", "jumptable:ops",
+        ["jumptable", "ops", ["zero", "one"]],
+        ["zero", [0, 0, "return"]],
+        ["one", ["ops", 0, "mstore", 32, 0, "return"]]
+    ]"#).unwrap();
+
+    assert_eq!(&program[1..14], table_jump("ops").as_slice());
+    assert_eq!(program[14], AsmElement::JumpTable("ops".to_string(), vec!["zero".to_string(), "one".to_string()]));
+    // A bare table name pushes its address
+    assert_eq!(
+        program[16],
+        segment("one", vec![
+            AsmElement::BytesPtr("ops".to_string()),
+            AsmElement::Literal(vec![]),
+            op("mstore"),
+            lit(0x20),
+            AsmElement::Literal(vec![]),
+            op("return"),
+        ])
+    );
+    assert!(Assembler::new().verify(&program).unwrap().is_empty());
+}
+
+#[test]
+fn test_unknown_table_entry_is_an_error() {
+    let elements = vec![
+        AsmElement::JumpTable("ops".to_string(), vec!["missing".to_string()]),
+    ];
+    let err = Assembler::new().assemble(&elements).unwrap_err();
+    assert!(matches!(err, AssemblerError::LabelNotFound(label) if label == "missing"));
+}
+
+#[test]
+fn test_table_entries_are_live_code() {
+    let elements = [
+        vec![op("calldatasize")],
+        table_jump("ops"),
+        vec![
+            AsmElement::JumpTable("ops".to_string(), vec!["a".to_string()]),
+            segment("a", return_byte(0x0a)),
+            segment("unused", return_byte(0x0b)),
+        ],
+    ]
+    .concat();
+
+    let assembler = Assembler::new();
+    let (stripped, report) = assembler.eliminate_dead_code(&elements).unwrap();
+    assert_eq!(report.removed_segments, vec!["unused".to_string()]);
+    assert!(report.removed_bytes_segments.is_empty());
+    assert!(stripped.contains(&segment("a", return_byte(0x0a))));
+}
+
+#[test]
+fn test_stack_checker_follows_table_entries() {
+    let elements = [
+        vec![lit(0x01), op("calldatasize")],
+        table_jump("ops"),
+        vec![
+            AsmElement::JumpTable("ops".to_string(), vec!["a".to_string()]),
+            // Entered with the 0x01 still on the stack
+            segment("a", return_byte(0x0a)),
+        ],
+    ]
+    .concat();
+
+    let issues = Assembler::new().check_stack(&elements);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path.to_string(), "a+5");
+    assert_eq!(issues[0].to_string(), "a+5: RETURN leaves 1 items on the stack");
+}
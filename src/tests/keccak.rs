@@ -0,0 +1,50 @@
+use crate::*;
+use emasm_common::{
+    abi::{error_selector, event_topic, selector},
+    source::parse_program,
+};
+
+#[test]
+fn test_sig_pushes_selector() {
+    let bytecode = evm_asm!(["sig:transfer(address,uint256)"]);
+    assert_eq!(bytecode, hex::decode("63a9059cbb").unwrap());
+}
+
+#[test]
+fn test_signatures_are_canonicalized() {
+    let compact = evm_asm!(["sig:transfer(address,uint256)"]);
+    let spaced = evm_asm!(["sig:transfer(address, uint)"]);
+    assert_eq!(compact, spaced);
+}
+
+#[test]
+fn test_event_pushes_full_topic() {
+    let bytecode = evm_asm!(["event:Transfer(address,address,uint256)"]);
+    let topic = hex::decode("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef").unwrap();
+    assert_eq!(bytecode[0], 0x7f);
+    assert_eq!(&bytecode[1..], topic.as_slice());
+    assert_eq!(event_topic("Transfer(address,address,uint256)").unwrap().to_vec(), topic);
+}
+
+#[test]
+fn test_error_pushes_selector() {
+    let bytecode = evm_asm!(["error:Error(string)"]);
+    assert_eq!(bytecode, hex::decode("6308c379a0").unwrap());
+    assert_eq!(error_selector("Error(string)").unwrap(), selector("Error(string)").unwrap());
+}
+
+#[test]
+fn test_json_source_hashes_signatures() {
+    let elements = parse_program(r#"["sig:balanceOf(address)", "event:Approval(address,address,uint256)"]"#).unwrap();
+    assert_eq!(elements[0], AsmElement::Literal(hex::decode("70a08231").unwrap()));
+    assert_eq!(
+        elements[1],
+        AsmElement::Literal(event_topic("Approval(address,address,uint256)").unwrap().to_vec())
+    );
+}
+
+#[test]
+fn test_invalid_signature_is_rejected() {
+    let err = parse_program(r#"["sig:transfer(address,uint7)"]"#).unwrap_err();
+    assert!(matches!(err, AssemblerError::InvalidSignature(_)));
+}
@@ -29,7 +29,7 @@ fn test_simple_jump_to_label() {
 #[test]
 fn test_conditional_jump_pattern() {
     // Pattern similar to what we're generating: check condition, jump if true
-    let bytecode = evm_asm!([
+    let bytecode = evm_asm!(stack_check = false, [
         0x01,
         "iszero",        // Check if zero
         "finish",        // Push finish address
@@ -4,7 +4,7 @@ use crate::*;
 fn test_256bit_hex_literal() {
     // Test a 256-bit hex literal (common for EVM masks)
     // This is the mask for rounding down to 32-byte boundary: ~31 = 0xffffffe0 extended to 256 bits
-    let bytecode = evm_asm!([
+    let bytecode = evm_asm!(stack_check = false, [
         0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe0,
         "and"
     ]);
@@ -34,7 +34,7 @@ fn test_256bit_hex_literal_in_interpolator() {
     let bytecode = builder(Box::new(100u64));
 
     // Should compile without errors and produce valid bytecode
-    assert!(bytecode.len() > 0);
+    assert!(!bytecode.is_empty());
 
     // Verify PUSH32 is present for the large constant
     assert!(bytecode.contains(&0x7f)); // PUSH32 opcode
@@ -68,14 +68,14 @@ fn test_mixed_size_literals() {
 #[test]
 fn test_256bit_not_mask() {
     // Another common pattern: NOT to create masks
-    let bytecode = evm_asm!([
+    let bytecode = evm_asm!(stack_check = false, [
         0x1f,  // 31
         "not", // ~31 = 0xffff...ffe0
         "and"
     ]);
 
     // This should work as an alternative to the large literal
-    assert!(bytecode.len() > 0);
+    assert!(!bytecode.is_empty());
     assert!(bytecode.contains(&0x19)); // NOT opcode
 }
 
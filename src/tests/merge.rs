@@ -0,0 +1,145 @@
+use crate::*;
+use super::{label, lit, op, run, segment};
+
+fn revert_with(code: u8) -> Vec<AsmElement> {
+    vec![lit(code), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("revert")]
+}
+
+/// Assert both programs behave the same for each calldata
+fn assert_same_behavior(before: &[AsmElement], after: &[AsmElement], inputs: &[&[u8]]) {
+    let assembler = Assembler::new();
+    let before = assembler.assemble(before).unwrap();
+    let after = assembler.assemble(after).unwrap();
+    for calldata in inputs {
+        let (expected, actual) = (run(before.clone(), calldata), run(after.clone(), calldata));
+        assert_eq!(
+            (actual.status, actual.output),
+            (expected.status, expected.output),
+            "calldata {:?}",
+            calldata
+        );
+    }
+}
+
+#[test]
+fn test_duplicate_segments_are_merged() {
+    let elements = vec![
+        op("callvalue"), label("no_value"), op("jumpi"),
+        op("calldatasize"), label("no_data"), op("jumpi"),
+        lit(0x2a), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return"),
+        segment("no_value", revert_with(0x01)),
+        segment("no_data", revert_with(0x01)),
+    ];
+
+    let assembler = Assembler::new();
+    let (merged, report) = assembler.merge_tails(&elements).unwrap();
+    assert_eq!(report.merged_segments, vec![("no_data".to_string(), "no_value".to_string())]);
+    // JUMPDEST plus ten bytes of body
+    assert_eq!(report.bytes_saved, 11);
+    // The reference to the removed segment now points at its twin
+    assert_eq!(&merged[..6], &[
+        op("callvalue"), label("no_value"), op("jumpi"),
+        op("calldatasize"), label("no_value"), op("jumpi"),
+    ]);
+    assert_eq!(merged.last(), Some(&segment("no_value", revert_with(0x01))));
+    assert_same_behavior(&elements, &merged, &[&[], &[0x01]]);
+}
+
+#[test]
+fn test_segment_entered_by_fall_through_becomes_a_jump() {
+    let elements = vec![
+        op("calldatasize"), label("a"), op("jumpi"),
+        // Execution falls into `b`, so it cannot simply disappear
+        lit(0x00), op("pop"),
+        segment("b", revert_with(0x07)),
+        segment("a", revert_with(0x07)),
+    ];
+
+    let assembler = Assembler::new();
+    let (merged, report) = assembler.merge_tails(&elements).unwrap();
+    assert_eq!(report.merged_segments, vec![("a".to_string(), "b".to_string())]);
+    assert_eq!(merged, vec![
+        op("calldatasize"), label("b"), op("jumpi"),
+        lit(0x00), op("pop"),
+        segment("b", revert_with(0x07)),
+    ]);
+    assert_same_behavior(&elements, &merged, &[&[], &[0x01]]);
+}
+
+#[test]
+fn test_repeated_epilogue_is_shared() {
+    let epilogue = [lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")];
+    let elements = vec![
+        op("calldatasize"), label("one"), op("jumpi"),
+        lit(0x01),
+    ]
+    .into_iter()
+    .chain(epilogue.clone())
+    .chain([
+        segment("one", [lit(0x00), op("calldataload"), label("two"), op("jumpi"), lit(0x02)]
+            .into_iter()
+            .chain(epilogue.clone())
+            .collect()),
+        segment("two", [lit(0x03)].into_iter().chain(epilogue.clone()).collect()),
+    ])
+    .collect::<Vec<_>>();
+
+    let assembler = Assembler::new();
+    let (merged, report) = assembler.merge_tails(&elements).unwrap();
+    assert_eq!(report.merged_tails, 1);
+    assert!(report.merged_segments.is_empty());
+    // The first copy is wrapped in a segment and the others jump to it
+    assert_eq!(&merged[..5], &[
+        op("calldatasize"), label("one"), op("jumpi"),
+        lit(0x01),
+        segment("tail_0", epilogue.to_vec()),
+    ]);
+    assert_eq!(merged[6], segment("two", vec![lit(0x03), label("tail_0"), op("jump")]));
+    // Two 8-byte copies become PUSH1 + JUMP, the kept copy gains a JUMPDEST
+    assert_eq!(report.bytes_saved, 2 * (8 - 3) - 1);
+
+    let mut word = [0u8; 32];
+    word[31] = 0x01;
+    assert_same_behavior(&elements, &merged, &[&[], &[0u8; 32], &word]);
+}
+
+#[test]
+fn test_tail_that_is_a_whole_segment_is_reused() {
+    let elements = vec![
+        op("calldatasize"), label("fail"), op("jumpi"),
+        lit(0x00), op("calldataload"), op("pop"),
+        lit(0x09), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("revert"),
+        segment("fail", revert_with(0x09)),
+    ];
+
+    let assembler = Assembler::new();
+    let (merged, report) = assembler.merge_tails(&elements).unwrap();
+    assert_eq!(report.merged_tails, 1);
+    assert_eq!(merged, vec![
+        op("calldatasize"), label("fail"), op("jumpi"),
+        lit(0x00), op("calldataload"), op("pop"),
+        label("fail"), op("jump"),
+        segment("fail", revert_with(0x09)),
+    ]);
+    assert_same_behavior(&elements, &merged, &[&[], &[0x01]]);
+}
+
+#[test]
+fn test_short_tails_are_left_alone() {
+    // Past offset 255 a jump is PUSH2 + JUMP, so replacing the 5-byte
+    // `0x00, 0x00, "return"` would not save anything
+    let elements = vec![
+        label("main"), op("jump"),
+        AsmElement::BytesSegment("padding".to_string(), vec![0xfe; 300]),
+        segment("main", vec![
+            op("calldatasize"), label("a"), op("jumpi"),
+            lit(0x01), lit(0x00), lit(0x00), op("return"),
+        ]),
+        segment("a", vec![lit(0x02), lit(0x00), lit(0x00), op("return")]),
+    ];
+
+    let (merged, report) = Assembler::new().merge_tails(&elements).unwrap();
+    assert_eq!(merged, elements);
+    assert_eq!(report.merged_tails, 0);
+    assert_eq!(report.bytes_saved, 0);
+}
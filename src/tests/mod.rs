@@ -1,3 +1,7 @@
+use crate::*;
+use crate::testing::{Outcome, Runner, Tx};
+use revm::primitives::{Address, Bytes, U256};
+
 mod basic_assembly;
 mod interpolation;
 mod revm_integration;
@@ -8,3 +12,78 @@ mod placeholder_size_test;
 mod exact_failing_case;
 mod large_hex_literals;
 mod nested_segments;
+mod stack_check;
+mod stack_slots;
+mod gas_estimate;
+mod verify;
+mod dead_code;
+mod optimize;
+mod constants;
+mod reorder;
+mod merge;
+mod jumptable;
+mod dispatch;
+mod keccak;
+mod contract;
+mod immutable;
+mod create2;
+mod child;
+mod data;
+mod offset;
+mod anchor;
+mod testing;
+mod trace;
+mod snapshot;
+mod properties;
+mod eof;
+mod eof_verify;
+
+// Fixtures shared by the test modules
+
+fn op(name: &str) -> AsmElement {
+    AsmElement::Opcode(name.to_string())
+}
+
+fn lit(value: u8) -> AsmElement {
+    AsmElement::Literal(vec![value])
+}
+
+fn label(name: &str) -> AsmElement {
+    AsmElement::Label(name.to_string())
+}
+
+fn segment(name: &str, inner: Vec<AsmElement>) -> AsmElement {
+    AsmElement::Segment(name.to_string(), inner)
+}
+
+/// `value` as a 32 byte big-endian word, e.g. for calldata.
+fn word(value: u64) -> Vec<u8> {
+    U256::from(value).to_be_bytes::<32>().to_vec()
+}
+
+/// Address [`run`] installs code at.
+const CONTRACT: Address = Address::repeat_byte(0x42);
+
+/// Call `code`, installed at [`CONTRACT`] of a fresh [`Runner`], with `calldata`.
+fn run(code: Vec<u8>, calldata: &[u8]) -> Outcome {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, code);
+    runner.call(CONTRACT, Tx::default().data(calldata.to_vec()))
+}
+
+/// Return data of [`run`], which must succeed.
+fn execute(code: Vec<u8>, calldata: &[u8]) -> Bytes {
+    let outcome = run(code, calldata);
+    assert!(outcome.is_success(), "{}", outcome);
+    outcome.output
+}
+
+/// Deploy `initcode` and call the contract without calldata; returns its
+/// runtime code and the call's return data.
+fn deploy_and_call(initcode: Vec<u8>) -> (Bytes, Bytes) {
+    let mut runner = Runner::new();
+    let address = runner.deploy(initcode);
+    let outcome = runner.call(address, Tx::default());
+    assert!(outcome.is_success(), "{}", outcome);
+    (runner.code(address), outcome.output)
+}
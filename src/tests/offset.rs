@@ -0,0 +1,125 @@
+use crate::*;
+use super::execute;
+use emasm_common::{
+    offset::OffsetExpr,
+    source::parse_program,
+    AssemblerError,
+};
+use revm::primitives::U256;
+
+fn start(name: &str) -> Box<OffsetExpr> {
+    Box::new(OffsetExpr::Start(name.to_string()))
+}
+
+#[test]
+fn test_bytes_pointer_plus_constant() {
+    let bytecode = evm_asm!([
+        "bytes:data:ptr+0x20", "stop",
+        ["bytes:data", "fill:64"]
+    ]);
+
+    // PUSH1 3 + 0x20, STOP, data
+    assert_eq!(&bytecode[..3], &[0x60, 0x23, 0x00]);
+    assert_eq!(bytecode.len(), 3 + 64);
+}
+
+#[test]
+fn test_read_struct_field() {
+    // Copy the second word of a table and return it
+    let bytecode = evm_asm!([
+        0x20, "bytes:table:ptr + 0x20", 0x00, "codecopy",
+        0x20, 0x00, "return",
+        ["bytes:table", "fill:31", "0x01", "fill:31", "0x02"]
+    ]);
+
+    let output = execute(bytecode, &[]);
+    assert_eq!(U256::from_be_slice(&output), U256::from(2));
+}
+
+#[test]
+fn test_code_segment_end_and_size() {
+    let bytecode = evm_asm!([
+        "body:size", "body:end", "stop",
+        ["body", [0x01, "pop", "stop"]]
+    ]);
+
+    // body starts at 5 and is JUMPDEST, PUSH1 1, POP, STOP
+    assert_eq!(hex::encode(bytecode), "6005600a005b60015000");
+}
+
+#[test]
+fn test_distance_between_labels() {
+    let bytecode = evm_asm!([
+        "end - start", "(end - start) * 2", "stop",
+        ["start", ["stop"]],
+        ["middle", [0x01, "stop"]],
+        ["end", ["stop"]]
+    ]);
+
+    // start at 5, end at 5 + 2 + 4 = 11
+    assert_eq!(&bytecode[..5], &[0x60, 0x06, 0x60, 0x0c, 0x00]);
+}
+
+#[test]
+fn test_pushed_at_minimal_width() {
+    let small = evm_asm!(["bytes:data:end", "stop", ["bytes:data", "fill:252"]]);
+    assert_eq!(&small[..2], &[0x60, 0xff]);
+
+    // One more byte pushes the end past 0xff, which widens the push itself
+    let wide = evm_asm!(["bytes:data:end", "stop", ["bytes:data", "fill:253"]]);
+    assert_eq!(&wide[..3], &[0x61, 0x01, 0x01]);
+    assert_eq!(wide.len(), 0x101);
+}
+
+#[test]
+fn test_constructor_finds_its_arguments() {
+    // The constructor arguments start where the runtime ends
+    let initcode = evm_contract!(["runtime:end", "pop"], [0x00, "stop"]);
+
+    assert_eq!(initcode[0], 0x60);
+    assert_eq!(initcode[1] as usize, initcode.len());
+}
+
+#[test]
+fn test_json_offsets() {
+    let program = parse_program(r#"[
+        "end - start", "runtime:size", "bytes:data:ptr+0x20", "bytes:data:ptr", "sig:transfer(address,uint256)"
+    ]"#).unwrap();
+
+    assert_eq!(program[0], AsmElement::Offset(OffsetExpr::Sub(start("end"), start("start"))));
+    assert_eq!(program[1], AsmElement::Offset(OffsetExpr::Size("runtime".to_string())));
+    assert_eq!(
+        program[2],
+        AsmElement::Offset(OffsetExpr::Add(start("data"), Box::new(OffsetExpr::Number(0x20))))
+    );
+    assert_eq!(program[3], AsmElement::BytesPtr("data".to_string()));
+    assert_eq!(program[4], AsmElement::Literal(vec![0xa9, 0x05, 0x9c, 0xbb]));
+}
+
+#[test]
+fn test_offset_errors() {
+    let assembler = Assembler::new();
+
+    let negative = parse_program(r#"["start - end", ["start", ["stop"]], ["end", ["stop"]]]"#).unwrap();
+    assert!(matches!(assembler.assemble(&negative), Err(AssemblerError::InvalidOffset(_))));
+
+    let unknown = parse_program(r#"["missing:end", "stop"]"#).unwrap();
+    assert!(matches!(assembler.assemble(&unknown), Err(AssemblerError::LabelNotFound(_))));
+
+    for invalid in ["start +", "(start - end", "start:middle + 1", "start - * 2"] {
+        assert!(matches!(OffsetExpr::parse(invalid), Err(AssemblerError::InvalidOffset(_))), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_hyphenated_label_is_not_an_expression() {
+    let bytecode = evm_asm!([
+        "copy-loop", "jump",
+        ["copy-loop", [0x2a, 0x00, "mstore", 0x20, 0x00, "return"]]
+    ]);
+    assert_eq!(U256::from_be_slice(&execute(bytecode, &[])), U256::from(42));
+
+    let program = parse_program(r#"["copy-loop", "jump", ["copy-loop", ["stop"]]]"#).unwrap();
+    assert_eq!(program[0], AsmElement::Label("copy-loop".to_string()));
+    assert_eq!(hex::encode(Assembler::new().assemble(&program).unwrap()), "6003565b00");
+}
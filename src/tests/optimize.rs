@@ -0,0 +1,162 @@
+use crate::*;
+use super::{execute, lit, op, run};
+use emasm_common::peephole::{Objective, OptimizeOptions};
+
+/// Store the top of the stack at 0x00 and return it
+fn return_top() -> Vec<AsmElement> {
+    vec![lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")]
+}
+
+/// Optimize, check the result against revm and return the optimized program
+fn optimize_checked(body: Vec<AsmElement>, options: &OptimizeOptions) -> Vec<AsmElement> {
+    let assembler = Assembler::new();
+    let optimized = assembler.optimize(&body, options).unwrap();
+
+    let before = assembler.assemble(&body).unwrap();
+    let after = assembler.assemble(&optimized).unwrap();
+    let expected = run(before.clone(), &[]);
+    let actual = run(after.clone(), &[]);
+    assert!(expected.is_success(), "{}", expected);
+    assert_eq!(actual.output, expected.output, "optimized program returns a different value");
+    assert!(after.len() <= before.len() || actual.gas_used < expected.gas_used);
+    optimized
+}
+
+/// Peephole rewrites without constant materialization, to keep literals comparable
+fn rewrites_only() -> OptimizeOptions {
+    OptimizeOptions { materialize_constants: false, ..OptimizeOptions::default() }
+}
+
+fn program(head: Vec<AsmElement>) -> Vec<AsmElement> {
+    head.into_iter().chain(return_top()).collect()
+}
+
+#[test]
+fn test_push_pop_removed() {
+    let optimized = optimize_checked(
+        program(vec![lit(0x42), lit(0x07), op("pop")]),
+        &rewrites_only(),
+    );
+    assert_eq!(optimized, program(vec![lit(0x42)]));
+}
+
+#[test]
+fn test_immutable_pop_kept() {
+    // The constructor patches the slot, so it must survive even when popped
+    let body = program(vec![lit(0x42), AsmElement::Immutable("owner".to_string(), 20), op("pop")]);
+    let optimized = optimize_checked(body.clone(), &rewrites_only());
+    assert_eq!(optimized, body);
+}
+
+#[test]
+fn test_swap_pair_cancelled() {
+    let optimized = optimize_checked(
+        program(vec![op("caller"), op("callvalue"), op("swap1"), op("swap1"), op("sub")]),
+        &rewrites_only(),
+    );
+    assert_eq!(optimized, program(vec![op("caller"), op("callvalue"), op("sub")]));
+}
+
+#[test]
+fn test_dup_pop_removed() {
+    let optimized = optimize_checked(
+        program(vec![op("caller"), op("dup1"), op("pop")]),
+        &rewrites_only(),
+    );
+    assert_eq!(optimized, program(vec![op("caller")]));
+}
+
+#[test]
+fn test_constant_chain_folded() {
+    // ((1 + 2) * 3) << 4
+    let optimized = optimize_checked(
+        program(vec![
+            lit(0x01), lit(0x02), op("add"),
+            lit(0x03), op("mul"),
+            lit(0x04), op("shl"),
+        ]),
+        &rewrites_only(),
+    );
+    assert_eq!(optimized, program(vec![lit(0x90)]));
+}
+
+#[test]
+fn test_fold_respects_operand_order() {
+    // SUB and DIV take the top of the stack as the left operand
+    let optimized = optimize_checked(
+        program(vec![lit(0x03), lit(0x0c), op("div"), lit(0x01), op("swap1"), op("sub")]),
+        &rewrites_only(),
+    );
+    assert_eq!(optimized[0], lit(0x04));
+}
+
+#[test]
+fn test_objective_decides_growing_folds() {
+    // 3 - 10 wraps around to a 32-byte constant: smaller code, cheaper execution
+    let body = program(vec![lit(0x0a), lit(0x03), op("sub")]);
+
+    let size = OptimizeOptions { objective: Objective::Size, ..rewrites_only() };
+    let for_size = optimize_checked(body.clone(), &size);
+    assert_eq!(for_size, body);
+
+    let gas = OptimizeOptions { objective: Objective::Gas, ..rewrites_only() };
+    let for_gas = optimize_checked(body, &gas);
+    assert_eq!(for_gas[0], AsmElement::Literal([vec![0xff; 31], vec![0xf9]].concat()));
+}
+
+#[test]
+fn test_double_iszero_before_jumpi_collapsed() {
+    let body = vec![
+        op("callvalue"), lit(0x01), op("add"),
+        op("iszero"), op("iszero"),
+        AsmElement::Label("nonzero".to_string()),
+        op("jumpi"),
+        lit(0x00), lit(0x00), op("revert"),
+        AsmElement::Segment("nonzero".to_string(), program(vec![lit(0x01)])),
+    ];
+    let optimized = optimize_checked(body, &rewrites_only());
+    assert_eq!(&optimized[3..5], &[AsmElement::Label("nonzero".to_string()), op("jumpi")]);
+}
+
+#[test]
+fn test_disabled_passes_are_skipped() {
+    let body = program(vec![lit(0x42), lit(0x07), op("pop"), lit(0x01), lit(0x02), op("add"), op("pop")]);
+    let options = OptimizeOptions {
+        remove_push_pop: false,
+        fold_constants: false,
+        ..rewrites_only()
+    };
+    assert_eq!(optimize_checked(body.clone(), &options), body);
+}
+
+#[test]
+fn test_rewrites_do_not_cross_jumpdest() {
+    let body = vec![
+        lit(0x07),
+        AsmElement::Segment("next".to_string(), vec![op("pop"), lit(0x01)]),
+    ]
+    .into_iter()
+    .chain(return_top())
+    .collect::<Vec<_>>();
+    assert_eq!(optimize_checked(body.clone(), &rewrites_only()), body);
+}
+
+#[test]
+fn test_macro_option_optimizes() {
+    let plain = evm_asm!([0x02, 0x03, "mul", 0x05, "pop", 0x00, "mstore", 0x20, 0x00, "return"]);
+    let optimized = evm_asm!(
+        optimize = "gas",
+        [0x02, 0x03, "mul", 0x05, "pop", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+
+    // Without a fork the optimizer keeps to Frontier opcodes, so no PUSH0
+    assert_eq!(optimized, vec![0x60, 0x06, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+    let cancun = evm_asm!(
+        optimize = "gas",
+        fork = "cancun",
+        [0x02, 0x03, "mul", 0x05, "pop", 0x00, "mstore", 0x20, 0x00, "return"]
+    );
+    assert_eq!(cancun, vec![0x60, 0x06, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3]);
+    assert_eq!(execute(plain.clone(), &[]), execute(optimized, &[]));
+    assert_eq!(execute(plain, &[]), execute(cancun, &[]));
+}
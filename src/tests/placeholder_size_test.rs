@@ -54,7 +54,7 @@ fn test_placeholder_size_calculation() {
     
     if jump_pos >= 2 {
         let push_opcode = bytecode_with_addr[jump_pos - 2];
-        if push_opcode >= 0x60 && push_opcode <= 0x7f {
+        if (0x60..=0x7f).contains(&push_opcode) {
             let target = bytecode_with_addr[jump_pos - 1] as usize;
             eprintln!("Target from PUSH: {}", target);
             eprintln!("Expected target (JUMPDEST position): {}", jumpdest_pos_with_addr);
@@ -0,0 +1,378 @@
+//! Property tests that assemble random programs and check invariants of the
+//! output, and that the optimization passes leave behaviour unchanged.
+use crate::*;
+use super::{lit, op};
+use crate::testing::{Runner, Tx};
+use emasm_common::{
+    cfg::{InstrKind, PushOperand},
+    disasm::{disassemble, reassemble, Instruction},
+    layout::Layout,
+    offset::OffsetExpr,
+    opcodes::Opcode,
+    peephole::OptimizeOptions,
+};
+use proptest::{collection::vec, prelude::*, sample::select};
+use revm::primitives::Address;
+use std::collections::HashMap;
+
+const CONTRACT: Address = Address::repeat_byte(0x42);
+
+/// Stack-neutral work on the accumulator, the only item on the stack.
+#[derive(Debug, Clone)]
+enum Step {
+    /// `PUSH literal; op`
+    Apply(Vec<u8>, &'static str),
+    /// Copy a data segment to memory and add its first word.
+    LoadData(usize),
+    /// Copy the code from a data segment to the end anchor, or the segment
+    /// alone without one, and add the segment's leading bytes.
+    LoadTail(usize),
+    /// Jump to a later block if the literal is below the accumulator.
+    JumpIf(Vec<u8>, usize),
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    steps: Vec<Step>,
+    /// Later block the block jumps to; the last block returns instead.
+    exit: usize,
+    /// Leave off the jump when the code that follows is a later block or the
+    /// end of the code, which is an implicit STOP.
+    open: bool,
+    /// Earlier block, or the root, whose body contains this block's segment.
+    parent: usize,
+    /// Order among the segments of the parent body.
+    key: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Data {
+    bytes: Vec<u8>,
+    /// Block whose body contains the data segment.
+    parent: usize,
+    key: u32,
+}
+
+/// Blocks run in increasing order, so every program terminates. Block 0 is
+/// the root, the others are segments nested at random depths, and data
+/// segments sit after the terminating instructions of any block. A block
+/// may instead fall into the block laid out after it, or off the end of the
+/// code.
+#[derive(Debug, Clone)]
+struct Program {
+    initial: Vec<u8>,
+    blocks: Vec<Block>,
+    data: Vec<Data>,
+    /// Whether the root ends with the `end` anchor.
+    anchored: bool,
+}
+
+/// A block or data segment, in the order they are laid out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Item {
+    Block(usize),
+    Data(usize),
+}
+
+/// Empty (PUSH1 0), one-byte and up to 32-byte literals, leading zeros included.
+fn literal() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![Just(vec![]), any::<u8>().prop_map(|b| vec![b]), vec(any::<u8>(), 1..=32)]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        (literal(), select(vec!["add", "sub", "mul", "xor", "or", "and"])).prop_map(|(l, op)| Step::Apply(l, op)),
+        any::<usize>().prop_map(Step::LoadData),
+        any::<usize>().prop_map(Step::LoadTail),
+        (literal(), any::<usize>()).prop_map(|(l, target)| Step::JumpIf(l, target)),
+    ]
+}
+
+/// Data without PUSH opcodes, whose immediates would swallow a JUMPDEST
+/// after the segment (which `verify` reports).
+fn data_bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>().prop_map(|b| if (0x60..=0x7f).contains(&b) { b ^ 0x80 } else { b }), 1..48)
+}
+
+fn program() -> impl Strategy<Value = Program> {
+    (1..7usize, 1..4usize).prop_flat_map(|(block_count, data_count)| {
+        (
+            literal(),
+            vec((vec(step(), 0..6), any::<usize>(), any::<bool>(), any::<usize>(), any::<u32>()), block_count),
+            vec((data_bytes(), any::<usize>(), any::<u32>()), data_count),
+            any::<bool>(),
+        )
+            .prop_map(move |(initial, raw_blocks, raw_data, anchored)| {
+                let blocks = raw_blocks
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (steps, exit, open, parent, key))| Block {
+                        steps,
+                        exit: if i + 1 < block_count { i + 1 + exit % (block_count - i - 1) } else { i },
+                        open,
+                        parent: if i == 0 { 0 } else { parent % i },
+                        key,
+                    })
+                    .collect();
+                let data = raw_data
+                    .into_iter()
+                    .map(|(bytes, parent, key)| Data { bytes, parent: parent % block_count, key })
+                    .collect();
+                Program { initial, blocks, data, anchored }
+            })
+    })
+}
+
+impl Program {
+    fn elements(&self) -> Vec<AsmElement> {
+        self.body(0)
+    }
+
+    fn data_segments(&self) -> HashMap<String, Vec<u8>> {
+        self.data.iter().enumerate().map(|(i, data)| (format!("d{}", i), data.bytes.clone())).collect()
+    }
+
+    /// Blocks and data segments in layout order: each block's own code,
+    /// then its nested segments by key.
+    fn items(&self) -> Vec<Item> {
+        let mut items = Vec::new();
+        self.collect_items(0, &mut items);
+        items
+    }
+
+    fn collect_items(&self, index: usize, items: &mut Vec<Item>) {
+        items.push(Item::Block(index));
+        let mut children: Vec<(u32, Item)> = Vec::new();
+        for (i, child) in self.blocks.iter().enumerate().skip(1) {
+            if child.parent == index {
+                children.push((child.key, Item::Block(i)));
+            }
+        }
+        for (i, data) in self.data.iter().enumerate() {
+            if data.parent == index {
+                children.push((data.key, Item::Data(i)));
+            }
+        }
+        children.sort_by_key(|(key, _)| *key);
+        for (_, child) in children {
+            match child {
+                Item::Block(i) => self.collect_items(i, items),
+                item => items.push(item),
+            }
+        }
+    }
+
+    /// Whether block `index` falls through: into a later block, or off the
+    /// end of the code.
+    fn falls_through(&self, index: usize) -> bool {
+        let items = self.items();
+        let position = items.iter().position(|&item| item == Item::Block(index)).unwrap();
+        self.blocks[index].open
+            && match items.get(position + 1) {
+                Some(Item::Block(next)) => *next > index,
+                Some(Item::Data(_)) => false,
+                None => true,
+            }
+    }
+
+    /// Whether execution can stop at the end of the code, with no output.
+    fn falls_off_end(&self) -> bool {
+        match self.items().last() {
+            Some(Item::Block(index)) => self.falls_through(*index),
+            _ => false,
+        }
+    }
+
+    fn body(&self, index: usize) -> Vec<AsmElement> {
+        let block = &self.blocks[index];
+        let last = index + 1 == self.blocks.len();
+        let mut body = Vec::new();
+        if index == 0 {
+            body.push(AsmElement::Literal(self.initial.clone()));
+        }
+        for step in &block.steps {
+            match step {
+                Step::Apply(literal, name) => body.extend([AsmElement::Literal(literal.clone()), op(name)]),
+                Step::LoadData(data) => {
+                    let name = format!("d{}", data % self.data.len());
+                    body.extend([
+                        AsmElement::BytesSize(name.clone()),
+                        AsmElement::BytesPtr(name),
+                        lit(0),
+                        op("codecopy"),
+                        lit(0),
+                        op("mload"),
+                        op("add"),
+                    ]);
+                }
+                Step::LoadTail(data) => {
+                    let name = format!("d{}", data % self.data.len());
+                    let start = Box::new(OffsetExpr::Start(name.clone()));
+                    let size = match self.anchored {
+                        true => OffsetExpr::Sub(Box::new(OffsetExpr::Start("end".to_string())), start.clone()),
+                        false => OffsetExpr::Size(name.clone()),
+                    };
+                    let leading = self.data[data % self.data.len()].bytes.len().min(32);
+                    let mut mask = vec![0xff; leading];
+                    mask.resize(32, 0);
+                    body.extend([
+                        AsmElement::Offset(size),
+                        AsmElement::Offset(*start),
+                        // Past the word LoadData reads, which the code would overwrite
+                        lit(0x40),
+                        op("codecopy"),
+                        lit(0x40),
+                        op("mload"),
+                        AsmElement::Literal(mask),
+                        op("and"),
+                        op("add"),
+                    ]);
+                }
+                Step::JumpIf(literal, target) if !last => {
+                    let target = index + 1 + target % (self.blocks.len() - index - 1);
+                    body.extend([
+                        op("dup1"),
+                        AsmElement::Literal(literal.clone()),
+                        op("lt"),
+                        AsmElement::Label(format!("b{}", target)),
+                        op("jumpi"),
+                    ]);
+                }
+                Step::JumpIf(..) => {}
+            }
+        }
+        if self.falls_through(index) {
+            // Into the next block, or off the end of the code
+        } else if last {
+            body.extend([lit(0), op("mstore"), lit(0x20), lit(0), op("return")]);
+        } else {
+            body.extend([AsmElement::Label(format!("b{}", block.exit)), op("jump")]);
+        }
+
+        let mut children: Vec<(u32, AsmElement)> = Vec::new();
+        for (i, child) in self.blocks.iter().enumerate().skip(1) {
+            if child.parent == index {
+                children.push((child.key, AsmElement::Segment(format!("b{}", i), self.body(i))));
+            }
+        }
+        for (i, data) in self.data.iter().enumerate() {
+            if data.parent == index {
+                children.push((data.key, AsmElement::BytesSegment(format!("d{}", i), data.bytes.clone())));
+            }
+        }
+        children.sort_by_key(|(key, _)| *key);
+        body.extend(children.into_iter().map(|(_, child)| child));
+        if index == 0 && self.anchored {
+            body.push(AsmElement::Anchor("end".to_string()));
+        }
+        body
+    }
+}
+
+fn pushed_value(code: &[u8], offset: usize, size: usize) -> usize {
+    code[offset + 1..offset + size].iter().fold(0, |value, &b| value << 8 | b as usize)
+}
+
+/// Invariants of assembled code, whatever passes produced the program.
+fn check_layout(code: &[u8], layout: &Layout, data: &HashMap<String, Vec<u8>>) -> Result<(), TestCaseError> {
+    // Linear-sweep disassembly round-trips and agrees with the layout
+    let instructions = disassemble(code);
+    prop_assert_eq!(&reassemble(&instructions), code);
+    let at: HashMap<usize, &Instruction> = instructions.iter().map(|i| (i.pc, i)).collect();
+
+    for entry in &layout.entries {
+        let expected_opcode = match &entry.kind {
+            InstrKind::Jumpdest(_) => Opcode::JUMPDEST,
+            InstrKind::Op(opcode) => *opcode,
+            InstrKind::Push(_) => Opcode(Opcode::PUSH1.0 + entry.size as u8 - 2),
+            _ => continue,
+        };
+        let Some(instruction) = at.get(&entry.offset) else {
+            return Err(TestCaseError::fail(format!("{} at {:#x} is not an instruction boundary", entry.path, entry.offset)));
+        };
+        prop_assert_eq!(instruction.opcode, expected_opcode, "{}", entry.path);
+        prop_assert_eq!(instruction.size(), entry.size, "{}", entry.path);
+
+        let value = || pushed_value(code, entry.offset, entry.size);
+        match &entry.kind {
+            InstrKind::Push(PushOperand::Label(label)) => {
+                prop_assert_eq!(value(), layout.labels[label], "{}", label);
+                prop_assert_eq!(code[value()], Opcode::JUMPDEST.0, "{} is not a JUMPDEST", label);
+            }
+            InstrKind::Push(PushOperand::BytesPtr(name)) => prop_assert_eq!(value(), layout.bytes[name].offset),
+            InstrKind::Push(PushOperand::BytesSize(name)) => prop_assert_eq!(value(), layout.bytes[name].size),
+            _ => {}
+        }
+    }
+
+    for (name, info) in &layout.bytes {
+        prop_assert_eq!(&code[info.offset..info.offset + info.size], &data[name][..], "{}", name);
+    }
+    Ok(())
+}
+
+fn run(code: Vec<u8>) -> (bool, Vec<u8>) {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, code);
+    let outcome = runner.call(CONTRACT, Tx::default());
+    (outcome.is_success(), outcome.output.to_vec())
+}
+
+/// Dead code elimination, peephole optimization, tail merging and segment
+/// reordering, each on when its bit of `passes` is set.
+fn apply_passes(assembler: &Assembler, elements: &[AsmElement], passes: u8) -> Vec<AsmElement> {
+    let mut program = elements.to_vec();
+    if passes & 1 != 0 {
+        program = assembler.eliminate_dead_code(&program).unwrap().0;
+    }
+    if passes & 2 != 0 {
+        program = assembler.optimize(&program, &OptimizeOptions::default()).unwrap();
+    }
+    if passes & 4 != 0 {
+        program = assembler.merge_tails(&program).unwrap().0;
+    }
+    if passes & 8 != 0 {
+        program = assembler.reorder_segments(&program).unwrap();
+    }
+    program
+}
+
+proptest! {
+    #[test]
+    fn prop_assembled_layout_is_consistent(program in program()) {
+        let assembler = Assembler::new();
+        let elements = program.elements();
+        let (code, layout) = assembler.assemble_with_layout(&elements).unwrap();
+
+        prop_assert!(assembler.verify(&elements).unwrap().is_empty());
+        check_layout(&code, &layout, &program.data_segments())?;
+        let (success, output) = run(code);
+        prop_assert!(success);
+        prop_assert!(output.len() == 32 || output.is_empty() && program.falls_off_end());
+    }
+
+    #[test]
+    fn prop_passes_preserve_behaviour(program in program()) {
+        let assembler = Assembler::new();
+        let elements = program.elements();
+        let expected = run(assembler.assemble(&elements).unwrap());
+
+        for passes in 1..16 {
+            let transformed = apply_passes(&assembler, &elements, passes);
+            let (code, layout) = assembler.assemble_with_layout(&transformed).unwrap();
+            check_layout(&code, &layout, &program.data_segments())?;
+            prop_assert_eq!(&run(code), &expected, "passes {:#06b}", passes);
+        }
+    }
+}
+
+#[test]
+fn test_disassemble() {
+    let code = [0x60, 0x2a, 0x5b, 0x0c, 0x61, 0x01];
+    let instructions = disassemble(&code);
+
+    let lines: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+    assert_eq!(lines, ["0x0000: PUSH1 0x2a", "0x0002: JUMPDEST", "0x0003: UNKNOWN(0x0c)", "0x0004: PUSH2 0x01"]);
+    assert!(instructions[3].is_truncated());
+    assert_eq!(reassemble(&instructions), code);
+}
@@ -0,0 +1,135 @@
+use crate::*;
+use emasm_common::offset::OffsetExpr;
+use super::{execute, label, lit, op, segment};
+
+fn segment_names(elements: &[AsmElement]) -> Vec<&str> {
+    elements
+        .iter()
+        .filter_map(|e| match e {
+            AsmElement::Segment(name, _) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_hot_segment_moves_before_cold_code() {
+    let mut elements = Vec::new();
+    for _ in 0..5 {
+        // Never taken, but each one pushes the label
+        elements.extend([lit(0x00), label("hot"), op("jumpi")]);
+    }
+    elements.extend([label("hot"), op("jump")]);
+    let cold: Vec<AsmElement> = (0..150).flat_map(|_| [lit(0x01), op("pop")]).chain([op("stop")]).collect();
+    elements.push(segment("cold", cold));
+    elements.push(segment("hot", vec![lit(0x2a), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")]));
+
+    let assembler = Assembler::new();
+    let reordered = assembler.reorder_segments(&elements).unwrap();
+    assert_eq!(segment_names(&reordered), vec!["hot", "cold"]);
+    // The entry's trailing jump into `hot` became a fall-through
+    assert!(!reordered.contains(&op("jump")));
+
+    let before = assembler.assemble(&elements).unwrap();
+    let after = assembler.assemble(&reordered).unwrap();
+    // Six PUSH2 become PUSH1 and the jump pair is gone
+    assert_eq!(before.len() - after.len(), 6 + 3);
+    assert_eq!(execute(before, &[]), execute(after, &[]));
+}
+
+#[test]
+fn test_fall_through_segments_stay_together() {
+    let elements = vec![
+        label("b"), label("b"), op("pop"), op("jump"),
+        segment("cold", (0..130).flat_map(|_| [lit(0x01), op("pop")]).chain([op("stop")]).collect()),
+        // `a` falls through into `b`
+        segment("a", vec![lit(0x01), op("pop")]),
+        segment("b", vec![lit(0x2a), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")]),
+    ];
+
+    let assembler = Assembler::new();
+    let reordered = assembler.reorder_segments(&elements).unwrap();
+    assert_eq!(segment_names(&reordered), vec!["a", "b", "cold"]);
+    assert_eq!(
+        execute(assembler.assemble(&elements).unwrap(), &[]),
+        execute(assembler.assemble(&reordered).unwrap(), &[])
+    );
+}
+
+#[test]
+fn test_jump_to_next_segment_is_dropped() {
+    let elements = vec![
+        label("next"),
+        op("jump"),
+        segment("next", vec![op("stop")]),
+    ];
+
+    let reordered = Assembler::new().reorder_segments(&elements).unwrap();
+    assert_eq!(reordered, vec![segment("next", vec![op("stop")])]);
+}
+
+#[test]
+fn test_layout_never_grows() {
+    let elements = vec![
+        label("b"),
+        op("jump"),
+        segment("a", vec![op("stop")]),
+        segment("b", vec![label("a"), op("jump")]),
+    ];
+
+    let assembler = Assembler::new();
+    let reordered = assembler.reorder_segments(&elements).unwrap();
+    assert!(assembler.assemble(&reordered).unwrap().len() <= assembler.assemble(&elements).unwrap().len());
+}
+
+/// Entry code that references `hot` often, then jumps to `cold`, a long
+/// segment, followed by `hot`.
+fn hot_after_cold(hot: Vec<AsmElement>) -> Vec<AsmElement> {
+    let mut elements = Vec::new();
+    for _ in 0..5 {
+        elements.extend([lit(0x00), label("hot"), op("jumpi")]);
+    }
+    elements.extend([label("cold"), op("jump")]);
+    let cold = (0..150).flat_map(|_| [lit(0x01), op("pop")])
+        .chain([lit(0x2a), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")]);
+    elements.push(segment("cold", cold.collect()));
+    elements.push(segment("hot", hot));
+    elements
+}
+
+#[test]
+fn test_code_running_off_the_end_stays_last() {
+    // `hot` ends without a terminator, so moving it up would fall into `cold`
+    let elements = hot_after_cold(vec![lit(0x42), op("pop")]);
+
+    let assembler = Assembler::new();
+    let reordered = assembler.reorder_segments(&elements).unwrap();
+    assert_eq!(segment_names(&reordered), vec!["cold", "hot"]);
+    assert_eq!(
+        execute(assembler.assemble(&elements).unwrap(), &[]),
+        execute(assembler.assemble(&reordered).unwrap(), &[])
+    );
+}
+
+#[test]
+fn test_layout_dependent_programs_are_unchanged() {
+    let hot = vec![lit(0x2a), lit(0x00), op("mstore"), lit(0x20), lit(0x00), op("return")];
+    let assembler = Assembler::new();
+    assert_eq!(segment_names(&assembler.reorder_segments(&hot_after_cold(hot.clone())).unwrap()), vec!["hot", "cold"]);
+
+    // The distance from `cold` to the end of `hot` changes when `hot` moves
+    let mut elements = hot_after_cold(hot.clone());
+    elements.insert(0, AsmElement::Offset(OffsetExpr::parse("hot:end - cold").unwrap()));
+    elements.insert(1, op("pop"));
+    assert_eq!(assembler.reorder_segments(&elements).unwrap(), elements);
+
+    // Within one segment, the distance moves with it
+    let mut elements = hot_after_cold(hot.clone());
+    elements.insert(0, AsmElement::Offset(OffsetExpr::parse("hot:end - hot").unwrap()));
+    elements.insert(1, op("pop"));
+    assert_eq!(segment_names(&assembler.reorder_segments(&elements).unwrap()), vec!["hot", "cold"]);
+
+    let mut elements = hot_after_cold(hot);
+    elements.push(AsmElement::Anchor("end".to_string()));
+    assert_eq!(assembler.reorder_segments(&elements).unwrap(), elements);
+}
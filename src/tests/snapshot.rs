@@ -0,0 +1,88 @@
+use crate::*;
+use crate::testing::{
+    snapshot::{Diff, Measurement, ParseSnapshotError, Scenario, Snapshot},
+    Tx,
+};
+
+fn measurement(gas: u64, size: usize) -> Measurement {
+    Measurement { gas, size }
+}
+
+fn snapshot(entries: &[(&str, u64, usize)]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for (name, gas, size) in entries {
+        snapshot.record(name, measurement(*gas, *size));
+    }
+    snapshot
+}
+
+#[test]
+fn test_run_scenarios() {
+    let runtime = evm_asm!([0x00, "calldataload", 0x00, "sstore", "stop"]);
+    let initcode = evm_contract!([], [0x00, "calldataload", 0x00, "sstore", "stop"]);
+
+    let snapshot = Snapshot::run(&[
+        Scenario::call("store zero", runtime.clone(), Tx::default()),
+        Scenario::call("store one", runtime.clone(), Tx::default().data(vec![1; 32])),
+        Scenario::deploy("deploy", initcode.clone(), Tx::default()),
+    ])
+    .unwrap();
+
+    let zero = snapshot.get("store zero").unwrap();
+    let one = snapshot.get("store one").unwrap();
+    assert_eq!(zero.size, runtime.len());
+    assert_eq!(snapshot.get("deploy").unwrap().size, initcode.len());
+    // Setting a cold slot costs 22100 instead of 2200 for a no-op store, and calldata bytes cost 16 each
+    assert_eq!(one.gas - zero.gas, 22_100 - 2_200 + 32 * 16);
+}
+
+#[test]
+fn test_failed_scenario() {
+    let failed = Snapshot::run(&[Scenario::call("reverts", evm_asm!([0x00, 0x00, "revert"]), Tx::default())])
+        .unwrap_err();
+
+    assert_eq!(failed.name, "reverts");
+    assert!(failed.to_string().starts_with("scenario reverts failed: reverted"), "{}", failed);
+}
+
+#[test]
+fn test_snapshot_file_round_trip() {
+    let snapshot = snapshot(&[("transfer", 51234, 312), ("approve (max)", 46000, 312)]);
+    let text = snapshot.to_string();
+
+    assert_eq!(text, "approve (max) (gas: 46000, size: 312)\ntransfer (gas: 51234, size: 312)\n");
+    assert_eq!(Snapshot::parse(&text).unwrap(), snapshot);
+    assert_eq!(Snapshot::parse("a (gas: 1, size: 2)\n\nb (gas: x, size: 2)"), Err(ParseSnapshotError { line: 3 }));
+
+    let path = std::env::temp_dir().join(format!("emasm-snapshot-{}", std::process::id()));
+    assert_eq!(Snapshot::read(&path).unwrap(), Snapshot::new());
+    snapshot.write(&path).unwrap();
+    assert_eq!(Snapshot::read(&path).unwrap(), snapshot);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_compare_against_baseline() {
+    let baseline = snapshot(&[("cheaper", 1000, 10), ("pricier", 1000, 10), ("removed", 5, 1), ("same", 7, 1)]);
+    let current = snapshot(&[("added", 9, 1), ("cheaper", 990, 9), ("pricier", 1015, 12), ("same", 7, 1)]);
+
+    let strict = current.compare(&baseline, 0.0);
+    assert_eq!(strict.diffs.len(), 5);
+    assert_eq!(strict.changes().count(), 4);
+    let regressions: Vec<&Diff> = strict.regressions().collect();
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].name, "pricier");
+    assert_eq!(regressions[0].gas_delta(), Some(15));
+    assert!(!strict.is_ok());
+    assert_eq!(strict.to_string(), [
+        "added: new (gas: 9, size: 1)",
+        "cheaper: gas 1000 -> 990 (-10, -1.00%), size 10 -> 9 (-1)",
+        "pricier: gas 1000 -> 1015 (+15, +1.50%), size 10 -> 12 (+2)  [regression]",
+        "removed: removed",
+        "5 scenarios, 4 changed, 1 regressed over 0%",
+    ].join("\n"));
+
+    // 1.5% is within a 2% tolerance but not a 1% one
+    assert!(current.compare(&baseline, 2.0).is_ok());
+    assert!(!current.compare(&baseline, 1.0).is_ok());
+}
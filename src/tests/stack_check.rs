@@ -0,0 +1,130 @@
+use crate::*;
+use super::{label, lit, op};
+use emasm_common::stack::StackIssueKind;
+
+#[test]
+fn test_balanced_program_has_no_issues() {
+    let elements = vec![
+        lit(0x42),
+        lit(0x00),
+        op("mstore"),
+        lit(0x20),
+        lit(0x00),
+        op("return"),
+    ];
+
+    let issues = Assembler::new().check_stack(&elements);
+    assert!(issues.is_empty(), "unexpected issues: {:?}", issues);
+}
+
+#[test]
+fn test_underflow_reports_element_path() {
+    let elements = vec![
+        label("body"),
+        op("jump"),
+        AsmElement::Segment("body".to_string(), vec![lit(0x01), op("add"), op("stop")]),
+    ];
+
+    let issues = Assembler::new().check_stack(&elements);
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(issues[0].to_string(), "body+1: stack underflow: ADD needs 2 items, 1 available");
+    assert!(issues[0].kind.is_error());
+}
+
+#[test]
+fn test_dup_and_swap_deeper_than_stack() {
+    let elements = vec![lit(0x01), op("dup2"), op("swap2"), op("stop")];
+
+    let issues = Assembler::new().check_stack(&elements);
+    let kinds: Vec<_> = issues.iter().map(|i| &i.kind).collect();
+    assert!(matches!(kinds[0], StackIssueKind::TooDeep { opcode: "DUP2", depth: 2, available: 1 }));
+    assert!(matches!(kinds[1], StackIssueKind::TooDeep { opcode: "SWAP2", depth: 3, .. }));
+}
+
+#[test]
+fn test_overflow() {
+    let mut elements: Vec<AsmElement> = (0..1025).map(|_| lit(0x01)).collect();
+    elements.push(op("invalid"));
+
+    let issues = Assembler::new().check_stack(&elements);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].kind, StackIssueKind::Overflow { height: 1025 });
+    assert_eq!(issues[0].path.index, Some(1024));
+}
+
+#[test]
+fn test_inconsistent_heights_at_jumpdest() {
+    // One path reaches "join" with an extra item on the stack
+    let elements = vec![
+        lit(0x01),
+        label("join"),
+        op("jumpi"),
+        lit(0x02),
+        AsmElement::Segment("join".to_string(), vec![op("stop")]),
+    ];
+
+    let issues = Assembler::new().check_stack(&elements);
+    assert!(issues.iter().any(|i| matches!(
+        &i.kind,
+        StackIssueKind::InconsistentHeight { label, .. } if label == "join"
+    )), "{:?}", issues);
+    assert_eq!(issues[0].path.to_string(), "join");
+}
+
+#[test]
+fn test_items_left_at_return() {
+    let elements = vec![lit(0x01), lit(0x20), lit(0x00), op("return")];
+
+    let issues = Assembler::new().check_stack(&elements);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].kind, StackIssueKind::LeftOnStack { opcode: "RETURN", remaining: 1 });
+    assert_eq!(issues[0].path.to_string(), "<root>+3");
+}
+
+#[test]
+fn test_copy_loop_passes_stack_check() {
+    // Compiles only because the macro finds no stack errors
+    let bytecode = evm_asm!([
+        0x100,              // size
+        0x40,               // src
+        0x1000,             // dest
+        "copy_loop",
+        "jump",
+
+        ["copy_loop", [
+            "dup3",
+            "iszero",
+            "copy_done",
+            "jumpi",
+
+            "dup2",
+            "mload",
+            "dup2",
+            "mstore",
+
+            0x20,
+            "add",
+            "swap1",
+            0x20,
+            "add",
+            "swap1",
+            "swap2",
+            0x20,
+            "swap1",
+            "sub",
+            "swap2",
+
+            "copy_loop",
+            "jump"
+        ]],
+
+        ["copy_done", [
+            "pop",
+            "pop",
+            "pop",
+            "stop"
+        ]]
+    ]);
+
+    assert!(!bytecode.is_empty());
+}
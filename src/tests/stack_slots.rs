@@ -0,0 +1,129 @@
+use crate::*;
+
+#[test]
+fn test_named_copy_loop_matches_manual_dup_swap() {
+    let named = evm_asm!([
+        0x100,
+        0x40,
+        0x1000,
+        ["let", "dest", "src", "remaining"],
+        "copy_loop",
+        "jump",
+
+        ["copy_loop", [
+            "dup:remaining",
+            "iszero",
+            "copy_done",
+            "jumpi",
+
+            "dup:src",
+            "mload",
+            "dup:dest",
+            "mstore",
+
+            "dup:dest", 0x20, "add", "swap:dest", "pop",
+            "dup:src", 0x20, "add", "swap:src", "pop",
+            "dup:remaining", 0x20, "swap1", "sub", "swap:remaining", "pop",
+
+            "copy_loop",
+            "jump"
+        ]],
+
+        ["copy_done", [
+            "pop",
+            "pop",
+            "pop",
+            "stop"
+        ]]
+    ]);
+
+    let manual = evm_asm!([
+        0x100,
+        0x40,
+        0x1000,
+        "copy_loop",
+        "jump",
+
+        ["copy_loop", [
+            "dup3",
+            "iszero",
+            "copy_done",
+            "jumpi",
+
+            "dup2",
+            "mload",
+            "dup2",
+            "mstore",
+
+            "dup1", 0x20, "add", "swap1", "pop",
+            "dup2", 0x20, "add", "swap2", "pop",
+            "dup3", 0x20, "swap1", "sub", "swap3", "pop",
+
+            "copy_loop",
+            "jump"
+        ]],
+
+        ["copy_done", [
+            "pop",
+            "pop",
+            "pop",
+            "stop"
+        ]]
+    ]);
+
+    assert_eq!(hex::encode(&named), hex::encode(&manual));
+}
+
+#[test]
+fn test_names_follow_raw_swaps() {
+    let bytecode = evm_asm!([
+        0x01,
+        ["let", "a"],
+        0x02,
+        ["let", "b"],
+        "swap1",        // [a, b]
+        "dup:b",        // b is now second from the top
+        "stop"
+    ]);
+
+    // PUSH1 1, PUSH1 2, SWAP1, DUP2, STOP
+    assert_eq!(hex::encode(&bytecode), "60016002908100");
+}
+
+#[test]
+fn test_slot_too_deep_is_rejected() {
+    let mut elements = vec![
+        AsmElement::Literal(vec![0x01]),
+        AsmElement::Let(vec!["x".to_string()]),
+    ];
+    elements.extend((0..16).map(|_| AsmElement::Literal(vec![0x02])));
+
+    // SWAP16 still reaches the 17th item
+    let mut swap = elements.clone();
+    swap.push(AsmElement::SwapSlot("x".to_string()));
+    assert!(Assembler::new().assemble(&swap).is_ok());
+
+    elements.push(AsmElement::DupSlot("x".to_string()));
+    let err = Assembler::new().assemble(&elements).unwrap_err();
+    assert!(matches!(err, AssemblerError::StackSlotTooDeep(ref name, 17) if name == "x"));
+    assert_eq!(err.to_string(), "Stack slot x is 17 items deep, out of reach of DUP16/SWAP16");
+}
+
+#[test]
+fn test_names_disagreeing_at_join_are_dropped() {
+    // Both paths reach "join" with one item, but only one of them names it
+    let elements = vec![
+        AsmElement::Literal(vec![0x01]),
+        AsmElement::Literal(vec![0x00]),
+        AsmElement::Label("join".to_string()),
+        AsmElement::Opcode("jumpi".to_string()),
+        AsmElement::Let(vec!["x".to_string()]),
+        AsmElement::Segment("join".to_string(), vec![
+            AsmElement::DupSlot("x".to_string()),
+            AsmElement::Opcode("stop".to_string()),
+        ]),
+    ];
+
+    let err = Assembler::new().assemble(&elements).unwrap_err();
+    assert!(matches!(err, AssemblerError::UnknownStackSlot(ref name) if name == "x"));
+}
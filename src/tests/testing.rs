@@ -0,0 +1,164 @@
+use crate::*;
+use crate::testing::{Revert, Runner, Status, StorageChange, Tx, DEFAULT_CALLER};
+use revm::primitives::{keccak256, Address, Bytes, U256};
+
+const CONTRACT: Address = Address::repeat_byte(0x42);
+
+fn word(value: U256) -> Vec<u8> {
+    value.to_be_bytes::<32>().to_vec()
+}
+
+#[test]
+fn test_call_sees_calldata_caller_and_value() {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, evm_asm!([
+        0x00, "calldataload", 0x00, "mstore",
+        "caller", 0x20, "mstore",
+        "callvalue", 0x40, "mstore",
+        0x60, 0x00, "return"
+    ]));
+    let sender = Address::repeat_byte(0x07);
+    runner.set_balance(sender, U256::from(1000));
+
+    let outcome = runner.call(
+        CONTRACT,
+        Tx::default().from(sender).value(U256::from(5)).data(word(U256::from(9))),
+    );
+
+    assert!(outcome.is_success(), "{}", outcome);
+    assert_eq!(U256::from_be_slice(&outcome.output[..32]), U256::from(9));
+    assert_eq!(Address::from_slice(&outcome.output[44..64]), sender);
+    assert_eq!(U256::from_be_slice(&outcome.output[64..]), U256::from(5));
+    assert_eq!(runner.balance(sender), U256::from(995));
+    assert_eq!(runner.balance(CONTRACT), U256::from(5));
+}
+
+#[test]
+fn test_deploy_and_storage_changes() {
+    let mut runner = Runner::new();
+    let store = runner.deploy(evm_contract!([], [0x00, "calldataload", 0x01, "sstore", "stop"]));
+    assert_eq!(runner.code(store).to_vec(), evm_asm!([0x00, "calldataload", 0x01, "sstore", "stop"]));
+    runner.set_storage(store, U256::from(1), U256::from(3));
+
+    let outcome = runner.call(store, Tx::default().data(word(U256::from(4))));
+
+    assert!(outcome.is_success(), "{}", outcome);
+    assert_eq!(outcome.storage_changes, vec![StorageChange {
+        address: store,
+        slot: U256::from(1),
+        old: U256::from(3),
+        new: U256::from(4),
+    }]);
+    assert_eq!(runner.storage(store, U256::from(1)), U256::from(4));
+}
+
+#[test]
+fn test_revert_with_message() {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, evm_asm!([
+        "bytes:error:size", "dup1", "bytes:error:ptr", 0x00, "codecopy",
+        0x00, "revert",
+        // Error("hello")
+        ["bytes:error", "0x08c379a0", "fill:31", "0x20", "fill:31", "0x05", "utf8:hello", "fill:27"]
+    ]));
+
+    let outcome = runner.call(CONTRACT, Tx::default());
+
+    assert_eq!(outcome.status, Status::Revert);
+    assert_eq!(outcome.revert(), Some(Revert::Message("hello".to_string())));
+    assert_eq!(outcome.to_string(), format!("reverted ({} gas): Error(\"hello\")", outcome.gas_used));
+}
+
+#[test]
+fn test_revert_with_custom_error() {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, evm_asm!([
+        "error:Unauthorized(address)", 0xe0, "shl", 0x00, "mstore",
+        "caller", 0x04, "mstore",
+        0x24, 0x00, "revert"
+    ]));
+
+    let revert = runner.call(CONTRACT, Tx::default()).revert().unwrap();
+
+    assert!(revert.is("Unauthorized(address)"));
+    assert!(!revert.is("Unauthorized()"));
+    match revert {
+        Revert::Custom { args, .. } => assert_eq!(Address::from_slice(&args[12..]), DEFAULT_CALLER),
+        other => panic!("expected a custom error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_decode_revert_data() {
+    let mut panic = vec![0x4e, 0x48, 0x7b, 0x71];
+    panic.extend(word(U256::from(0x11)));
+
+    assert_eq!(Revert::decode(&[]), Revert::Empty);
+    assert_eq!(Revert::decode(&panic), Revert::Panic(U256::from(0x11)));
+    assert_eq!(Revert::decode(&[0xde, 0xad]), Revert::Raw(Bytes::from(vec![0xde, 0xad])));
+    // A truncated Error(string) is left undecoded
+    assert!(matches!(Revert::decode(&[0x08, 0xc3, 0x79, 0xa0, 0x00]), Revert::Custom { .. }));
+}
+
+#[test]
+fn test_logs() {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, evm_asm!([
+        0x2a, 0x00, "mstore",
+        "event:Ping(uint256)", 0x20, 0x00, "log1",
+        "stop"
+    ]));
+
+    let outcome = runner.call(CONTRACT, Tx::default());
+
+    assert_eq!(outcome.logs.len(), 1);
+    let log = &outcome.logs[0];
+    assert_eq!(log.address, CONTRACT);
+    assert_eq!(log.topics(), &[keccak256("Ping(uint256)")]);
+    assert_eq!(log.data.data.to_vec(), word(U256::from(42)));
+}
+
+#[test]
+fn test_contracts_call_each_other() {
+    let mut runner = Runner::new();
+    let callee = runner.deploy(evm_contract!([], [0x2a, 0x00, "mstore", 0x20, 0x00, "return"]));
+    let caller = runner.deploy(evm_contract!([], [
+        // STATICCALL the address in the calldata and return what it returned, plus one
+        0x20, 0x00, 0x00, 0x00, 0x00, "calldataload", "gas", "staticcall", "pop",
+        0x00, "mload", 0x01, "add", 0x00, "mstore",
+        0x20, 0x00, "return"
+    ]));
+    assert_ne!(callee, caller);
+
+    let outcome = runner.call(caller, Tx::default().data(word(U256::from_be_slice(callee.as_slice()))));
+
+    assert!(outcome.is_success(), "{}", outcome);
+    assert_eq!(outcome.word(), U256::from(43));
+}
+
+#[test]
+fn test_block_env() {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, evm_asm!(["number", 0x00, "mstore", "timestamp", 0x20, "mstore", 0x40, 0x00, "return"]));
+    runner.block().number = U256::from(1234);
+    runner.block().timestamp = U256::from(5678);
+
+    let outcome = runner.call(CONTRACT, Tx::default());
+
+    assert_eq!(U256::from_be_slice(&outcome.output[..32]), U256::from(1234));
+    assert_eq!(U256::from_be_slice(&outcome.output[32..]), U256::from(5678));
+}
+
+#[test]
+fn test_halt_and_failed_deployment() {
+    let mut runner = Runner::new();
+    runner.install(CONTRACT, evm_asm!([0x00, "jump"]));
+
+    let outcome = runner.call(CONTRACT, Tx::default().gas_limit(100_000));
+    assert!(matches!(outcome.status, Status::Halt(_)), "{}", outcome);
+    assert_eq!(outcome.gas_used, 100_000);
+
+    let failed = runner.create(evm_asm!([0x00, 0x00, "revert"]), Tx::default());
+    assert_eq!(failed.status, Status::Revert);
+    assert_eq!(failed.created, None);
+}
@@ -0,0 +1,137 @@
+use crate::*;
+use crate::testing::{trace::MemoryWrite, Runner, Tracer, Tx};
+use emasm_common::source::parse_program;
+use revm::primitives::{Address, Bytes, U256};
+
+const CONTRACT: Address = Address::repeat_byte(0x42);
+
+/// Assemble a JSON program and install it, with a tracer that knows its layout
+fn install(runner: &mut Runner, address: Address, source: &str) -> Tracer {
+    let program = parse_program(source).unwrap();
+    let (code, layout) = Assembler::new().assemble_with_layout(&program).unwrap();
+    runner.install(address, code.clone());
+    Tracer::new().with_layout(&code, layout)
+}
+
+#[test]
+fn test_steps_map_to_element_paths() {
+    let mut runner = Runner::new();
+    let mut tracer = install(&mut runner, CONTRACT, r#"[
+        "0x02", "countdown", "jump",
+        ["countdown", ["dup1", "iszero", "done", "jumpi", "0x01", "swap1", "sub", "countdown", "jump"]],
+        ["done", ["stop"]]
+    ]"#);
+
+    let outcome = runner.trace_call(CONTRACT, Tx::default(), &mut tracer);
+    assert!(outcome.is_success(), "{}", outcome);
+    let trace = tracer.take_trace();
+
+    let lines: Vec<String> = trace.steps.iter().map(|step| step.to_string()).collect();
+    assert_eq!(lines[..5], [
+        "<root>+0: PUSH1 []",
+        "<root>+1: PUSH1 [0x2]",
+        "<root>+2: JUMP [0x5, 0x2]",
+        "countdown: JUMPDEST [0x2]",
+        "countdown+0: DUP1 [0x2]",
+    ]);
+    assert_eq!(lines.last().unwrap(), "done+0: STOP [0x0]");
+
+    // The loop body runs once per count and the check once more
+    assert_eq!(trace.at("countdown+0").count(), 3);
+    assert_eq!(trace.at("countdown+6").count(), 2);
+    let subs: Vec<Vec<U256>> = trace.at("countdown+6").map(|step| step.stack.clone()).collect();
+    assert_eq!(subs, [
+        vec![U256::from(2), U256::from(1)],
+        vec![U256::from(1), U256::from(1)],
+    ]);
+    assert!(tracer.trace().steps.is_empty());
+}
+
+#[test]
+fn test_gas_and_memory_writes() {
+    let mut runner = Runner::new();
+    let mut tracer = install(&mut runner, CONTRACT, r#"["0x2a", "0x20", "mstore", "0xff", "0x01", "mstore8", "stop"]"#);
+
+    runner.trace_call(CONTRACT, Tx::default(), &mut tracer);
+    let trace = tracer.trace();
+
+    let push = &trace.steps[0];
+    assert_eq!(push.gas_cost, 3);
+    assert_eq!(push.gas_remaining - trace.steps[1].gas_remaining, 3);
+    assert_eq!(push.memory, None);
+
+    // Two words of fresh memory: 3 for MSTORE and 6 for expanding
+    let mstore = &trace.steps[2];
+    assert_eq!(mstore.gas_cost, 9);
+    let mut word = [0u8; 32];
+    word[31] = 0x2a;
+    assert_eq!(mstore.memory, Some(MemoryWrite { offset: 0x20, data: Bytes::copy_from_slice(&word) }));
+
+    assert_eq!(trace.steps[5].memory, Some(MemoryWrite { offset: 1, data: Bytes::from(vec![0xff]) }));
+}
+
+#[test]
+fn test_nested_calls_and_unknown_code() {
+    let mut runner = Runner::new();
+    let callee = Address::repeat_byte(0x07);
+    runner.install(callee, evm_asm!(["stop"]));
+    let mut tracer = install(&mut runner, CONTRACT, r#"[
+        "0x00", "0x00", "0x00", "0x00", "0x0707070707070707070707070707070707070707", "gas", "staticcall", "stop"
+    ]"#);
+
+    runner.trace_call(CONTRACT, Tx::default(), &mut tracer);
+    let trace = tracer.trace();
+
+    let names: Vec<(usize, String)> = trace.steps.iter().map(|step| (step.depth, step.name())).collect();
+    assert_eq!(names[6..], [(0, "STATICCALL".to_string()), (1, "STOP".to_string()), (0, "STOP".to_string())]);
+
+    // Code without a layout is traced by PC and indented by depth
+    let inner = &trace.steps[7];
+    assert_eq!(inner.address, callee);
+    assert_eq!(inner.path, None);
+    assert_eq!(inner.to_string(), "pc 0x0: STOP []");
+    assert!(trace.to_string().contains("\n  pc 0x0: STOP []\n<root>+7: STOP [0x1]\n"));
+}
+
+#[test]
+fn test_trace_deployment() {
+    let mut runner = Runner::new();
+    let program = parse_program(r#"[
+        "bytes:runtime:size", "dup1", "bytes:runtime:ptr", "0x00", "codecopy", "0x00", "return",
+        ["bytes:runtime", "0x00"]
+    ]"#).unwrap();
+    let (initcode, layout) = Assembler::new().assemble_with_layout(&program).unwrap();
+    let mut tracer = Tracer::new().with_layout(&initcode, layout);
+
+    let outcome = runner.trace_create(initcode, Tx::default(), &mut tracer);
+    assert!(outcome.is_success(), "{}", outcome);
+
+    let trace = tracer.trace();
+    let codecopy = trace.at("<root>+4").next().unwrap();
+    assert_eq!(codecopy.address, outcome.created.unwrap());
+    assert_eq!(codecopy.memory, Some(MemoryWrite { offset: 0, data: Bytes::from(vec![0x00]) }));
+    assert_eq!(trace.steps.last().unwrap().name(), "RETURN");
+}
+
+#[test]
+fn test_json_trace() {
+    let mut runner = Runner::new();
+    let mut tracer = install(&mut runner, CONTRACT, r#"["0x01", "0x00", "mstore8", "stop"]"#);
+
+    runner.trace_call(CONTRACT, Tx::default(), &mut tracer);
+    let json = tracer.trace().to_json();
+
+    assert_eq!(json.as_array().unwrap().len(), 4);
+    assert_eq!(json[2], serde_json::json!({
+        "depth": 0,
+        "address": CONTRACT.to_string(),
+        "pc": 4,
+        "op": "MSTORE8",
+        "path": "<root>+2",
+        "stack": ["0x0", "0x1"],
+        "gas": json[2]["gas"],
+        "gasCost": 6,
+        "memory": { "offset": 0, "data": "0x01" },
+    }));
+    assert_eq!(json[3]["memory"], serde_json::Value::Null);
+}
@@ -0,0 +1,130 @@
+use crate::*;
+use super::{execute, lit, op};
+use emasm_common::{
+    source::parse_program,
+    verify::{jumpdest_analysis, VerifyIssueKind},
+};
+
+fn bytes(name: &str, data: &[u8]) -> AsmElement {
+    AsmElement::BytesSegment(name.to_string(), data.to_vec())
+}
+
+#[test]
+fn test_bytes_segment_copied_after_label_shrinking() {
+    // The jump label is first estimated wider than it ends up, so the data
+    // offset must be updated when labels shrink
+    let bytecode = evm_asm!([
+        "copy",
+        "jump",
+        ["copy", [
+            "bytes:data:size",
+            "bytes:data",
+            0x00,
+            "codecopy",
+            "bytes:data:size",
+            0x00,
+            "return"
+        ]],
+        ["bytes:data", ["0xdeadbeefcafebabe"]]
+    ]);
+
+    let output = execute(bytecode, &[]);
+    assert_eq!(output.as_ref(), hex::decode("deadbeefcafebabe").unwrap());
+}
+
+#[test]
+fn test_macro_verifies_on_request() {
+    // Falling into data only fails the build with verify = true
+    let unchecked = evm_asm!([0x01, ["bytes:data", ["0x00"]]]);
+    let checked = evm_asm!(verify = true, ["main", "jump", ["main", ["stop"]]]);
+    assert_eq!(hex::encode(unchecked), "600100");
+    assert_eq!(hex::encode(checked), "6003565b00");
+}
+
+#[test]
+fn test_layout_records_element_offsets() {
+    let elements = vec![
+        AsmElement::Literal(vec![0x12, 0x34]),
+        op("pop"),
+        AsmElement::Segment("end".to_string(), vec![op("stop")]),
+        bytes("data", &[0xaa, 0xbb]),
+    ];
+
+    let (bytecode, layout) = Assembler::new().assemble_with_layout(&elements).unwrap();
+    assert_eq!(bytecode, vec![0x61, 0x12, 0x34, 0x50, 0x5b, 0x00, 0xaa, 0xbb]);
+    assert_eq!(layout.labels["end"], 4);
+    assert_eq!(layout.bytes["data"].offset, 6);
+    assert_eq!(layout.entry_at(2).unwrap().path.to_string(), "<root>+0");
+    assert_eq!(layout.entry_at(4).unwrap().path.to_string(), "end");
+    assert_eq!(layout.entry_at(5).unwrap().path.to_string(), "end+0");
+    assert_eq!(layout.entry_at(7).unwrap().path.to_string(), "<root>+3");
+    assert!(layout.entry_at(8).is_none());
+}
+
+#[test]
+fn test_jumpdest_analysis_skips_push_data() {
+    // PUSH1 0x5b; JUMPDEST
+    assert_eq!(jumpdest_analysis(&[0x60, 0x5b, 0x5b]), vec![false, false, true]);
+}
+
+#[test]
+fn test_data_after_halt_is_accepted() {
+    let elements = vec![lit(0x00), lit(0x00), op("return"), bytes("data", &[0x01])];
+    let issues = Assembler::new().verify(&elements).unwrap();
+    assert!(issues.is_empty(), "unexpected issues: {:?}", issues);
+}
+
+#[test]
+fn test_fall_into_data() {
+    let elements = vec![lit(0x01), op("pop"), bytes("data", &[0x01])];
+    let issues = Assembler::new().verify(&elements).unwrap();
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(issues[0].to_string(), "<root>+2: execution falls into bytes segment data");
+}
+
+#[test]
+fn test_label_swallowed_by_push_in_data() {
+    // 0x61 decodes as PUSH2, hiding the JUMPDEST of `target`
+    let elements = vec![
+        AsmElement::Label("target".to_string()),
+        op("jump"),
+        bytes("data", &[0x61]),
+        AsmElement::Segment("target".to_string(), vec![op("stop")]),
+    ];
+
+    let issues = Assembler::new().verify(&elements).unwrap();
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(
+        issues[0].kind,
+        VerifyIssueKind::LabelNotJumpdest { label: "target".to_string(), offset: 4 }
+    );
+}
+
+#[test]
+fn test_bare_bytes_name_used_as_jump_target() {
+    let elements = parse_program(r#"["data", "jump", ["bytes:data", ["0x5b00"]]]"#).unwrap();
+    assert_eq!(elements[0], AsmElement::BytesPtr("data".to_string()));
+
+    let issues = Assembler::new().verify(&elements).unwrap();
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(
+        issues[0].kind,
+        VerifyIssueKind::JumpToData { opcode: "JUMP", label: "data".to_string() }
+    );
+    assert_eq!(issues[0].path.to_string(), "<root>+1");
+}
+
+#[test]
+fn test_literal_jump_target_traced_through_dup() {
+    // PUSH1 n; DUP1; JUMP; JUMPDEST; STOP
+    let program = |target| vec![lit(target), op("dup1"), op("jump"), op("jumpdest"), op("stop")];
+
+    let issues = Assembler::new().verify(&program(0x04)).unwrap();
+    assert!(issues.is_empty(), "unexpected issues: {:?}", issues);
+
+    let issues = Assembler::new().verify(&program(0x05)).unwrap();
+    assert_eq!(
+        issues.iter().map(|i| i.kind.clone()).collect::<Vec<_>>(),
+        vec![VerifyIssueKind::InvalidJumpTarget { opcode: "JUMP", target: 5 }]
+    );
+}